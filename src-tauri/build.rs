@@ -1,3 +1,87 @@
+use std::fs;
+use std::path::Path;
+
 fn main() {
-    tauri_build::build()
+    write_protocol_bindings();
+
+    // App commands the frontend may call; each one also has to be granted in
+    // capabilities/default.json.
+    tauri_build::try_build(tauri_build::Attributes::new().app_manifest(
+        tauri_build::AppManifest::new().commands(&[
+            "get_window_state",
+            "set_mode",
+            "toggle_mini",
+            "set_always_on_top_policy",
+            "get_window_settings",
+            "set_window_settings",
+            "get_shortcuts",
+            "set_shortcut",
+            "set_unread_channels",
+            "get_presence",
+            "set_presence",
+            "report_auto_presence",
+            "push_presence",
+            "get_profiles",
+            "save_profile",
+            "delete_profile",
+            "switch_profile",
+            "list_accounts",
+            "migrate_tokens",
+            "add_account",
+            "remove_account",
+            "switch_account",
+            "api_signup",
+            "api_login",
+            "api_verify_token",
+            "api_list_users",
+            "api_get_me",
+            "api_update_me",
+            "api_get_user",
+            "api_search_users",
+            "api_list_channels",
+            "api_create_channel",
+            "api_get_channel",
+            "api_join_channel",
+            "api_leave_channel",
+            "api_channel_members",
+            "api_join_by_invite_code",
+            "api_add_member",
+            "api_remove_member",
+            "api_create_invite",
+            "api_list_invites",
+            "api_revoke_invite",
+            "api_get_messages",
+            "api_send_message",
+            "api_mark_channel_read",
+            "api_get_or_create_dm",
+            "api_list_dms",
+            "ws_connect",
+            "ws_disconnect",
+            "ws_send",
+            "ws_latency",
+            "get_heartbeat_settings",
+            "set_heartbeat_settings",
+            "outbox_send",
+            "outbox_list",
+            "outbox_retry",
+            "outbox_discard",
+            "history_cached",
+            "history_page",
+            "search_messages",
+            "export_channel",
+            "import_pick_archive",
+            "import_history",
+        ]),
+    ))
+    .expect("failed to run tauri-build");
+}
+
+/// Render the wire types for the frontend. Only written when the output changes,
+/// so the dev server does not reload on every build.
+fn write_protocol_bindings() {
+    let path = Path::new("../src/lib/protocol.ts");
+    let bindings = closechat_protocol::typescript();
+    if fs::read_to_string(path).ok().as_deref() != Some(bindings.as_str()) {
+        fs::write(path, bindings).expect("failed to write src/lib/protocol.ts");
+    }
 }
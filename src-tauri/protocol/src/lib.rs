@@ -0,0 +1,55 @@
+//! Wire protocol of the close-chat server: REST payloads and WebSocket frames.
+//!
+//! The app's build script renders these types to `src/lib/protocol.ts`, so the
+//! frontend and the Rust client share one definition.
+
+pub mod rest;
+pub mod ws;
+
+#[cfg(test)]
+mod tests;
+
+/// TypeScript declarations for every wire type, one `export type` each.
+#[cfg(feature = "ts")]
+pub fn typescript() -> String {
+    use ts_rs::TS;
+
+    let decls = [
+        rest::User::decl(),
+        rest::AuthResponse::decl(),
+        rest::ChannelKind::decl(),
+        rest::MemberRole::decl(),
+        rest::ChannelLastMessage::decl(),
+        rest::DmRecipient::decl(),
+        rest::Channel::decl(),
+        rest::DmChannel::decl(),
+        rest::ChannelMember::decl(),
+        rest::InviteCreator::decl(),
+        rest::Invite::decl(),
+        rest::MessageKind::decl(),
+        rest::Message::decl(),
+        rest::MessagePage::decl(),
+        rest::DmResult::decl(),
+        rest::UpdateMe::decl(),
+        rest::InviteOptions::decl(),
+        rest::MessagesQuery::decl(),
+        rest::SignupRequest::decl(),
+        rest::LoginRequest::decl(),
+        rest::CreateChannelRequest::decl(),
+        rest::AddMemberRequest::decl(),
+        rest::SendMessageRequest::decl(),
+        ws::FrameUser::decl(),
+        ws::IncomingFrame::decl(),
+        ws::OutgoingFrame::decl(),
+    ];
+    let mut out =
+        String::from("// Generated from src-tauri/protocol by src-tauri/build.rs. Do not edit.\n");
+    for decl in decls {
+        // ts-rs maps i64 to bigint, but ids arrive as plain JSON numbers and stay
+        // well below 2^53.
+        out.push_str("\nexport ");
+        out.push_str(&decl.replace("bigint", "number"));
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,219 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Debug;
+
+use crate::rest::*;
+use crate::ws::*;
+
+/// Every field of `expected` must be in `actual` with the same value. Extra
+/// fields in `actual` may only be `null`, i.e. options the sample left out.
+fn assert_covers(actual: &Value, expected: &Value, at: &str) {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            for (key, value) in expected {
+                let path = format!("{at}.{key}");
+                let found = actual.get(key).unwrap_or_else(|| panic!("{path} dropped"));
+                assert_covers(found, value, &path);
+            }
+            for (key, value) in actual {
+                assert!(
+                    expected.contains_key(key) || value.is_null(),
+                    "{at}.{key} = {value} was not in the sample"
+                );
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            assert_eq!(actual.len(), expected.len(), "{at} length");
+            for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+                assert_covers(a, e, &format!("{at}[{i}]"));
+            }
+        }
+        _ => assert_eq!(actual, expected, "{at}"),
+    }
+}
+
+/// Parse the sample, write it back out and parse that again.
+fn round_trip<T>(sample: Value) -> T
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let parsed: T = serde_json::from_value(sample.clone()).expect("sample should parse");
+    let written = serde_json::to_value(&parsed).unwrap();
+    assert_covers(&written, &sample, "$");
+    let reparsed: T = serde_json::from_value(written).unwrap();
+    assert_eq!(reparsed, parsed);
+    parsed
+}
+
+#[test]
+fn incoming_message() {
+    let frame = round_trip::<IncomingFrame>(json!({
+        "type": "message", "id": 10, "channelId": 3, "senderId": 1,
+        "senderUsername": "alice", "content": "hi", "messageType": "user",
+        "imageUrl": null, "timestamp": "2024-05-01T10:00:00Z",
+        "createdAt": "2024-05-01T10:00:00Z"
+    }));
+    assert!(matches!(
+        frame,
+        IncomingFrame::Message {
+            channel_id: 3,
+            message_type: Some(MessageKind::User),
+            ..
+        }
+    ));
+    // Older servers send only the essentials
+    round_trip::<IncomingFrame>(json!({
+        "type": "message", "id": 11, "channelId": 3, "senderId": 2
+    }));
+}
+
+#[test]
+fn incoming_presence_and_membership() {
+    round_trip::<IncomingFrame>(json!({ "type": "connected", "userId": 1, "username": "alice" }));
+    let joined = round_trip::<IncomingFrame>(json!({
+        "type": "user-joined", "channelId": 3,
+        "user": { "id": 2, "username": "bob" }, "username": "bob"
+    }));
+    assert!(matches!(
+        joined,
+        IncomingFrame::UserJoined {
+            user: Some(FrameUser { id: Some(2), .. }),
+            ..
+        }
+    ));
+    round_trip::<IncomingFrame>(json!({ "type": "user-left", "channelId": 3, "userId": 2 }));
+    round_trip::<IncomingFrame>(json!({
+        "type": "status-changed", "userId": 2, "status": "idle"
+    }));
+    round_trip::<IncomingFrame>(json!({ "type": "joined-channel", "channelId": 3 }));
+    round_trip::<IncomingFrame>(json!({ "type": "channel_update", "channelId": 3 }));
+}
+
+#[test]
+fn incoming_typing_and_errors() {
+    round_trip::<IncomingFrame>(json!({
+        "type": "user-typing", "channelId": 3, "userId": 2, "username": "bob"
+    }));
+    round_trip::<IncomingFrame>(json!({
+        "type": "user-stopped-typing", "channelId": 3, "userId": 2
+    }));
+    round_trip::<IncomingFrame>(json!({ "type": "error", "message": "not a member" }));
+    let legacy: IncomingFrame =
+        serde_json::from_value(json!({ "type": "error", "error": "bad frame" })).unwrap();
+    assert_eq!(
+        legacy,
+        IncomingFrame::Error {
+            message: Some("bad frame".into())
+        }
+    );
+}
+
+#[test]
+fn unknown_incoming_types_are_tolerated() {
+    let frame: IncomingFrame =
+        serde_json::from_value(json!({ "type": "reaction-added", "emoji": "+1" })).unwrap();
+    assert_eq!(frame, IncomingFrame::Unknown);
+    // Known types still have to be well formed
+    assert!(serde_json::from_value::<IncomingFrame>(json!({ "type": "user-left" })).is_err());
+}
+
+#[test]
+fn outgoing_frames() {
+    for sample in [
+        json!({ "type": "message", "channelId": 3, "content": "hello" }),
+        json!({ "type": "typing-start", "channelId": 3 }),
+        json!({ "type": "typing-stop", "channelId": 3 }),
+        json!({ "type": "join-channel", "channelId": 3 }),
+        json!({ "type": "leave-channel", "channelId": 3 }),
+        json!({ "type": "status-update", "status": "dnd" }),
+        json!({ "type": "mark-read", "channelId": 3, "messageId": 10 }),
+    ] {
+        round_trip::<OutgoingFrame>(sample);
+    }
+    assert_eq!(
+        serde_json::to_value(OutgoingFrame::MarkRead {
+            channel_id: 3,
+            message_id: 10
+        })
+        .unwrap(),
+        json!({ "type": "mark-read", "channelId": 3, "messageId": 10 })
+    );
+}
+
+#[test]
+fn users_and_auth() {
+    let user = json!({
+        "id": 1, "username": "alice", "email": "a@example.com", "status": "online",
+        "isBot": false, "lastSeen": "2024-05-01T10:00:00Z", "createdAt": "2024-04-01T10:00:00Z"
+    });
+    round_trip::<User>(user.clone());
+    round_trip::<AuthResponse>(json!({ "user": user, "token": "t0k" }));
+    round_trip::<UpdateMe>(json!({ "status": "dnd" }));
+    round_trip::<SignupRequest>(json!({
+        "username": "alice", "email": "a@example.com", "password": "hunter2"
+    }));
+    round_trip::<LoginRequest>(json!({ "username": "alice", "password": "hunter2" }));
+}
+
+#[test]
+fn channels_and_members() {
+    let channel = round_trip::<Channel>(json!({
+        "id": 3, "name": "general", "type": "channel", "createdBy": 1,
+        "createdAt": "2024-04-01T10:00:00Z", "unreadCount": 2, "members": [1, 2],
+        "role": "admin",
+        "lastMessage": { "content": "hi", "senderId": 1, "senderUsername": "alice",
+                         "createdAt": "2024-05-01T10:00:00Z" }
+    }));
+    assert_eq!(channel.kind, ChannelKind::Channel);
+    let recipient = json!({ "id": 2, "username": "bob", "status": "idle" });
+    let dm = json!({
+        "id": 9, "name": "dm-1-2", "type": "dm", "lastMessage": null, "unreadCount": 0,
+        "recipient": recipient
+    });
+    assert_eq!(round_trip::<DmChannel>(dm.clone()).kind, ChannelKind::Dm);
+    round_trip::<DmResult>(json!({ "channel": dm, "created": true }));
+    round_trip::<ChannelMember>(json!({
+        "id": 2, "username": "bob", "status": "idle", "isBot": true, "role": "member",
+        "joinedAt": "2024-04-01T10:00:00Z"
+    }));
+    round_trip::<CreateChannelRequest>(json!({ "name": "ops", "type": "channel", "members": [2] }));
+    round_trip::<AddMemberRequest>(json!({ "userId": 5 }));
+}
+
+#[test]
+fn invites() {
+    let inline = round_trip::<Invite>(json!({
+        "id": 1, "code": "x", "createdBy": { "id": 1, "username": "alice" },
+        "maxUses": null, "uses": 0, "expiresAt": null, "createdAt": "2024-05-01T10:00:00Z"
+    }));
+    assert!(matches!(
+        inline.created_by,
+        InviteCreator::User { id: 1, .. }
+    ));
+    let by_id = round_trip::<Invite>(json!({
+        "id": 2, "code": "y", "channelId": 4, "createdBy": 1, "maxUses": 5, "uses": 2,
+        "expiresAt": "2024-05-02T10:00:00Z", "isActive": false,
+        "createdAt": "2024-05-01T10:00:00Z"
+    }));
+    assert_eq!(by_id.created_by, InviteCreator::Id(1));
+    round_trip::<InviteOptions>(json!({ "maxUses": 5, "expiresInHours": 24 }));
+}
+
+#[test]
+fn messages() {
+    for kind in ["user", "bot", "system"] {
+        round_trip::<Message>(json!({
+            "id": 1, "channelId": 3, "senderId": 1, "senderUsername": "alice",
+            "content": null, "type": kind, "createdAt": "2024-04-30T09:00:00Z"
+        }));
+    }
+    round_trip::<MessagePage>(json!({
+        "messages": [{ "id": 1, "channelId": 3, "senderId": 1, "senderUsername": "alice",
+                       "content": "hi", "type": "user", "imageUrl": "/img/1.png",
+                       "createdAt": "2024-04-30T09:00:00Z" }],
+        "hasMore": true
+    }));
+    round_trip::<MessagesQuery>(json!({ "limit": 50, "before": "2024-05-01T10:00:00Z" }));
+    round_trip::<SendMessageRequest>(json!({ "content": "hello", "type": "bot" }));
+    round_trip::<SendMessageRequest>(json!({ "content": "hello" }));
+}
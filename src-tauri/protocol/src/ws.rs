@@ -0,0 +1,125 @@
+//! Frames exchanged over `/ws`. Both directions are flat JSON objects tagged by
+//! `type`.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "ts")]
+use ts_rs::TS;
+
+use crate::rest::MessageKind;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS))]
+#[serde(rename_all = "camelCase")]
+pub struct FrameUser {
+    pub id: Option<i64>,
+    pub username: String,
+}
+
+/// Frames the server sends. Unknown types are kept as [`IncomingFrame::Unknown`]
+/// so a newer server does not break older clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS))]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum IncomingFrame {
+    #[serde(rename_all = "camelCase")]
+    Message {
+        id: i64,
+        channel_id: i64,
+        sender_id: i64,
+        #[serde(default)]
+        sender_username: Option<String>,
+        #[serde(default)]
+        content: Option<String>,
+        #[serde(default)]
+        message_type: Option<MessageKind>,
+        #[serde(default)]
+        image_url: Option<String>,
+        #[serde(default)]
+        timestamp: Option<String>,
+        #[serde(default)]
+        created_at: Option<String>,
+    },
+    /// Server accepted the token.
+    #[serde(rename_all = "camelCase")]
+    Connected {
+        #[serde(default)]
+        user_id: Option<i64>,
+        #[serde(default)]
+        username: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    UserJoined {
+        channel_id: i64,
+        #[serde(default)]
+        user: Option<FrameUser>,
+        #[serde(default)]
+        username: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    UserLeft {
+        channel_id: i64,
+        #[serde(default)]
+        user_id: Option<i64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    StatusChanged { user_id: i64, status: String },
+    #[serde(rename_all = "camelCase")]
+    UserTyping {
+        channel_id: i64,
+        user_id: i64,
+        #[serde(default)]
+        username: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    UserStoppedTyping { channel_id: i64, user_id: i64 },
+    #[serde(rename_all = "camelCase")]
+    JoinedChannel { channel_id: i64 },
+    #[serde(rename = "channel_update", rename_all = "camelCase")]
+    ChannelUpdate {
+        #[serde(default)]
+        channel_id: Option<i64>,
+    },
+    Error {
+        #[serde(default, alias = "error")]
+        message: Option<String>,
+    },
+    #[serde(other)]
+    #[cfg_attr(feature = "ts", ts(skip))]
+    Unknown,
+}
+
+/// Frames the client sends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(TS))]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum OutgoingFrame {
+    #[serde(rename_all = "camelCase")]
+    Message {
+        channel_id: i64,
+        content: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    TypingStart {
+        channel_id: i64,
+    },
+    #[serde(rename_all = "camelCase")]
+    TypingStop {
+        channel_id: i64,
+    },
+    #[serde(rename_all = "camelCase")]
+    JoinChannel {
+        channel_id: i64,
+    },
+    #[serde(rename_all = "camelCase")]
+    LeaveChannel {
+        channel_id: i64,
+    },
+    StatusUpdate {
+        status: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    MarkRead {
+        channel_id: i64,
+        message_id: i64,
+    },
+}
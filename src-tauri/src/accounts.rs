@@ -0,0 +1,543 @@
+//! Signed-in accounts. Each one has its own REST client, WebSocket and unread
+//! counts and stays connected in the background; the webview shows the active one.
+//! Tokens live in [`Credentials`] and never reach the webview; settings only
+//! record which accounts to restore.
+
+use closechat_protocol::ws::IncomingFrame;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::api::models::{Channel, ChannelKind, DmChannel, User};
+use crate::api::{ApiClient, ApiError};
+use crate::credentials::Credentials;
+use crate::error::{Error, Result};
+use crate::history::HistoryDb;
+use crate::outbox;
+use crate::profiles::{self, ConnectionProfile};
+use crate::settings::SettingsStore;
+use crate::tray::{self, UnreadChannel};
+use crate::ws::{self, WsClient};
+
+/// Emitted with an [`AccountsView`] when an account is added, removed or switched
+/// to, or its unread total changes.
+pub const ACCOUNTS_CHANGED_EVENT: &str = "accounts-changed";
+
+/// Emitted with the new active [`AccountView`], or `null` for the sign-in screen,
+/// when the switch came from outside the webview. The frontend reloads.
+pub const ACCOUNT_SWITCHED_EVENT: &str = "account-switched";
+
+/// Accounts are keyed by profile and user, so the same user on two servers is two
+/// accounts.
+pub fn account_id(profile: &str, user_id: i64) -> String {
+    format!("{profile}:{user_id}")
+}
+
+/// An account to restore on the next start. Its token is in [`Credentials`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedAccount {
+    pub id: String,
+    pub profile: String,
+}
+
+pub struct Session {
+    pub id: String,
+    pub api: Arc<ApiClient>,
+    pub ws: WsClient,
+    user: User,
+    /// Every known channel, including those with nothing unread.
+    unread: Mutex<Vec<UnreadChannel>>,
+}
+
+impl Session {
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Every known channel and DM, in server order.
+    pub fn channels(&self) -> Vec<UnreadChannel> {
+        self.unread.lock().unwrap().clone()
+    }
+
+    /// Channels with something unread, in server order.
+    pub fn unread(&self) -> Vec<UnreadChannel> {
+        let unread = self.unread.lock().unwrap();
+        unread.iter().filter(|c| c.count > 0).cloned().collect()
+    }
+
+    pub fn total_unread(&self) -> u32 {
+        self.unread.lock().unwrap().iter().map(|c| c.count).sum()
+    }
+
+    /// Replace the counts; `false` if nothing changed.
+    pub fn set_unread(&self, channels: Vec<UnreadChannel>) -> bool {
+        let mut unread = self.unread.lock().unwrap();
+        if *unread == channels {
+            return false;
+        }
+        *unread = channels;
+        true
+    }
+
+    pub fn view(&self) -> AccountView {
+        AccountView {
+            id: self.id.clone(),
+            profile: self.api.profile(),
+            user: self.user.clone(),
+            unread: self.total_unread(),
+            connected: self.ws.is_connected(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountView {
+    pub id: String,
+    pub profile: ConnectionProfile,
+    pub user: User,
+    pub unread: u32,
+    pub connected: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountsView {
+    pub accounts: Vec<AccountView>,
+    pub active: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Accounts {
+    sessions: RwLock<Vec<Arc<Session>>>,
+    active: RwLock<Option<String>>,
+    restored: tokio::sync::OnceCell<()>,
+}
+
+impl Accounts {
+    pub fn all(&self) -> Vec<Arc<Session>> {
+        self.sessions.read().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let sessions = self.sessions.read().unwrap();
+        sessions.iter().find(|session| session.id == id).cloned()
+    }
+
+    pub fn active(&self) -> Option<Arc<Session>> {
+        let id = self.active.read().unwrap().clone()?;
+        self.get(&id)
+    }
+
+    /// The active account's client, for commands acting on behalf of the webview.
+    pub fn api(&self) -> Result<Arc<ApiClient>> {
+        self.active()
+            .map(|session| session.api.clone())
+            .ok_or(Error::SignedOut)
+    }
+
+    pub fn view(&self) -> AccountsView {
+        AccountsView {
+            accounts: self.all().iter().map(|session| session.view()).collect(),
+            active: self.active.read().unwrap().clone(),
+        }
+    }
+}
+
+/// The tray and the account list both show per-account totals.
+pub fn changed(app: &AppHandle) {
+    tray::refresh_menu(app);
+    let _ = app.emit(ACCOUNTS_CHANGED_EVENT, app.state::<Accounts>().view());
+}
+
+/// Check `token` against `profile`, keep it in [`Credentials`] and start a
+/// session for it, or hand back the running one for the same user. The account
+/// remembered as active from the last run becomes active again when it is restored.
+pub async fn add(
+    app: &AppHandle,
+    profile: &ConnectionProfile,
+    token: String,
+) -> Result<Arc<Session>> {
+    let api = Arc::new(ApiClient::new(profile)?);
+    api.set_token(Some(token.clone()));
+    let user = api.verify_token().await?;
+    let id = account_id(&profile.name, user.id);
+    app.state::<Credentials>().set(&id, &token).await?;
+    let saved = SavedAccount {
+        id: id.clone(),
+        profile: profile.name.clone(),
+    };
+    app.state::<SettingsStore>().update(|settings| {
+        if !settings.accounts.contains(&saved) {
+            settings.accounts.push(saved);
+        }
+    });
+
+    let accounts = app.state::<Accounts>();
+    let session = {
+        let mut sessions = accounts.sessions.write().unwrap();
+        if let Some(existing) = sessions.iter().find(|session| session.id == id) {
+            existing.api.set_token(api.token());
+            return Ok(existing.clone());
+        }
+        let session = Arc::new(Session {
+            ws: ws::spawn(app, id.clone(), api.clone()),
+            id: id.clone(),
+            api,
+            user,
+            unread: Mutex::default(),
+        });
+        sessions.push(session.clone());
+        session
+    };
+    {
+        let remembered = app.state::<SettingsStore>().get().active_account;
+        let mut active = accounts.active.write().unwrap();
+        if active.is_none() && remembered.as_deref() == Some(id.as_str()) {
+            *active = Some(id);
+        }
+    }
+    session.ws.connect();
+    load_unread(app, session.clone());
+    changed(app);
+    Ok(session)
+}
+
+/// Make `id` the account the webview shows, or none for the sign-in screen.
+/// `notify` tells the frontend to reload; leave it off when the webview asked.
+pub fn switch(app: &AppHandle, id: Option<String>, notify: bool) -> Result<()> {
+    let accounts = app.state::<Accounts>();
+    let session = match &id {
+        Some(id) => Some(
+            accounts
+                .get(id)
+                .ok_or_else(|| Error::NotFound(format!("no account {id}")))?,
+        ),
+        None => None,
+    };
+    *accounts.active.write().unwrap() = id.clone();
+    app.state::<SettingsStore>()
+        .update(|settings| settings.active_account = id);
+    if let Some(session) = &session {
+        profiles::select(app, session.api.profile());
+    }
+    changed(app);
+    if notify {
+        let _ = app.emit(
+            ACCOUNT_SWITCHED_EVENT,
+            session.map(|session| session.view()),
+        );
+    }
+    Ok(())
+}
+
+/// Drop a saved account and its token, e.g. once the server rejects the token.
+async fn forget(app: &AppHandle, id: &str) -> Result<()> {
+    app.state::<SettingsStore>()
+        .update(|settings| settings.accounts.retain(|saved| saved.id != id));
+    app.state::<Credentials>().delete(id).await?;
+    outbox::forget_account(app, id);
+    app.state::<HistoryDb>().forget_account(id)?;
+    Ok(())
+}
+
+/// Forget the saved accounts on `profile`, which is being deleted. None of them
+/// has a session by then, so these are ones [`restore`] skipped.
+pub(crate) async fn forget_profile(app: &AppHandle, profile: &str) -> Result<()> {
+    let saved = app.state::<SettingsStore>().get().accounts;
+    for account in saved.iter().filter(|saved| saved.profile == profile) {
+        forget(app, &account.id).await?;
+    }
+    Ok(())
+}
+
+/// Sign an account out, drop its session and forget its token. If it was active,
+/// the next account takes over, or the sign-in screen if there is none. Nothing
+/// is forgotten for an id without a session.
+pub async fn remove(app: &AppHandle, id: &str) -> Result<()> {
+    let accounts = app.state::<Accounts>();
+    let removed = {
+        let mut sessions = accounts.sessions.write().unwrap();
+        let index = sessions
+            .iter()
+            .position(|session| session.id == id)
+            .ok_or_else(|| Error::NotFound(format!("no account {id}")))?;
+        sessions.remove(index)
+    };
+    removed.ws.disconnect();
+    let forgotten = forget(app, id).await;
+    let was_active = accounts.active.read().unwrap().as_deref() == Some(id);
+    if was_active {
+        let next = accounts.all().first().map(|next| next.id.clone());
+        switch(app, next, true)?;
+    } else {
+        changed(app);
+    }
+    forgotten
+}
+
+/// Start sessions for the accounts saved last time. Runs once, from setup; callers
+/// that need the result wait for it. Tokens the server rejects are forgotten. The
+/// rest are kept and tried again on the next start, including accounts whose
+/// profile was only given on the command line or whose token could not be read.
+pub async fn restore(app: &AppHandle) {
+    let accounts = app.state::<Accounts>();
+    accounts
+        .restored
+        .get_or_init(|| async {
+            for saved in app.state::<SettingsStore>().get().accounts {
+                if let Err(err) = restore_one(app, &saved).await {
+                    eprintln!("could not restore {}: {err}", saved.id);
+                }
+            }
+            activate_any(app);
+        })
+        .await;
+}
+
+async fn restore_one(app: &AppHandle, saved: &SavedAccount) -> Result<()> {
+    let profile = profiles::lookup(app, &saved.profile)
+        .ok_or_else(|| Error::NotFound(format!("no profile named {}", saved.profile)))?;
+    let token = app
+        .state::<Credentials>()
+        .get(&saved.id)
+        .await?
+        .ok_or_else(|| Error::NotFound(format!("no stored token for {}", saved.id)))?;
+    match add(app, &profile, token).await {
+        Err(Error::Api(ApiError::Unauthorized(_))) => forget(app, &saved.id).await,
+        result => result.map(|_| ()),
+    }
+}
+
+/// Nothing remembered as active, e.g. right after migrating: show the first account.
+fn activate_any(app: &AppHandle) {
+    let accounts = app.state::<Accounts>();
+    if accounts.active().is_some() {
+        return;
+    }
+    if let Some(first) = accounts.all().first() {
+        let _ = switch(app, Some(first.id.clone()), false);
+    }
+}
+
+/// Fetch the account's channels and DMs to start the unread counts from.
+fn load_unread(app: &AppHandle, session: Arc<Session>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let (channels, dms) = tokio::join!(session.api.list_channels(), session.api.list_dms());
+        match (channels, dms) {
+            (Ok(channels), Ok(dms)) => {
+                if session.set_unread(unread_from(&channels, &dms)) {
+                    changed(&app);
+                }
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                eprintln!("failed to load unread counts for {}: {err}", session.id);
+            }
+        }
+    });
+}
+
+fn unread_from(channels: &[Channel], dms: &[DmChannel]) -> Vec<UnreadChannel> {
+    let mut unread: Vec<UnreadChannel> = channels
+        .iter()
+        .map(|channel| UnreadChannel {
+            id: channel.id,
+            name: match (&channel.kind, &channel.recipient) {
+                (ChannelKind::Dm, Some(recipient)) => recipient.username.clone(),
+                _ => channel.name.clone(),
+            },
+            kind: channel.kind,
+            count: channel.unread_count,
+        })
+        .collect();
+    for dm in dms {
+        if unread.iter().all(|channel| channel.id != dm.id) {
+            unread.push(UnreadChannel {
+                id: dm.id,
+                name: dm.recipient.username.clone(),
+                kind: dm.kind,
+                count: dm.unread_count,
+            });
+        }
+    }
+    unread
+}
+
+/// Count a message towards its channel. `false` if the channel is not known yet.
+fn count_message(unread: &mut [UnreadChannel], channel_id: i64) -> bool {
+    match unread.iter_mut().find(|channel| channel.id == channel_id) {
+        Some(channel) => {
+            channel.count += 1;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Called by the socket task for every frame. The webview keeps the active
+/// account's counts itself and reports them through `set_unread_channels`;
+/// background accounts are counted here.
+pub fn on_frame(app: &AppHandle, account: &str, frame: &IncomingFrame) {
+    let IncomingFrame::Message {
+        channel_id,
+        sender_id,
+        ..
+    } = frame
+    else {
+        return;
+    };
+    let accounts = app.state::<Accounts>();
+    if accounts.active.read().unwrap().as_deref() == Some(account) {
+        return;
+    }
+    let Some(session) = accounts.get(account) else {
+        return;
+    };
+    if *sender_id == session.user.id {
+        return;
+    }
+    let known = count_message(&mut session.unread.lock().unwrap(), *channel_id);
+    if known {
+        changed(app);
+    } else {
+        // A channel or DM created since the last fetch
+        load_unread(app, session);
+    }
+}
+
+/// Add a session for `token` on `profile` and, if asked, show it without a reload.
+pub async fn sign_in(
+    app: &AppHandle,
+    profile: &ConnectionProfile,
+    token: String,
+    activate: bool,
+) -> Result<AccountView> {
+    let session = add(app, profile, token).await?;
+    if activate {
+        switch(app, Some(session.id.clone()), false)?;
+    }
+    Ok(session.view())
+}
+
+/// Waits for the accounts from the last run to be restored.
+#[tauri::command]
+pub async fn list_accounts(app: AppHandle) -> Result<AccountsView> {
+    restore(&app).await;
+    Ok(app.state::<Accounts>().view())
+}
+
+/// A token the webview kept in localStorage before tokens moved to Rust.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyToken {
+    /// The localStorage key, handed back once the token is dealt with.
+    pub key: String,
+    pub profile: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationView {
+    pub accounts: AccountsView,
+    /// Keys the webview can delete: migrated, or rejected by the server.
+    pub settled: Vec<String>,
+}
+
+/// Move tokens out of localStorage into [`Credentials`], once per old install.
+#[tauri::command]
+pub async fn migrate_tokens(app: AppHandle, tokens: Vec<LegacyToken>) -> Result<MigrationView> {
+    restore(&app).await;
+    let mut settled = Vec::new();
+    for legacy in tokens {
+        let Some(profile) = profiles::lookup(&app, &legacy.profile) else {
+            settled.push(legacy.key);
+            continue;
+        };
+        match add(&app, &profile, legacy.token).await {
+            Ok(_) | Err(Error::Api(ApiError::Unauthorized(_))) => settled.push(legacy.key),
+            Err(err) => eprintln!("could not migrate the token for {}: {err}", legacy.profile),
+        }
+    }
+    activate_any(&app);
+    Ok(MigrationView {
+        accounts: app.state::<Accounts>().view(),
+        settled,
+    })
+}
+
+/// Sign in with a token issued elsewhere, e.g. for a bot, on the profile named
+/// `profile`.
+#[tauri::command]
+pub async fn add_account(
+    app: AppHandle,
+    profile: String,
+    token: String,
+    activate: bool,
+) -> Result<AccountView> {
+    let profile = profiles::lookup(&app, &profile)
+        .ok_or_else(|| Error::NotFound(format!("no profile named {profile}")))?;
+    sign_in(&app, &profile, token, activate).await
+}
+
+#[tauri::command]
+pub async fn remove_account(app: AppHandle, id: String) -> Result<AccountsView> {
+    remove(&app, &id).await?;
+    Ok(app.state::<Accounts>().view())
+}
+
+/// `None` shows the sign-in screen, to add another account.
+#[tauri::command]
+pub fn switch_account(app: AppHandle, id: Option<String>) -> Result<AccountsView> {
+    switch(&app, id, true)?;
+    Ok(app.state::<Accounts>().view())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::models::DmRecipient;
+
+    fn channel(id: i64, name: &str, unread_count: u32) -> Channel {
+        serde_json::from_value(serde_json::json!({
+            "id": id, "name": name, "type": "channel", "unreadCount": unread_count
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn unread_counts_start_from_channels_and_dms() {
+        let dm = DmChannel {
+            id: 9,
+            name: "dm-1-2".into(),
+            kind: ChannelKind::Dm,
+            recipient: DmRecipient {
+                id: 2,
+                username: "bob".into(),
+                status: "online".into(),
+            },
+            last_message: None,
+            unread_count: 2,
+        };
+        let mut unread = unread_from(&[channel(3, "general", 0), channel(4, "ops", 1)], &[dm]);
+        assert_eq!(
+            unread
+                .iter()
+                .map(|c| (c.id, c.name.as_str(), c.count))
+                .collect::<Vec<_>>(),
+            [(3, "general", 0), (4, "ops", 1), (9, "bob", 2)]
+        );
+
+        assert!(count_message(&mut unread, 3));
+        assert!(!count_message(&mut unread, 10));
+        assert_eq!(unread.iter().map(|c| c.count).sum::<u32>(), 4);
+    }
+
+    #[test]
+    fn account_ids_include_the_profile() {
+        assert_eq!(account_id("default", 7), "default:7");
+        assert_ne!(account_id("work", 7), account_id("default", 7));
+    }
+}
@@ -0,0 +1,200 @@
+//! Every [`ApiClient`] endpoint as a Tauri command, named `api_<method>`. Signing
+//! up and in go to the selected profile and add an account; everything else acts
+//! as the active account. Tokens stay on this side.
+
+use tauri::{AppHandle, Manager, State};
+
+use super::models::*;
+use super::ApiClient;
+use crate::accounts::{self, AccountView, Accounts};
+use crate::error::Result;
+use crate::profiles::SelectedProfile;
+
+#[tauri::command]
+pub async fn api_signup(
+    app: AppHandle,
+    username: String,
+    email: String,
+    password: String,
+) -> Result<AccountView> {
+    let profile = app.state::<SelectedProfile>().get();
+    let auth = ApiClient::new(&profile)?
+        .signup(&username, &email, &password)
+        .await?;
+    accounts::sign_in(&app, &profile, auth.token, true).await
+}
+
+#[tauri::command]
+pub async fn api_login(app: AppHandle, username: String, password: String) -> Result<AccountView> {
+    let profile = app.state::<SelectedProfile>().get();
+    let auth = ApiClient::new(&profile)?
+        .login(&username, &password)
+        .await?;
+    accounts::sign_in(&app, &profile, auth.token, true).await
+}
+
+#[tauri::command]
+pub async fn api_verify_token(accounts: State<'_, Accounts>) -> Result<User> {
+    Ok(accounts.api()?.verify_token().await?)
+}
+
+#[tauri::command]
+pub async fn api_list_users(accounts: State<'_, Accounts>) -> Result<Vec<User>> {
+    Ok(accounts.api()?.list_users().await?)
+}
+
+#[tauri::command]
+pub async fn api_get_me(accounts: State<'_, Accounts>) -> Result<User> {
+    Ok(accounts.api()?.get_me().await?)
+}
+
+#[tauri::command]
+pub async fn api_update_me(accounts: State<'_, Accounts>, update: UpdateMe) -> Result<User> {
+    Ok(accounts.api()?.update_me(&update).await?)
+}
+
+#[tauri::command]
+pub async fn api_get_user(accounts: State<'_, Accounts>, id: i64) -> Result<User> {
+    Ok(accounts.api()?.get_user(id).await?)
+}
+
+#[tauri::command]
+pub async fn api_search_users(accounts: State<'_, Accounts>, query: String) -> Result<Vec<User>> {
+    Ok(accounts.api()?.search_users(&query).await?)
+}
+
+#[tauri::command]
+pub async fn api_list_channels(accounts: State<'_, Accounts>) -> Result<Vec<Channel>> {
+    Ok(accounts.api()?.list_channels().await?)
+}
+
+#[tauri::command]
+pub async fn api_create_channel(
+    accounts: State<'_, Accounts>,
+    name: String,
+    kind: ChannelKind,
+    members: Option<Vec<i64>>,
+) -> Result<Channel> {
+    Ok(accounts
+        .api()?
+        .create_channel(&name, kind, members.as_deref())
+        .await?)
+}
+
+#[tauri::command]
+pub async fn api_get_channel(accounts: State<'_, Accounts>, id: i64) -> Result<Channel> {
+    Ok(accounts.api()?.get_channel(id).await?)
+}
+
+#[tauri::command]
+pub async fn api_join_channel(accounts: State<'_, Accounts>, id: i64) -> Result<()> {
+    Ok(accounts.api()?.join_channel(id).await?)
+}
+
+#[tauri::command]
+pub async fn api_leave_channel(accounts: State<'_, Accounts>, id: i64) -> Result<()> {
+    Ok(accounts.api()?.leave_channel(id).await?)
+}
+
+#[tauri::command]
+pub async fn api_channel_members(
+    accounts: State<'_, Accounts>,
+    id: i64,
+) -> Result<Vec<ChannelMember>> {
+    Ok(accounts.api()?.channel_members(id).await?)
+}
+
+#[tauri::command]
+pub async fn api_join_by_invite_code(
+    accounts: State<'_, Accounts>,
+    code: String,
+) -> Result<Channel> {
+    Ok(accounts.api()?.join_by_invite_code(&code).await?)
+}
+
+#[tauri::command]
+pub async fn api_add_member(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+    user_id: i64,
+) -> Result<()> {
+    Ok(accounts.api()?.add_member(channel_id, user_id).await?)
+}
+
+#[tauri::command]
+pub async fn api_remove_member(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+    user_id: i64,
+) -> Result<()> {
+    Ok(accounts.api()?.remove_member(channel_id, user_id).await?)
+}
+
+#[tauri::command]
+pub async fn api_create_invite(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+    options: Option<InviteOptions>,
+) -> Result<Invite> {
+    Ok(accounts
+        .api()?
+        .create_invite(channel_id, &options.unwrap_or_default())
+        .await?)
+}
+
+#[tauri::command]
+pub async fn api_list_invites(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+) -> Result<Vec<Invite>> {
+    Ok(accounts.api()?.list_invites(channel_id).await?)
+}
+
+#[tauri::command]
+pub async fn api_revoke_invite(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+    invite_id: i64,
+) -> Result<()> {
+    Ok(accounts.api()?.revoke_invite(channel_id, invite_id).await?)
+}
+
+#[tauri::command]
+pub async fn api_get_messages(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+    query: Option<MessagesQuery>,
+) -> Result<MessagePage> {
+    Ok(accounts
+        .api()?
+        .get_messages(channel_id, &query.unwrap_or_default())
+        .await?)
+}
+
+#[tauri::command]
+pub async fn api_send_message(
+    accounts: State<'_, Accounts>,
+    channel_id: i64,
+    content: String,
+    kind: Option<MessageKind>,
+) -> Result<Message> {
+    Ok(accounts
+        .api()?
+        .send_message(channel_id, &content, kind)
+        .await?)
+}
+
+#[tauri::command]
+pub async fn api_mark_channel_read(accounts: State<'_, Accounts>, channel_id: i64) -> Result<()> {
+    Ok(accounts.api()?.mark_channel_read(channel_id).await?)
+}
+
+#[tauri::command]
+pub async fn api_get_or_create_dm(accounts: State<'_, Accounts>, user_id: i64) -> Result<DmResult> {
+    Ok(accounts.api()?.get_or_create_dm(user_id).await?)
+}
+
+#[tauri::command]
+pub async fn api_list_dms(accounts: State<'_, Accounts>) -> Result<Vec<DmChannel>> {
+    Ok(accounts.api()?.list_dms().await?)
+}
@@ -0,0 +1,477 @@
+//! Native client for the close-chat REST API, mirroring `src/lib/api.ts`.
+
+pub mod commands;
+#[cfg(test)]
+mod tests;
+
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::sync::RwLock;
+
+pub use closechat_protocol::rest as models;
+use models::*;
+
+use crate::profiles::ConnectionProfile;
+use crate::tls;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.t-bash.space";
+
+/// Lets the server recognise a retried send it already posted.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// 401: the token is missing, expired or revoked.
+    #[error("{0}")]
+    Unauthorized(String),
+    /// Any other non-2xx answer; `message` is the body's `error` field when present.
+    #[error("{message}")]
+    Server { status: u16, message: String },
+    #[error("request failed: {0}")]
+    Network(reqwest::Error),
+    /// The server's certificate does not carry a key pinned in the profile.
+    #[error("{0}")]
+    PinMismatch(String),
+    /// The profile's CA bundle or pins could not be used.
+    #[error("TLS setup failed: {0}")]
+    Tls(String),
+    #[error("unexpected response: {0}")]
+    Decode(String),
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> Self {
+        match tls::pin_mismatch(&err) {
+            Some(mismatch) => ApiError::PinMismatch(mismatch.to_string()),
+            None => ApiError::Network(err),
+        }
+    }
+}
+
+impl ApiError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Server { .. } => "server",
+            ApiError::Network(_) => "network",
+            ApiError::PinMismatch(_) => "pinMismatch",
+            ApiError::Tls(_) => "tls",
+            ApiError::Decode(_) => "decode",
+        }
+    }
+
+    /// Whether the same request may well succeed later: the server could not be
+    /// reached, is overloaded or restarting, or asked to slow down.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ApiError::Network(_) => true,
+            ApiError::Server { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+}
+
+pub type ApiResult<T> = std::result::Result<T, ApiError>;
+
+fn decode<T: DeserializeOwned>(value: Value) -> ApiResult<T> {
+    serde_json::from_value(value).map_err(|err| ApiError::Decode(err.to_string()))
+}
+
+/// Where requests go; swapped as a whole when the profile changes.
+#[derive(Clone)]
+struct Endpoint {
+    http: reqwest::Client,
+    /// Without a trailing slash.
+    base_url: String,
+    profile: ConnectionProfile,
+}
+
+impl Endpoint {
+    fn new(profile: &ConnectionProfile) -> ApiResult<Self> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(config) = tls::http_config(&profile.tls).map_err(ApiError::Tls)? {
+            builder = builder.tls_backend_preconfigured(config);
+        }
+        if let Some(proxy) = profile.proxy.reqwest()? {
+            builder = builder.proxy(proxy);
+        }
+        let http = builder.build()?;
+        Ok(Self {
+            http,
+            base_url: profile.base_url.trim_end_matches('/').to_string(),
+            profile: profile.clone(),
+        })
+    }
+}
+
+/// A profile whose HTTP client is already built, so handing it to
+/// [`ApiClient::set_profile`] cannot fail.
+pub struct PreparedProfile(Endpoint);
+
+impl PreparedProfile {
+    pub fn new(profile: &ConnectionProfile) -> ApiResult<Self> {
+        Endpoint::new(profile).map(Self)
+    }
+}
+
+pub struct ApiClient {
+    endpoint: RwLock<Endpoint>,
+    token: RwLock<Option<String>>,
+}
+
+impl ApiClient {
+    pub fn new(profile: &ConnectionProfile) -> ApiResult<Self> {
+        // reqwest is built without a default crypto provider; this is a no-op if
+        // another part of the app installed one first.
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        Ok(Self {
+            endpoint: RwLock::new(Endpoint::new(profile)?),
+            token: RwLock::new(None),
+        })
+    }
+
+    pub fn profile(&self) -> ConnectionProfile {
+        self.endpoint.read().unwrap().profile.clone()
+    }
+
+    /// Send later requests to `prepared`'s profile. Requests already in flight
+    /// finish against the old server.
+    pub fn set_profile(&self, prepared: &PreparedProfile) {
+        *self.endpoint.write().unwrap() = prepared.0.clone();
+    }
+
+    pub fn token(&self) -> Option<String> {
+        self.token.read().unwrap().clone()
+    }
+
+    pub fn set_token(&self, token: Option<String>) {
+        *self.token.write().unwrap() = token;
+    }
+
+    /// The profile's WebSocket URL with `?token=…` for the current token.
+    pub fn websocket_url(&self) -> String {
+        let mut url = self.profile().websocket_url();
+        if let Ok(mut parsed) = Url::parse(&url) {
+            parsed
+                .query_pairs_mut()
+                .append_pair("token", &self.token().unwrap_or_default());
+            url = parsed.into();
+        }
+        url
+    }
+
+    fn endpoint(&self) -> Endpoint {
+        self.endpoint.read().unwrap().clone()
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let endpoint = self.endpoint();
+        self.authorize(
+            endpoint
+                .http
+                .request(method, format!("{}{path}", endpoint.base_url)),
+        )
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.token() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Send and return the JSON body; an empty body comes back as `null`.
+    async fn send(&self, request: RequestBuilder) -> ApiResult<Value> {
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|body| body.get("error")?.as_str().map(str::to_string))
+                .unwrap_or_else(|| format!("HTTP {}", status.as_u16()));
+            return Err(match status {
+                StatusCode::UNAUTHORIZED => ApiError::Unauthorized(message),
+                _ => ApiError::Server {
+                    status: status.as_u16(),
+                    message,
+                },
+            });
+        }
+        if text.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&text).map_err(|err| ApiError::Decode(err.to_string()))
+    }
+
+    /// Send and deserialize the `field` of the response envelope.
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+        field: &str,
+    ) -> ApiResult<T> {
+        let mut body = self.send(request).await?;
+        let value = body
+            .get_mut(field)
+            .map(Value::take)
+            .ok_or_else(|| ApiError::Decode(format!("missing `{field}`")))?;
+        serde_json::from_value(value).map_err(|err| ApiError::Decode(format!("{field}: {err}")))
+    }
+
+    /// Like [`Self::fetch`], with a missing or `null` list read as empty.
+    async fn fetch_list<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+        field: &str,
+    ) -> ApiResult<Vec<T>> {
+        let mut body = self.send(request).await?;
+        match body.get_mut(field).map(Value::take) {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(value) => serde_json::from_value(value)
+                .map_err(|err| ApiError::Decode(format!("{field}: {err}"))),
+        }
+    }
+
+    async fn send_json(&self, method: Method, path: &str, body: &impl Serialize) -> ApiResult<()> {
+        self.send(self.request(method, path).json(body)).await?;
+        Ok(())
+    }
+
+    // ── Auth ──
+
+    /// Creates the account and keeps its token for later requests.
+    pub async fn signup(
+        &self,
+        username: &str,
+        email: &str,
+        password: &str,
+    ) -> ApiResult<AuthResponse> {
+        let request = self
+            .request(Method::POST, "/api/auth/signup")
+            .json(&SignupRequest {
+                username: username.to_string(),
+                email: email.to_string(),
+                password: password.to_string(),
+            });
+        let auth: AuthResponse = decode(self.send(request).await?)?;
+        self.set_token(Some(auth.token.clone()));
+        Ok(auth)
+    }
+
+    /// Signs in and keeps the token for later requests.
+    pub async fn login(&self, username: &str, password: &str) -> ApiResult<AuthResponse> {
+        let request = self
+            .request(Method::POST, "/api/auth/login")
+            .json(&LoginRequest {
+                username: username.to_string(),
+                password: password.to_string(),
+            });
+        let auth: AuthResponse = decode(self.send(request).await?)?;
+        self.set_token(Some(auth.token.clone()));
+        Ok(auth)
+    }
+
+    pub async fn verify_token(&self) -> ApiResult<User> {
+        self.fetch(self.request(Method::GET, "/api/auth/verify"), "user")
+            .await
+    }
+
+    // ── Users ──
+
+    pub async fn list_users(&self) -> ApiResult<Vec<User>> {
+        self.fetch_list(self.request(Method::GET, "/api/users/"), "users")
+            .await
+    }
+
+    pub async fn get_me(&self) -> ApiResult<User> {
+        self.fetch(self.request(Method::GET, "/api/users/me"), "user")
+            .await
+    }
+
+    pub async fn update_me(&self, update: &UpdateMe) -> ApiResult<User> {
+        let request = self.request(Method::PATCH, "/api/users/me").json(update);
+        self.fetch(request, "user").await
+    }
+
+    pub async fn get_user(&self, id: i64) -> ApiResult<User> {
+        self.fetch(
+            self.request(Method::GET, &format!("/api/users/{id}")),
+            "user",
+        )
+        .await
+    }
+
+    pub async fn search_users(&self, query: &str) -> ApiResult<Vec<User>> {
+        let request = self
+            .request(Method::GET, "/api/users/search")
+            .query(&[("q", query)]);
+        self.fetch_list(request, "users").await
+    }
+
+    // ── Channels ──
+
+    pub async fn list_channels(&self) -> ApiResult<Vec<Channel>> {
+        self.fetch_list(self.request(Method::GET, "/api/channels/"), "channels")
+            .await
+    }
+
+    pub async fn create_channel(
+        &self,
+        name: &str,
+        kind: ChannelKind,
+        members: Option<&[i64]>,
+    ) -> ApiResult<Channel> {
+        let request = self
+            .request(Method::POST, "/api/channels/")
+            .json(&CreateChannelRequest {
+                name: name.to_string(),
+                kind,
+                members: members.map(<[i64]>::to_vec),
+            });
+        self.fetch(request, "channel").await
+    }
+
+    pub async fn get_channel(&self, id: i64) -> ApiResult<Channel> {
+        let request = self.request(Method::GET, &format!("/api/channels/{id}"));
+        self.fetch(request, "channel").await
+    }
+
+    pub async fn join_channel(&self, id: i64) -> ApiResult<()> {
+        self.send(self.request(Method::POST, &format!("/api/channels/{id}/join")))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn leave_channel(&self, id: i64) -> ApiResult<()> {
+        self.send(self.request(Method::POST, &format!("/api/channels/{id}/leave")))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn channel_members(&self, id: i64) -> ApiResult<Vec<ChannelMember>> {
+        let request = self.request(Method::GET, &format!("/api/channels/{id}/members"));
+        self.fetch_list(request, "members").await
+    }
+
+    pub async fn join_by_invite_code(&self, code: &str) -> ApiResult<Channel> {
+        // Codes are user input, so let Url percent-encode the segment
+        let endpoint = self.endpoint();
+        let mut url = Url::parse(&format!("{}/api/channels/join/invite/", endpoint.base_url))
+            .map_err(|err| ApiError::Decode(err.to_string()))?;
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push(code);
+        }
+        let request = self.authorize(endpoint.http.request(Method::POST, url));
+        self.fetch(request, "channel").await
+    }
+
+    // ── Members and invites (channel admins) ──
+
+    pub async fn add_member(&self, channel_id: i64, user_id: i64) -> ApiResult<()> {
+        let path = format!("/api/channels/{channel_id}/members");
+        self.send_json(Method::POST, &path, &AddMemberRequest { user_id })
+            .await
+    }
+
+    pub async fn remove_member(&self, channel_id: i64, user_id: i64) -> ApiResult<()> {
+        let path = format!("/api/channels/{channel_id}/members/{user_id}");
+        self.send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    pub async fn create_invite(
+        &self,
+        channel_id: i64,
+        options: &InviteOptions,
+    ) -> ApiResult<Invite> {
+        let request = self
+            .request(Method::POST, &format!("/api/channels/{channel_id}/invites"))
+            .json(options);
+        self.fetch(request, "invite").await
+    }
+
+    pub async fn list_invites(&self, channel_id: i64) -> ApiResult<Vec<Invite>> {
+        let request = self.request(Method::GET, &format!("/api/channels/{channel_id}/invites"));
+        self.fetch_list(request, "invites").await
+    }
+
+    pub async fn revoke_invite(&self, channel_id: i64, invite_id: i64) -> ApiResult<()> {
+        let path = format!("/api/channels/{channel_id}/invites/{invite_id}");
+        self.send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+
+    // ── Messages ──
+
+    pub async fn get_messages(
+        &self,
+        channel_id: i64,
+        query: &MessagesQuery,
+    ) -> ApiResult<MessagePage> {
+        let request = self
+            .request(Method::GET, &format!("/api/channels/{channel_id}/messages"))
+            .query(query);
+        decode(self.send(request).await?)
+    }
+
+    pub async fn send_message(
+        &self,
+        channel_id: i64,
+        content: &str,
+        kind: Option<MessageKind>,
+    ) -> ApiResult<Message> {
+        let request = self.message_request(channel_id, content, kind);
+        self.fetch(request, "message").await
+    }
+
+    /// Like [`send_message`](Self::send_message), but the server posts it at most
+    /// once per `key`, so a retry after a lost response does not duplicate it.
+    pub async fn send_message_once(
+        &self,
+        channel_id: i64,
+        content: &str,
+        kind: Option<MessageKind>,
+        key: &str,
+    ) -> ApiResult<Message> {
+        let request = self
+            .message_request(channel_id, content, kind)
+            .header(IDEMPOTENCY_KEY_HEADER, key);
+        self.fetch(request, "message").await
+    }
+
+    fn message_request(
+        &self,
+        channel_id: i64,
+        content: &str,
+        kind: Option<MessageKind>,
+    ) -> RequestBuilder {
+        let body = SendMessageRequest {
+            content: content.to_string(),
+            kind,
+        };
+        self.request(
+            Method::POST,
+            &format!("/api/channels/{channel_id}/messages"),
+        )
+        .json(&body)
+    }
+
+    pub async fn mark_channel_read(&self, channel_id: i64) -> ApiResult<()> {
+        let path = format!("/api/channels/{channel_id}/read");
+        self.send(self.request(Method::POST, &path)).await?;
+        Ok(())
+    }
+
+    // ── DMs ──
+
+    pub async fn get_or_create_dm(&self, user_id: i64) -> ApiResult<DmResult> {
+        let request = self.request(Method::POST, &format!("/api/dm/{user_id}"));
+        decode(self.send(request).await?)
+    }
+
+    pub async fn list_dms(&self) -> ApiResult<Vec<DmChannel>> {
+        self.fetch_list(self.request(Method::GET, "/api/dm/"), "dms")
+            .await
+    }
+}
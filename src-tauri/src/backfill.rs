@@ -0,0 +1,310 @@
+//! Closes the hole a dropped socket leaves. The newest message id seen in each
+//! channel is kept across reconnects; after a reconnect the messages endpoint is
+//! paged back to it, and the missed messages are delivered oldest first before
+//! live frames resume. Live frames the backfill already delivered are dropped.
+//!
+//! Channels quiet on the socket count from their cache if it was live, i.e.
+//! reached the newest message since the socket last connected, e.g. through a
+//! history page. Nothing is live before the first connection after a launch:
+//! the unread counts from the server already include what came before it.
+
+use closechat_protocol::ws::IncomingFrame;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::api::models::{Message, MessagesQuery};
+use crate::api::{ApiClient, ApiResult};
+use crate::history::{self, HistoryDb};
+
+/// Messages per request while paging back to the last one seen.
+const PAGE_SIZE: u32 = 100;
+
+/// Give up on a channel's gap after this many pages; its history view loads
+/// anything older on demand.
+const MAX_PAGES: usize = 10;
+
+/// Newest message id delivered per channel, for one account's socket.
+#[derive(Debug, Default)]
+pub struct GapTracker {
+    last_seen: Mutex<HashMap<i64, i64>>,
+}
+
+impl GapTracker {
+    /// Note a frame about to be delivered; `false` if it is a message that
+    /// already was.
+    fn observe(&self, frame: &IncomingFrame) -> bool {
+        let IncomingFrame::Message { id, channel_id, .. } = frame else {
+            return true;
+        };
+        let mut last_seen = self.last_seen.lock().unwrap();
+        match last_seen.get(channel_id) {
+            Some(last) if last >= id => false,
+            _ => {
+                last_seen.insert(*channel_id, *id);
+                true
+            }
+        }
+    }
+
+    /// The socket (re)connected: count from the cache of channels synced over
+    /// the last connection, then mark the cache stale.
+    pub fn reconnected(&self, db: &HistoryDb, account: &str) -> rusqlite::Result<()> {
+        let cached = db.live_newest(account)?;
+        let mut last_seen = self.last_seen.lock().unwrap();
+        for (channel_id, newest) in cached {
+            let last = last_seen.entry(channel_id).or_insert(newest);
+            *last = (*last).max(newest);
+        }
+        drop(last_seen);
+        db.mark_stale(account)
+    }
+
+    /// A live frame to deliver, parsed; `None` if malformed, of a kind this
+    /// client does not know, or a message already delivered by a backfill.
+    pub fn admit(&self, text: &str) -> Option<IncomingFrame> {
+        match serde_json::from_str::<IncomingFrame>(text) {
+            Ok(IncomingFrame::Unknown) => None,
+            Ok(frame) => self.observe(&frame).then_some(frame),
+            Err(err) => {
+                eprintln!("dropping malformed frame: {err}");
+                None
+            }
+        }
+    }
+
+    /// Messages posted since the last one seen in each channel, as frames in id
+    /// order, marked as seen. Channels nothing was seen in yet have no gap.
+    pub async fn backfill(&self, api: &ApiClient) -> Vec<IncomingFrame> {
+        let channels: Vec<(i64, i64)> = self
+            .last_seen
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(channel, last)| (*channel, *last))
+            .collect();
+        let mut missed = BTreeMap::new();
+        for (channel_id, last_seen) in channels {
+            match missed_since(api, channel_id, last_seen).await {
+                Ok(messages) => missed.extend(messages.into_iter().map(|m| (m.id, m))),
+                Err(err) => eprintln!("backfilling channel {channel_id} failed: {err}"),
+            }
+        }
+        missed
+            .values()
+            .map(history::message_frame)
+            .filter(|frame| self.observe(frame))
+            .collect()
+    }
+}
+
+/// `channel_id`'s messages newer than `last_seen`, oldest first.
+async fn missed_since(api: &ApiClient, channel_id: i64, last_seen: i64) -> ApiResult<Vec<Message>> {
+    let mut found = BTreeMap::new();
+    let mut before = None;
+    for _ in 0..MAX_PAGES {
+        let query = MessagesQuery {
+            limit: Some(PAGE_SIZE),
+            before: before.take(),
+        };
+        let page = api.get_messages(channel_id, &query).await?;
+        let oldest = page.messages.iter().map(|m| m.id).min();
+        found.extend(
+            page.messages
+                .into_iter()
+                .filter(|m| m.id > last_seen)
+                .map(|m| (m.id, m)),
+        );
+        match oldest {
+            Some(oldest) if oldest > last_seen && page.has_more => {
+                before = Some(oldest.to_string());
+            }
+            _ => break,
+        }
+    }
+    Ok(found.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::ConnectionProfile;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+    use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn message(id: i64) -> serde_json::Value {
+        json!({
+            "id": id, "channelId": 1, "senderId": 2, "senderUsername": "bob",
+            "content": format!("m{id}"), "type": "user",
+            "createdAt": format!("2024-05-01T10:00:0{id}Z")
+        })
+    }
+
+    fn frame_text(id: i64) -> String {
+        let mut frame = message(id);
+        frame["type"] = json!("message");
+        frame.to_string()
+    }
+
+    fn frame(id: i64) -> WsMessage {
+        WsMessage::text(frame_text(id))
+    }
+
+    /// A socket server that plays one script per connection and then hangs up.
+    async fn scripted_socket(scripts: Vec<Vec<i64>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for script in scripts {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(socket).await.unwrap();
+                for id in script {
+                    socket.send(frame(id)).await.unwrap();
+                }
+                // Drop without a close frame, like a lost connection
+            }
+        });
+        url
+    }
+
+    fn ids(delivered: &[IncomingFrame]) -> Vec<i64> {
+        delivered
+            .iter()
+            .filter_map(|frame| match frame {
+                IncomingFrame::Message { id, .. } => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn admits_each_message_once_and_skips_what_it_cannot_read() {
+        let gaps = GapTracker::default();
+        assert!(gaps.admit(&frame_text(2)).is_some());
+        assert!(gaps.admit(&frame_text(2)).is_none());
+        assert!(gaps.admit(&frame_text(1)).is_none());
+        assert!(gaps.admit(r#"{"type":"somethingNew"}"#).is_none());
+        assert!(gaps.admit("not json").is_none());
+        assert!(gaps.admit(&frame_text(3)).is_some());
+    }
+
+    #[tokio::test]
+    async fn reconnects_fill_the_gap_once_and_in_order() {
+        let rest = MockServer::start().await;
+        // Newest first, as the server pages
+        Mock::given(method("GET"))
+            .and(path("/api/channels/1/messages"))
+            .and(query_param_is_missing("before"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    json!({ "messages": [message(5), message(4)], "hasMore": true }),
+                ),
+            )
+            .mount(&rest)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/channels/1/messages"))
+            .and(query_param("before", "4"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    json!({ "messages": [message(3), message(2)], "hasMore": true }),
+                ),
+            )
+            .expect(1)
+            .mount(&rest)
+            .await;
+
+        // 3 to 5 are posted while the first connection is down; 5 also arrives
+        // live once the second one is up.
+        let ws_url = scripted_socket(vec![vec![1, 2], vec![5, 6]]).await;
+        let mut profile = ConnectionProfile::for_server(&rest.uri());
+        profile.ws_url = Some(ws_url);
+        let api = ApiClient::new(&profile).unwrap();
+
+        let db = HistoryDb::in_memory();
+        let gaps = GapTracker::default();
+        let mut delivered = Vec::new();
+        for _ in 0..2 {
+            let socket = crate::ws::connect(&api).await.unwrap();
+            // As `ws::session` does: the backfill, then what `ws::forward` admits
+            gaps.reconnected(&db, "alice").unwrap();
+            delivered.extend(gaps.backfill(&api).await);
+            let (_, mut read) = socket.split();
+            while let Some(Ok(WsMessage::Text(text))) = read.next().await {
+                delivered.extend(gaps.admit(&text));
+            }
+        }
+
+        assert_eq!(ids(&delivered), [1, 2, 3, 4, 5, 6]);
+        let backfilled = history::frame_message(&delivered[2]).unwrap();
+        assert_eq!(backfilled.content.as_deref(), Some("m3"));
+    }
+
+    #[tokio::test]
+    async fn channels_only_loaded_as_history_are_backfilled_too() {
+        let rest = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/channels/1/messages"))
+            .and(query_param_is_missing("before"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "messages": [message(5), message(4), message(3), message(2)],
+                "hasMore": true
+            })))
+            .expect(1)
+            .mount(&rest)
+            .await;
+
+        // Quiet on the socket until after the drop
+        let ws_url = scripted_socket(vec![vec![], vec![6]]).await;
+        let mut profile = ConnectionProfile::for_server(&rest.uri());
+        profile.ws_url = Some(ws_url);
+        let api = ApiClient::new(&profile).unwrap();
+
+        let db = HistoryDb::in_memory();
+        let gaps = GapTracker::default();
+        let mut delivered = Vec::new();
+        for connection in 0..2 {
+            let socket = crate::ws::connect(&api).await.unwrap();
+            gaps.reconnected(&db, "alice").unwrap();
+            delivered.extend(gaps.backfill(&api).await);
+            if connection == 0 {
+                // The channel is opened, as `history::history_page` stores it
+                let page = serde_json::from_value(
+                    json!({ "messages": [message(2), message(1)], "hasMore": false }),
+                )
+                .unwrap();
+                db.merge_latest("alice", 1, &page).unwrap();
+            }
+            let (_, mut read) = socket.split();
+            while let Some(Ok(WsMessage::Text(text))) = read.next().await {
+                delivered.extend(gaps.admit(&text));
+            }
+        }
+
+        assert_eq!(ids(&delivered), [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn nothing_is_live_after_a_restart() {
+        let path = std::env::temp_dir().join(format!(
+            "closechat-history-{}.sqlite3",
+            rand::random::<u64>()
+        ));
+        let db = HistoryDb::open_at(&path).unwrap();
+        let page = serde_json::from_value(
+            json!({ "messages": [message(2), message(1)], "hasMore": false }),
+        )
+        .unwrap();
+        db.merge_latest("alice", 1, &page).unwrap();
+        assert_eq!(db.live_newest("alice").unwrap(), HashMap::from([(1, 2)]));
+        drop(db);
+
+        let reopened = HistoryDb::open_at(&path).unwrap();
+        assert!(reopened.live_newest("alice").unwrap().is_empty());
+        drop(reopened);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,228 @@
+//! Badges drawn onto the tray icon: the unread count and the connection status
+//! dot. Works on raw RGBA buffers so it needs no font or image crate: digits come
+//! from a 3x5 bitmap font scaled up to the icon size.
+
+const BADGE_COLOR: [u8; 4] = [0xe5, 0x39, 0x35, 0xff];
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+pub const DOT_CONNECTED: [u8; 4] = [0x43, 0xa0, 0x47, 0xff];
+pub const DOT_RECONNECTING: [u8; 4] = [0xff, 0xa0, 0x00, 0xff];
+pub const DOT_OFFLINE: [u8; 4] = [0x75, 0x75, 0x75, 0xff];
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Rows of a 3x5 glyph, most significant of the low three bits on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        _ => [0; 5],
+    }
+}
+
+/// Text shown in the badge, or `None` when there is nothing unread.
+pub fn badge_text(count: u32) -> Option<String> {
+    match count {
+        0 => None,
+        1..=99 => Some(count.to_string()),
+        _ => Some("99+".to_string()),
+    }
+}
+
+/// Copy of `rgba` (`width` x `height`, 4 bytes per pixel) with a pill-shaped
+/// badge showing `count` in the top-right corner. Returned unchanged for 0.
+pub fn render_badge(rgba: &[u8], width: u32, height: u32, count: u32) -> Vec<u8> {
+    let mut out = rgba.to_vec();
+    let Some(text) = badge_text(count) else {
+        return out;
+    };
+
+    let mut canvas = Canvas {
+        buf: &mut out,
+        width,
+        height,
+    };
+    let badge_height = (height * 3 / 5).max(GLYPH_HEIGHT + 2);
+    let scale = ((badge_height - 2) / (GLYPH_HEIGHT + 1)).max(1);
+    let glyphs = text.chars().count() as u32;
+    let text_width = glyphs * GLYPH_WIDTH * scale + (glyphs - 1) * scale;
+    let text_height = GLYPH_HEIGHT * scale;
+    let badge_width = (text_width + 2 * scale).max(badge_height).min(width);
+    let left = width - badge_width;
+
+    canvas.fill_pill(left, 0, badge_width, badge_height, BADGE_COLOR);
+
+    let mut x = left + (badge_width.saturating_sub(text_width)) / 2;
+    let y = (badge_height - text_height) / 2;
+    for c in text.chars() {
+        canvas.draw_glyph(glyph(c), x, y, scale, TEXT_COLOR);
+        x += (GLYPH_WIDTH + 1) * scale;
+    }
+    out
+}
+
+/// Draw a status dot of `color` in the bottom-right corner, in place.
+pub fn draw_status_dot(rgba: &mut [u8], width: u32, height: u32, color: [u8; 4]) {
+    let size = (height * 2 / 5).max(3).min(width);
+    let mut canvas = Canvas {
+        buf: rgba,
+        width,
+        height,
+    };
+    canvas.fill_pill(width - size, height - size, size, size, color);
+}
+
+/// Grey out the icon in place, for states where nothing will arrive.
+pub fn desaturate(rgba: &mut [u8]) {
+    for pixel in rgba.chunks_exact_mut(4) {
+        let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]].map(u32::from);
+        let luma = ((r * 299 + g * 587 + b * 114) / 1000) as u8;
+        pixel.copy_from_slice(&[luma, luma, luma, (a * 3 / 5) as u8]);
+    }
+}
+
+struct Canvas<'a> {
+    buf: &'a mut [u8],
+    width: u32,
+    height: u32,
+}
+
+impl Canvas<'_> {
+    fn put(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = ((y * self.width + x) * 4) as usize;
+        if let Some(pixel) = self.buf.get_mut(i..i + 4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    /// Horizontal capsule with its bounding box at `left`, `top`. A circle when
+    /// `width == height`.
+    fn fill_pill(&mut self, left: u32, top: u32, width: u32, height: u32, color: [u8; 4]) {
+        let r = height as f32 / 2.0;
+        let cy = top as f32 + r;
+        let (min_cx, max_cx) = (left as f32 + r, (left + width) as f32 - r);
+        for y in top..top + height {
+            for x in left..left + width {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let cx = px.clamp(min_cx, max_cx.max(min_cx));
+                let (dx, dy) = (px - cx, py - cy);
+                if dx * dx + dy * dy <= r * r {
+                    self.put(x, y, color);
+                }
+            }
+        }
+    }
+
+    fn draw_glyph(&mut self, rows: [u8; 5], x: u32, y: u32, scale: u32, color: [u8; 4]) {
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        self.put(x + col * scale + dx, y + row as u32 * scale + dy, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 32;
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn blank() -> Vec<u8> {
+        vec![0; (SIZE * SIZE * 4) as usize]
+    }
+
+    fn pixel(buf: &[u8], x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * SIZE + x) * 4) as usize;
+        buf[i..i + 4].try_into().unwrap()
+    }
+
+    fn count_color(buf: &[u8], color: [u8; 4]) -> usize {
+        buf.chunks_exact(4).filter(|p| *p == color).count()
+    }
+
+    #[test]
+    fn caps_text_at_99() {
+        assert_eq!(badge_text(0), None);
+        assert_eq!(badge_text(7).as_deref(), Some("7"));
+        assert_eq!(badge_text(99).as_deref(), Some("99"));
+        assert_eq!(badge_text(100).as_deref(), Some("99+"));
+    }
+
+    #[test]
+    fn zero_leaves_the_icon_alone() {
+        let icon: Vec<u8> = (0..SIZE * SIZE * 4).map(|i| i as u8).collect();
+        assert_eq!(render_badge(&icon, SIZE, SIZE, 0), icon);
+    }
+
+    #[test]
+    fn badge_sits_in_the_top_right_corner() {
+        let out = render_badge(&blank(), SIZE, SIZE, 3);
+        let (w, h) = (SIZE, SIZE);
+        assert_eq!(pixel(&out, w - 4, 2), BADGE_COLOR);
+        assert_eq!(pixel(&out, 0, 0), CLEAR);
+        assert_eq!(pixel(&out, 0, h - 1), CLEAR);
+        assert_eq!(pixel(&out, w - 1, h - 1), CLEAR);
+        // Rounded, so the very corner stays clear
+        assert_eq!(pixel(&out, w - 1, 0), CLEAR);
+        assert!(count_color(&out, TEXT_COLOR) > 0);
+        // Nothing drawn below the badge
+        assert!(out[(h * 3 / 5 * w * 4) as usize..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn longer_text_widens_the_badge() {
+        let one = render_badge(&blank(), SIZE, SIZE, 1);
+        let capped = render_badge(&blank(), SIZE, SIZE, 500);
+        let badge = |buf: &[u8]| count_color(buf, BADGE_COLOR) + count_color(buf, TEXT_COLOR);
+        assert!(badge(&capped) > badge(&one));
+        // "99+" has more lit pixels than "1"
+        assert!(count_color(&capped, TEXT_COLOR) > count_color(&one, TEXT_COLOR));
+    }
+
+    #[test]
+    fn draws_on_top_of_opaque_pixels() {
+        let icon = [10u8, 20, 30, 255].repeat((SIZE * SIZE) as usize);
+        let out = render_badge(&icon, SIZE, SIZE, 42);
+        assert_eq!(pixel(&out, 0, SIZE - 1), [10, 20, 30, 255]);
+        assert_eq!(pixel(&out, SIZE - 4, 2), BADGE_COLOR);
+    }
+
+    #[test]
+    fn status_dot_sits_in_the_bottom_right_corner() {
+        let mut out = blank();
+        draw_status_dot(&mut out, SIZE, SIZE, DOT_CONNECTED);
+        assert_eq!(pixel(&out, SIZE - 6, SIZE - 6), DOT_CONNECTED);
+        assert_eq!(pixel(&out, SIZE - 1, SIZE - 1), CLEAR);
+        assert_eq!(pixel(&out, SIZE - 6, 6), CLEAR);
+        assert_eq!(pixel(&out, 6, SIZE - 6), CLEAR);
+    }
+
+    #[test]
+    fn desaturate_greys_and_fades() {
+        let mut icon = [0xff, 0, 0, 0xff].repeat(4);
+        desaturate(&mut icon);
+        assert_eq!(&icon[..4], &[76, 76, 76, 153]);
+    }
+}
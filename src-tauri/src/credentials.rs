@@ -0,0 +1,398 @@
+//! Account tokens, kept out of the webview. They go to the Secret Service keyring
+//! when a session bus offers one, else to an encrypted file in the app data dir.
+//!
+//! The file is sealed with AES-256-GCM under a random key stored next to it with
+//! owner-only permissions. That keeps tokens out of backups and casual reads of
+//! the data file, but not from someone who can read both files as the user.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::Manager;
+
+pub const CREDENTIALS_FILE: &str = "credentials.bin";
+pub const CREDENTIALS_KEY_FILE: &str = "credentials.key";
+
+/// Where a credentials file whose key went missing is moved to, e.g. after
+/// restoring only part of a backup. Nothing can open it; it is kept in case the
+/// key turns up again.
+pub const ORPHANED_CREDENTIALS_FILE: &str = "credentials.bin.orphaned";
+
+/// Service name the keyring entries are filed under, one entry per account id.
+#[cfg(target_os = "linux")]
+const KEYRING_SERVICE: &str = "closechat";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialError {
+    #[error("secret store failed: {0}")]
+    Store(String),
+    #[error("credentials file: {0}")]
+    Io(#[from] io::Error),
+    #[error("credentials file cannot be decrypted")]
+    Corrupt,
+    #[error("no keyring and no app data directory to keep credentials in")]
+    Unavailable,
+}
+
+pub type CredentialResult<T> = std::result::Result<T, CredentialError>;
+
+/// Somewhere to keep one secret per account.
+pub trait SecretBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn get(&self, account: &str) -> CredentialResult<Option<String>>;
+    fn set(&self, account: &str, secret: &str) -> CredentialResult<()>;
+    fn delete(&self, account: &str) -> CredentialResult<()>;
+}
+
+#[cfg(target_os = "linux")]
+struct Keyring;
+
+#[cfg(target_os = "linux")]
+impl Keyring {
+    fn entry(account: &str) -> CredentialResult<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, account)
+            .map_err(|err| CredentialError::Store(err.to_string()))
+    }
+
+    /// Whether a Secret Service answers at all; headless sessions usually have none.
+    fn available() -> bool {
+        let probe = Self::entry("probe").and_then(|entry| match entry.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(CredentialError::Store(err.to_string())),
+        });
+        if let Err(err) = probe {
+            eprintln!("keyring unavailable, using the credentials file: {err}");
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SecretBackend for Keyring {
+    fn name(&self) -> &'static str {
+        "Secret Service keyring"
+    }
+
+    fn get(&self, account: &str) -> CredentialResult<Option<String>> {
+        match Self::entry(account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(CredentialError::Store(err.to_string())),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &str) -> CredentialResult<()> {
+        Self::entry(account)?
+            .set_password(secret)
+            .map_err(|err| CredentialError::Store(err.to_string()))
+    }
+
+    fn delete(&self, account: &str) -> CredentialResult<()> {
+        match Self::entry(account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(CredentialError::Store(err.to_string())),
+        }
+    }
+}
+
+/// All secrets in one file: a 12-byte nonce followed by the sealed JSON map.
+pub struct EncryptedFile {
+    path: PathBuf,
+    key_path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl EncryptedFile {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join(CREDENTIALS_FILE),
+            key_path: dir.join(CREDENTIALS_KEY_FILE),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn key(&self) -> CredentialResult<LessSafeKey> {
+        let bytes = match fs::read(&self.key_path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                // A new key could never open the old file, and saving would fail
+                // to read it from then on
+                if self.path.exists() {
+                    let aside = self.path.with_file_name(ORPHANED_CREDENTIALS_FILE);
+                    fs::rename(&self.path, &aside)?;
+                    eprintln!(
+                        "credentials key missing, moved the saved tokens aside to {}; \
+                         accounts need to sign in again",
+                        aside.display()
+                    );
+                }
+                let mut bytes = vec![0; AES_256_GCM.key_len()];
+                SystemRandom::new()
+                    .fill(&mut bytes)
+                    .map_err(|_| CredentialError::Store("no randomness".into()))?;
+                write_private(&self.key_path, &bytes)?;
+                bytes
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| CredentialError::Corrupt)?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    fn load(&self, key: &LessSafeKey) -> CredentialResult<BTreeMap<String, String>> {
+        let mut sealed = match fs::read(&self.path) {
+            Ok(sealed) => sealed,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(err.into()),
+        };
+        if sealed.len() < NONCE_LEN {
+            return Err(CredentialError::Corrupt);
+        }
+        let mut payload = sealed.split_off(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(&sealed).map_err(|_| CredentialError::Corrupt)?;
+        let plain = key
+            .open_in_place(nonce, Aad::empty(), &mut payload)
+            .map_err(|_| CredentialError::Corrupt)?;
+        serde_json::from_slice(plain).map_err(|_| CredentialError::Corrupt)
+    }
+
+    fn save(&self, key: &LessSafeKey, secrets: &BTreeMap<String, String>) -> CredentialResult<()> {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| CredentialError::Store("no randomness".into()))?;
+        let mut payload = serde_json::to_vec(secrets).map_err(|_| CredentialError::Corrupt)?;
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut payload,
+        )
+        .map_err(|_| CredentialError::Corrupt)?;
+        let mut sealed = nonce.to_vec();
+        sealed.append(&mut payload);
+        Ok(write_private(&self.path, &sealed)?)
+    }
+
+    fn modify(&self, f: impl FnOnce(&mut BTreeMap<String, String>)) -> CredentialResult<()> {
+        let _guard = self.lock.lock().unwrap();
+        let key = self.key()?;
+        let mut secrets = self.load(&key)?;
+        f(&mut secrets);
+        self.save(&key, &secrets)
+    }
+}
+
+impl SecretBackend for EncryptedFile {
+    fn name(&self) -> &'static str {
+        "encrypted credentials file"
+    }
+
+    fn get(&self, account: &str) -> CredentialResult<Option<String>> {
+        let _guard = self.lock.lock().unwrap();
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        Ok(self.load(&self.key()?)?.remove(account))
+    }
+
+    fn set(&self, account: &str, secret: &str) -> CredentialResult<()> {
+        self.modify(|secrets| {
+            secrets.insert(account.to_string(), secret.to_string());
+        })
+    }
+
+    fn delete(&self, account: &str) -> CredentialResult<()> {
+        self.modify(|secrets| {
+            secrets.remove(account);
+        })
+    }
+}
+
+/// Used when there is neither a keyring nor an app data dir. Tokens are not kept
+/// at all rather than in a temp dir other users can list.
+struct Unavailable;
+
+impl SecretBackend for Unavailable {
+    fn name(&self) -> &'static str {
+        "nowhere: no keyring or app data directory"
+    }
+
+    fn get(&self, _account: &str) -> CredentialResult<Option<String>> {
+        Err(CredentialError::Unavailable)
+    }
+
+    fn set(&self, _account: &str, _secret: &str) -> CredentialResult<()> {
+        Err(CredentialError::Unavailable)
+    }
+
+    fn delete(&self, _account: &str) -> CredentialResult<()> {
+        Err(CredentialError::Unavailable)
+    }
+}
+
+/// Write a file only the current user can read. It is written next to `path`
+/// and renamed over it, so a crash or a full disk leaves the old one intact.
+fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp)?;
+    io::Write::write_all(&mut file, bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+/// Picks the backend on first use, since asking D-Bus can take a moment. Calls run
+/// on the blocking pool: the keyring drives its own runtime and must not be
+/// called from an async task.
+pub struct Credentials(Arc<Picker>);
+
+struct Picker {
+    dir: Option<PathBuf>,
+    backend: OnceLock<Box<dyn SecretBackend>>,
+}
+
+impl Picker {
+    fn backend(&self) -> &dyn SecretBackend {
+        self.backend
+            .get_or_init(|| {
+                let backend = self.pick();
+                eprintln!("keeping credentials in the {}", backend.name());
+                backend
+            })
+            .as_ref()
+    }
+
+    fn pick(&self) -> Box<dyn SecretBackend> {
+        #[cfg(target_os = "linux")]
+        if Keyring::available() {
+            return Box::new(Keyring);
+        }
+        match &self.dir {
+            Some(dir) => Box::new(EncryptedFile::new(dir)),
+            None => Box::new(Unavailable),
+        }
+    }
+}
+
+impl Credentials {
+    pub fn new(app: &tauri::AppHandle) -> Self {
+        Self(Arc::new(Picker {
+            dir: app.path().app_data_dir().ok(),
+            backend: OnceLock::new(),
+        }))
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&dyn SecretBackend) -> CredentialResult<T> + Send + 'static,
+    ) -> CredentialResult<T> {
+        let picker = self.0.clone();
+        tauri::async_runtime::spawn_blocking(move || f(picker.backend()))
+            .await
+            .map_err(|err| CredentialError::Store(err.to_string()))?
+    }
+
+    pub async fn get(&self, account: &str) -> CredentialResult<Option<String>> {
+        let account = account.to_string();
+        self.run(move |backend| backend.get(&account)).await
+    }
+
+    pub async fn set(&self, account: &str, secret: &str) -> CredentialResult<()> {
+        let (account, secret) = (account.to_string(), secret.to_string());
+        self.run(move |backend| backend.set(&account, &secret))
+            .await
+    }
+
+    pub async fn delete(&self, account: &str) -> CredentialResult<()> {
+        let account = account.to_string();
+        self.run(move |backend| backend.delete(&account)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("closechat-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn file_keeps_secrets_sealed() {
+        let dir = scratch_dir("credentials");
+        let file = EncryptedFile::new(&dir);
+        assert_eq!(file.get("default:1").unwrap(), None);
+        file.set("default:1", "s3cret-token").unwrap();
+        file.set("work:7", "other").unwrap();
+        file.delete("work:7").unwrap();
+
+        let reopened = EncryptedFile::new(&dir);
+        assert_eq!(
+            reopened.get("default:1").unwrap().as_deref(),
+            Some("s3cret-token")
+        );
+        assert_eq!(reopened.get("work:7").unwrap(), None);
+        let raw = fs::read(dir.join(CREDENTIALS_FILE)).unwrap();
+        assert!(!raw.windows(12).any(|w| w == b"s3cret-token"));
+        assert!(!dir.join(format!("{CREDENTIALS_FILE}.tmp")).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(CREDENTIALS_FILE))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_without_its_key_is_moved_aside() {
+        let dir = scratch_dir("orphaned-credentials");
+        let file = EncryptedFile::new(&dir);
+        file.set("default:1", "s3cret-token").unwrap();
+        let sealed = fs::read(dir.join(CREDENTIALS_FILE)).unwrap();
+
+        fs::remove_file(dir.join(CREDENTIALS_KEY_FILE)).unwrap();
+        assert_eq!(file.get("default:1").unwrap(), None);
+        assert_eq!(
+            fs::read(dir.join(ORPHANED_CREDENTIALS_FILE)).unwrap(),
+            sealed
+        );
+        // The new key keeps tokens again
+        file.set("default:1", "new-token").unwrap();
+        assert_eq!(
+            EncryptedFile::new(&dir)
+                .get("default:1")
+                .unwrap()
+                .as_deref(),
+            Some("new-token")
+        );
+
+        // Another key, on the other hand, is never replaced
+        fs::write(dir.join(CREDENTIALS_KEY_FILE), [7; 32]).unwrap();
+        assert!(matches!(
+            file.get("default:1"),
+            Err(CredentialError::Corrupt)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+use crate::api::ApiError;
+use crate::credentials::CredentialError;
+use crate::window_mode::IllegalTransition;
+
+/// Error returned by every Tauri command. Serialized as `{ kind, message }` so the
+/// frontend can branch on `kind` and show `message` as-is.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    IllegalTransition(#[from] IllegalTransition),
+    #[error("main window is not available")]
+    NoWindow,
+    #[error("{0}")]
+    InvalidSettings(String),
+    /// What the command refers to, e.g. a queued message, does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// What the command refers to is in use, e.g. a message being sent.
+    #[error("{0}")]
+    Busy(String),
+    #[error("invalid shortcut {0}")]
+    InvalidShortcut(String),
+    #[error("window operation failed: {0}")]
+    Window(#[from] tauri::Error),
+    #[error(transparent)]
+    Api(#[from] ApiError),
+    #[error("not connected to the server")]
+    NotConnected,
+    #[error("no account is signed in")]
+    SignedOut,
+    #[error(transparent)]
+    Credentials(#[from] CredentialError),
+    #[error("{0}")]
+    InvalidQuery(String),
+    #[error("export failed: {0}")]
+    Export(String),
+    #[error("import failed: {0}")]
+    Import(String),
+    #[error("message cache: {0}")]
+    Cache(#[from] rusqlite::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::IllegalTransition(_) => "illegalTransition",
+            Error::NoWindow => "noWindow",
+            Error::InvalidSettings(_) => "invalidSettings",
+            Error::NotFound(_) => "notFound",
+            Error::Busy(_) => "busy",
+            Error::InvalidShortcut(_) => "invalidShortcut",
+            Error::Window(_) => "window",
+            Error::Api(err) => err.kind(),
+            Error::NotConnected => "notConnected",
+            Error::SignedOut => "signedOut",
+            Error::Credentials(_) => "credentials",
+            Error::InvalidQuery(_) => "invalidQuery",
+            Error::Export(_) => "export",
+            Error::Import(_) => "import",
+            Error::Cache(_) => "cache",
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
@@ -0,0 +1,511 @@
+//! Channel transcripts for archiving, e.g. an incident channel for its postmortem.
+//! History is read through the cache ([`crate::history::page`]), so pages already
+//! on disk are not fetched again, then written as JSON, Markdown, HTML or an
+//! IRC-style log.
+
+use chrono::{DateTime, Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
+
+use crate::accounts::Accounts;
+use crate::api::models::{ChannelKind, Message, MessageKind, MessagesQuery};
+use crate::api::ApiClient;
+use crate::error::{Error, Result};
+use crate::history::{self, HistoryDb};
+
+/// Emitted with an [`ExportProgress`] after each page and once the file is written.
+pub const EXPORT_PROGRESS_EVENT: &str = "export-progress";
+
+/// Messages per request while paging back through history.
+const PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// The `Message` objects as the server returns them.
+    Json,
+    Markdown,
+    /// One file with its styles inlined.
+    Html,
+    /// IRC-style `[time] <user> text` lines.
+    Log,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Log => "log",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::Markdown => "Markdown",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Log => "Text log",
+        }
+    }
+}
+
+/// Which days to include; both ends inclusive, open when unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateRange {
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// `created_at` is an RFC 3339 UTC timestamp, so a prefix comparison works.
+    fn contains(&self, created_at: &str) -> bool {
+        let after_start = self
+            .from
+            .is_none_or(|from| created_at >= from.to_string().as_str());
+        after_start && !self.is_past(created_at)
+    }
+
+    fn is_past(&self, created_at: &str) -> bool {
+        self.to
+            .is_some_and(|to| created_at >= (to + Days::new(1)).to_string().as_str())
+    }
+
+    fn is_before_start(&self, created_at: &str) -> bool {
+        self.from
+            .is_some_and(|from| created_at < from.to_string().as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub channel_id: i64,
+    /// Messages in range collected so far.
+    pub messages: usize,
+    pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// Page back from the newest message until the range starts or history ends,
+/// calling `progress` with the count after each page. Oldest first. Fails if
+/// the server cannot be reached rather than end the transcript at the cache.
+pub async fn collect(
+    db: &HistoryDb,
+    api: &ApiClient,
+    account: &str,
+    channel_id: i64,
+    range: DateRange,
+    mut progress: impl FnMut(usize),
+) -> Result<Vec<Message>> {
+    let mut messages = Vec::new();
+    let mut before: Option<i64> = None;
+    loop {
+        let query = MessagesQuery {
+            limit: Some(PAGE_SIZE),
+            before: before.map(|id| id.to_string()),
+        };
+        let page = history::page_online(db, api, account, channel_id, &query).await?;
+        let Some(oldest) = page.messages.last() else {
+            break;
+        };
+        let reached_start = range.is_before_start(&oldest.message.created_at);
+        before = Some(oldest.message.id);
+        messages.extend(
+            page.messages
+                .into_iter()
+                .map(|cached| cached.message)
+                .filter(|message| range.contains(&message.created_at)),
+        );
+        progress(messages.len());
+        if reached_start || !page.has_more {
+            break;
+        }
+    }
+    messages.reverse();
+    Ok(messages)
+}
+
+/// What a transcript is of.
+#[derive(Debug, Clone)]
+pub struct ExportChannel {
+    pub name: String,
+    pub kind: ChannelKind,
+}
+
+impl ExportChannel {
+    fn title(&self) -> String {
+        match self.kind {
+            ChannelKind::Channel => format!("#{}", self.name),
+            ChannelKind::Dm => format!("@{}", self.name),
+        }
+    }
+}
+
+pub fn render(
+    format: ExportFormat,
+    channel: &ExportChannel,
+    range: DateRange,
+    messages: &[Message],
+) -> Result<String> {
+    Ok(match format {
+        ExportFormat::Json => {
+            serde_json::to_string_pretty(messages).map_err(|err| Error::Export(err.to_string()))?
+        }
+        ExportFormat::Markdown => markdown(channel, range, messages),
+        ExportFormat::Html => html(channel, range, messages),
+        ExportFormat::Log => log(channel, messages),
+    })
+}
+
+/// `(day, time)` of a message in UTC, or the raw timestamp if it does not parse.
+fn day_and_time(created_at: &str) -> (String, String) {
+    match DateTime::parse_from_rfc3339(created_at) {
+        Ok(at) => {
+            let at = at.to_utc();
+            (
+                at.format("%Y-%m-%d").to_string(),
+                at.format("%H:%M:%S").to_string(),
+            )
+        }
+        Err(_) => (String::new(), created_at.to_string()),
+    }
+}
+
+fn describe_range(range: DateRange) -> String {
+    match (range.from, range.to) {
+        (Some(from), Some(to)) => format!("{from} to {to}"),
+        (Some(from), None) => format!("since {from}"),
+        (None, Some(to)) => format!("until {to}"),
+        (None, None) => "full history".to_string(),
+    }
+}
+
+fn markdown(channel: &ExportChannel, range: DateRange, messages: &[Message]) -> String {
+    let mut out = format!(
+        "# {}\n\n{}, {} messages, times in UTC.\n",
+        channel.title(),
+        describe_range(range),
+        messages.len()
+    );
+    let mut current_day = String::new();
+    for message in messages {
+        let (day, time) = day_and_time(&message.created_at);
+        if day != current_day {
+            let _ = write!(out, "\n## {day}\n\n");
+            current_day = day;
+        }
+        let content = message.content.as_deref().unwrap_or_default();
+        // Keep continuation lines inside the list item
+        let content = content.replace('\n', "\n  ");
+        let _ = match message.kind {
+            MessageKind::System => writeln!(out, "- {time} _{content}_"),
+            _ => writeln!(out, "- {time} **{}**: {content}", message.sender_username),
+        };
+        if let Some(image) = &message.image_url {
+            let _ = writeln!(out, "  ![image]({image})");
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body{font:14px/1.5 ui-monospace,SFMono-Regular,Menlo,monospace;\
+max-width:960px;margin:2em auto;padding:0 1em;color:#1f2328;background:#fff}\
+h1{font-size:1.4em}h2{font-size:1em;color:#57606a;border-bottom:1px solid #d0d7de;margin-top:2em}\
+.m{display:flex;gap:.75em;padding:.1em 0}.t{color:#8c959f;flex-shrink:0}\
+.u{font-weight:bold;flex-shrink:0}.bot .u{color:#0969da}.system{color:#57606a;font-style:italic}\
+.c{white-space:pre-wrap;word-break:break-word}img{max-width:320px;display:block}";
+
+fn html(channel: &ExportChannel, range: DateRange, messages: &[Message]) -> String {
+    let title = escape_html(&channel.title());
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<p>{}, {} messages, times in UTC.</p>\n",
+        describe_range(range),
+        messages.len()
+    );
+    let mut current_day = String::new();
+    for message in messages {
+        let (day, time) = day_and_time(&message.created_at);
+        if day != current_day {
+            let _ = writeln!(out, "<h2>{}</h2>", escape_html(&day));
+            current_day = day;
+        }
+        let class = match message.kind {
+            MessageKind::User => "m",
+            MessageKind::Bot => "m bot",
+            MessageKind::System => "m system",
+        };
+        let content = escape_html(message.content.as_deref().unwrap_or_default());
+        let _ = write!(
+            out,
+            "<div class=\"{class}\" id=\"m{}\"><span class=\"t\">{time}</span>",
+            message.id
+        );
+        if message.kind != MessageKind::System {
+            let _ = write!(
+                out,
+                "<span class=\"u\">{}</span>",
+                escape_html(&message.sender_username)
+            );
+        }
+        let _ = write!(out, "<span class=\"c\">{content}");
+        if let Some(image) = &message.image_url {
+            let _ = write!(out, "<img src=\"{}\" alt=\"\">", escape_html(image));
+        }
+        out.push_str("</span></div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn log(channel: &ExportChannel, messages: &[Message]) -> String {
+    let mut out = String::new();
+    let mut current_day = String::new();
+    for message in messages {
+        let (day, time) = day_and_time(&message.created_at);
+        if day != current_day {
+            let _ = writeln!(out, "--- {} {day}", channel.title());
+            current_day = day;
+        }
+        let content = message.content.as_deref().unwrap_or_default();
+        for line in content.split('\n') {
+            let _ = match message.kind {
+                MessageKind::System => writeln!(out, "[{time}] * {line}"),
+                _ => writeln!(out, "[{time}] <{}> {line}", message.sender_username),
+            };
+        }
+        if let Some(image) = &message.image_url {
+            let _ = writeln!(out, "[{time}] <{}> {image}", message.sender_username);
+        }
+    }
+    out
+}
+
+/// Ask where to save, defaulting to the downloads folder. `None` if cancelled.
+async fn choose_path(app: &AppHandle, file_name: String, format: ExportFormat) -> Option<PathBuf> {
+    let (tx, rx) = oneshot::channel();
+    let mut dialog = app
+        .dialog()
+        .file()
+        .set_title("Export channel history")
+        .set_file_name(file_name)
+        .add_filter(format.label(), &[format.extension()]);
+    if let Ok(downloads) = app.path().download_dir() {
+        dialog = dialog.set_directory(downloads);
+    }
+    dialog.save_file(move |path| {
+        let _ = tx.send(path);
+    });
+    rx.await.ok().flatten()?.into_path().ok()
+}
+
+/// Export a channel of the active account to a file picked in a save dialog.
+/// Returns the path written, or `None` if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_channel(
+    app: AppHandle,
+    accounts: State<'_, Accounts>,
+    db: State<'_, HistoryDb>,
+    channel_id: i64,
+    format: ExportFormat,
+    range: Option<DateRange>,
+) -> Result<Option<PathBuf>> {
+    let session = accounts.active().ok_or(Error::SignedOut)?;
+    let range = range.unwrap_or_default();
+    let channel = session
+        .channels()
+        .into_iter()
+        .find(|channel| channel.id == channel_id)
+        .map(|channel| ExportChannel {
+            name: channel.name,
+            kind: channel.kind,
+        })
+        .unwrap_or_else(|| ExportChannel {
+            name: format!("channel-{channel_id}"),
+            kind: ChannelKind::Channel,
+        });
+
+    let stamp = range.to.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let file_name = format!("{}-{stamp}.{}", channel.name, format.extension());
+    let Some(path) = choose_path(&app, file_name, format).await else {
+        return Ok(None);
+    };
+
+    let emit = |messages: usize, path: Option<PathBuf>| {
+        let progress = ExportProgress {
+            channel_id,
+            messages,
+            done: path.is_some(),
+            path,
+        };
+        let _ = app.emit(EXPORT_PROGRESS_EVENT, progress);
+    };
+    let messages = collect(&db, &session.api, &session.id, channel_id, range, |count| {
+        emit(count, None)
+    })
+    .await?;
+    let transcript = render(format, &channel, range, &messages)?;
+    std::fs::write(&path, transcript).map_err(|err| Error::Export(err.to_string()))?;
+    emit(messages.len(), Some(path.clone()));
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiError;
+    use crate::profiles::ConnectionProfile;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn message(id: i64, kind: MessageKind, content: &str, created_at: &str) -> Message {
+        Message {
+            id,
+            channel_id: 4,
+            sender_id: 2,
+            sender_username: "alice".into(),
+            content: Some(content.into()),
+            kind,
+            image_url: None,
+            created_at: created_at.into(),
+        }
+    }
+
+    fn incident() -> (ExportChannel, Vec<Message>) {
+        let channel = ExportChannel {
+            name: "incident-42".into(),
+            kind: ChannelKind::Channel,
+        };
+        let messages = vec![
+            message(
+                1,
+                MessageKind::User,
+                "rollback <now> & check",
+                "2026-08-31T23:59:00Z",
+            ),
+            message(2, MessageKind::System, "bob joined", "2026-09-01T00:01:00Z"),
+            message(
+                3,
+                MessageKind::Bot,
+                "deploy 1\ndeploy 2",
+                "2026-09-01T00:02:30Z",
+            ),
+        ];
+        (channel, messages)
+    }
+
+    #[test]
+    fn renders_every_format() {
+        let (channel, messages) = incident();
+        let range = DateRange::default();
+
+        let json = render(ExportFormat::Json, &channel, range, &messages).unwrap();
+        let parsed: Vec<Message> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, messages);
+
+        let md = render(ExportFormat::Markdown, &channel, range, &messages).unwrap();
+        assert!(md.starts_with("# #incident-42\n"));
+        assert!(md.contains("## 2026-08-31\n\n- 23:59:00 **alice**: rollback <now> & check\n"));
+        assert!(md.contains("## 2026-09-01\n\n- 00:01:00 _bob joined_\n- 00:02:30 **alice**: deploy 1\n  deploy 2\n"));
+
+        let html = render(ExportFormat::Html, &channel, range, &messages).unwrap();
+        assert!(html.contains("<style>"));
+        assert!(html.contains("rollback &lt;now&gt; &amp; check"));
+        assert!(!html.contains("<now>"));
+        assert!(html.contains("<div class=\"m system\" id=\"m2\">"));
+
+        let log = render(ExportFormat::Log, &channel, range, &messages).unwrap();
+        assert_eq!(
+            log,
+            "--- #incident-42 2026-08-31\n\
+             [23:59:00] <alice> rollback <now> & check\n\
+             --- #incident-42 2026-09-01\n\
+             [00:01:00] * bob joined\n\
+             [00:02:30] <alice> deploy 1\n\
+             [00:02:30] <alice> deploy 2\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_back_until_the_range_starts() {
+        let server = MockServer::start().await;
+        let day = |id: i64| format!("2026-08-{:02}T12:00:00Z", id);
+        let page = |ids: std::ops::RangeInclusive<i64>, has_more: bool| {
+            let messages: Vec<Message> = ids
+                .rev()
+                .map(|id| message(id, MessageKind::User, "x", &day(id)))
+                .collect();
+            json!({ "messages": messages, "hasMore": has_more })
+        };
+        Mock::given(method("GET"))
+            .and(path("/api/channels/4/messages"))
+            .and(query_param("before", "21"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(1..=20, false)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/channels/4/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(21..=30, true)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let api = ApiClient::new(&ConnectionProfile::for_server(&format!(
+            "{}/",
+            server.uri()
+        )))
+        .unwrap();
+        let db = HistoryDb::in_memory();
+        let range = DateRange {
+            from: NaiveDate::from_ymd_opt(2026, 8, 15),
+            to: NaiveDate::from_ymd_opt(2026, 8, 24),
+        };
+
+        let mut reported = Vec::new();
+        let messages = collect(&db, &api, "default:1", 4, range, |count| {
+            reported.push(count)
+        })
+        .await
+        .unwrap();
+        let ids: Vec<i64> = messages.iter().map(|message| message.id).collect();
+        assert_eq!(ids, (15..=24).collect::<Vec<_>>());
+        assert_eq!(reported, [4, 10]);
+
+        // Offline: no transcript cut off at what happens to be cached
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+        let api = ApiClient::new(&ConnectionProfile::for_server(&unreachable)).unwrap();
+        let offline = collect(&db, &api, "default:1", 4, range, |_| {}).await;
+        assert!(
+            matches!(offline, Err(Error::Api(ApiError::Network(_)))),
+            "{offline:?}"
+        );
+    }
+}
@@ -0,0 +1,705 @@
+//! Message history cached in SQLite under the app data dir, so switching channels
+//! paints right away and history stays readable offline.
+//!
+//! Each channel keeps one unbroken run of messages ending at the newest one seen.
+//! Older pages extend the run from the server; a fresh first page that does not
+//! reach the cached messages starts a new run, since the gap between them is
+//! unknown. Socket messages are added only while the channel is live, that is
+//! synced since the socket last connected, for the same reason.
+
+use closechat_protocol::ws::IncomingFrame;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Manager, State};
+
+use crate::accounts::Accounts;
+use crate::api::models::{Message, MessageKind, MessagePage, MessagesQuery};
+use crate::api::{ApiClient, ApiError};
+use crate::error::Result;
+use crate::search;
+
+pub const HISTORY_FILE: &str = "history.sqlite3";
+
+/// Page size when the caller does not ask for one; matches the frontend's.
+const DEFAULT_LIMIT: u32 = 50;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    account TEXT NOT NULL,
+    id INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    sender_id INTEGER NOT NULL,
+    sender_username TEXT NOT NULL,
+    content TEXT,
+    kind TEXT NOT NULL,
+    image_url TEXT,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (account, id)
+);
+CREATE INDEX IF NOT EXISTS messages_by_channel ON messages (account, channel_id, id);
+CREATE TABLE IF NOT EXISTS channels (
+    account TEXT NOT NULL,
+    channel_id INTEGER NOT NULL,
+    -- the oldest message of the channel is cached
+    complete INTEGER NOT NULL DEFAULT 0,
+    -- synced since the socket last connected
+    live INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (account, channel_id)
+);
+";
+
+/// Changes to databases made by older versions, in order. Each leaves
+/// `user_version` at its position plus one.
+const MIGRATIONS: &[&str] = &[
+    search::SCHEMA,
+    // History imported from other chats, see crate::import
+    "ALTER TABLE messages ADD COLUMN imported_from TEXT;",
+    // Which archive imported history came from, so importing another one from
+    // the same chat leaves it alone
+    "ALTER TABLE messages ADD COLUMN import_key TEXT;
+     UPDATE messages SET import_key = imported_from WHERE imported_from IS NOT NULL;",
+];
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        conn.execute_batch(migration)?;
+        conn.pragma_update(None, "user_version", index as i64 + 1)?;
+    }
+    Ok(())
+}
+
+/// A cached message. Imported history also carries the chat it came from, which
+/// the server's [`Message`] has no field for.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedMessage {
+    #[serde(flatten)]
+    pub message: Message,
+    /// E.g. `"slack"`; see [`crate::import`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_from: Option<String>,
+}
+
+/// A [`MessagePage`] as the history commands return it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryPage {
+    pub messages: Vec<CachedMessage>,
+    pub has_more: bool,
+}
+
+impl From<MessagePage> for HistoryPage {
+    fn from(page: MessagePage) -> Self {
+        Self {
+            messages: page
+                .messages
+                .into_iter()
+                .map(|message| CachedMessage {
+                    message,
+                    imported_from: None,
+                })
+                .collect(),
+            has_more: page.has_more,
+        }
+    }
+}
+
+pub struct HistoryDb(Mutex<Connection>);
+
+impl HistoryDb {
+    /// Open the database in the app data dir, or an in-memory one if that fails so
+    /// the app still works, just without a cache across restarts.
+    pub fn open(app: &AppHandle) -> Self {
+        let opened = app
+            .path()
+            .app_data_dir()
+            .map_err(|err| err.to_string())
+            .and_then(|dir| {
+                std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+                Self::open_at(&dir.join(HISTORY_FILE)).map_err(|err| err.to_string())
+            });
+        opened.unwrap_or_else(|err| {
+            eprintln!("message cache unavailable, keeping history in memory: {err}");
+            Self::in_memory()
+        })
+    }
+
+    pub(crate) fn open_at(path: &std::path::Path) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub(crate) fn in_memory() -> Self {
+        Self::init(Connection::open_in_memory().expect("in-memory SQLite"))
+            .expect("in-memory SQLite schema")
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        migrate(&conn)?;
+        // No socket has connected in this run yet
+        conn.execute("UPDATE channels SET live = 0", [])?;
+        Ok(Self(Mutex::new(conn)))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.0.lock().unwrap()
+    }
+
+    /// Up to `limit` cached messages older than `before`, newest first like the
+    /// server's pages, and whether the cache holds more. Imported history sorts
+    /// below the server's, so it only shows once that is cached completely.
+    fn cached(
+        &self,
+        account: &str,
+        channel_id: i64,
+        before: Option<i64>,
+        limit: u32,
+    ) -> rusqlite::Result<(Vec<CachedMessage>, bool)> {
+        let conn = self.0.lock().unwrap();
+        let mut statement = conn.prepare_cached(
+            "SELECT id, channel_id, sender_id, sender_username, content, kind, image_url, created_at,
+                    imported_from
+             FROM messages
+             WHERE account = ?1 AND channel_id = ?2 AND id < ?3
+               AND (imported_from IS NULL OR EXISTS (
+                   SELECT 1 FROM channels
+                   WHERE account = ?1 AND channel_id = ?2 AND complete
+               ))
+             ORDER BY id DESC LIMIT ?4",
+        )?;
+        let rows = statement.query_map(
+            params![account, channel_id, before.unwrap_or(i64::MAX), limit + 1],
+            |row| {
+                let kind: String = row.get(5)?;
+                let message = Message {
+                    id: row.get(0)?,
+                    channel_id: row.get(1)?,
+                    sender_id: row.get(2)?,
+                    sender_username: row.get(3)?,
+                    content: row.get(4)?,
+                    kind: kind_from_str(&kind),
+                    image_url: row.get(6)?,
+                    created_at: row.get(7)?,
+                };
+                Ok(CachedMessage {
+                    message,
+                    imported_from: row.get(8)?,
+                })
+            },
+        )?;
+        let mut messages = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        let more = messages.len() > limit as usize;
+        messages.truncate(limit as usize);
+        Ok((messages, more))
+    }
+
+    fn channel(&self, account: &str, channel_id: i64) -> rusqlite::Result<Option<(bool, bool)>> {
+        self.0
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT complete, live FROM channels WHERE account = ?1 AND channel_id = ?2",
+                params![account, channel_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+    }
+
+    /// Store the server's newest page. Unless it reaches the cached run, the run
+    /// is replaced by it.
+    pub(crate) fn merge_latest(
+        &self,
+        account: &str,
+        channel_id: i64,
+        page: &MessagePage,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.0.lock().unwrap();
+        let tx = conn.transaction()?;
+        let newest: Option<i64> = tx.query_row(
+            "SELECT MAX(id) FROM messages
+             WHERE account = ?1 AND channel_id = ?2 AND imported_from IS NULL",
+            params![account, channel_id],
+            |row| row.get(0),
+        )?;
+        let oldest_fetched = page.messages.iter().map(|message| message.id).min();
+        let reaches = match (newest, oldest_fetched) {
+            (Some(newest), Some(oldest)) => oldest <= newest,
+            // Nothing on the server, or nothing cached yet
+            _ => !page.has_more || newest.is_none(),
+        };
+        if !reaches {
+            tx.execute(
+                "DELETE FROM messages
+                 WHERE account = ?1 AND channel_id = ?2 AND imported_from IS NULL",
+                params![account, channel_id],
+            )?;
+        }
+        insert_all(&tx, account, None, &page.messages)?;
+        tx.execute(
+            "INSERT INTO channels (account, channel_id, complete, live) VALUES (?1, ?2, ?3, 1)
+             ON CONFLICT (account, channel_id) DO UPDATE
+             SET complete = CASE WHEN ?4 THEN MAX(complete, excluded.complete)
+                                 ELSE excluded.complete END,
+                 live = 1",
+            params![account, channel_id, !page.has_more, reaches],
+        )?;
+        tx.commit()
+    }
+
+    /// Store a page fetched from below the cached run.
+    fn merge_older(
+        &self,
+        account: &str,
+        channel_id: i64,
+        page: &MessagePage,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.0.lock().unwrap();
+        let tx = conn.transaction()?;
+        insert_all(&tx, account, None, &page.messages)?;
+        tx.execute(
+            "INSERT INTO channels (account, channel_id, complete) VALUES (?1, ?2, ?3)
+             ON CONFLICT (account, channel_id) DO UPDATE SET complete = excluded.complete",
+            params![account, channel_id, !page.has_more],
+        )?;
+        tx.commit()
+    }
+
+    /// Add a message from the socket or the outbox if its channel is live.
+    pub fn record(&self, account: &str, message: &Message) -> rusqlite::Result<()> {
+        let live = self
+            .channel(account, message.channel_id)?
+            .is_some_and(|(_, live)| live);
+        if live {
+            let conn = self.0.lock().unwrap();
+            insert_all(&conn, account, None, std::slice::from_ref(message))?;
+        }
+        Ok(())
+    }
+
+    /// Store history from another chat; see [`crate::import`]. Replaces what was
+    /// imported under the archive's `key` before, so a run with an edited mapping
+    /// moves messages to their new channels and drops those no longer mapped.
+    pub fn store_imported(
+        &self,
+        account: &str,
+        source: &str,
+        key: &str,
+        messages: &[Message],
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.0.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM messages WHERE account = ?1 AND import_key = ?2",
+            params![account, key],
+        )?;
+        insert_all(&tx, account, Some((source, key)), messages)?;
+        tx.commit()
+    }
+
+    /// The newest cached id of each live channel: everything up to it was seen,
+    /// as socket frames or history pages, since the socket last connected.
+    pub fn live_newest(&self, account: &str) -> rusqlite::Result<HashMap<i64, i64>> {
+        let conn = self.0.lock().unwrap();
+        let mut statement = conn.prepare_cached(
+            "SELECT messages.channel_id, MAX(messages.id) FROM messages
+             JOIN channels ON channels.account = messages.account
+                          AND channels.channel_id = messages.channel_id
+             WHERE messages.account = ?1 AND channels.live AND messages.imported_from IS NULL
+             GROUP BY messages.channel_id",
+        )?;
+        let rows = statement.query_map(params![account], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// The socket (re)connected: anything could have been missed while it was down.
+    pub fn mark_stale(&self, account: &str) -> rusqlite::Result<()> {
+        self.0.lock().unwrap().execute(
+            "UPDATE channels SET live = 0 WHERE account = ?1",
+            params![account],
+        )?;
+        Ok(())
+    }
+
+    pub fn forget_account(&self, account: &str) -> rusqlite::Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute("DELETE FROM messages WHERE account = ?1", params![account])?;
+        conn.execute("DELETE FROM channels WHERE account = ?1", params![account])?;
+        Ok(())
+    }
+}
+
+/// `imported` is the chat and archive key of imported history.
+fn insert_all(
+    conn: &Connection,
+    account: &str,
+    imported: Option<(&str, &str)>,
+    messages: &[Message],
+) -> rusqlite::Result<()> {
+    let (imported_from, import_key) = imported.unzip();
+    // The same message in another archive, e.g. overlapping exports, moves over
+    let mut statement = conn.prepare_cached(
+        "INSERT INTO messages
+         (account, id, channel_id, sender_id, sender_username, content, kind, image_url, created_at,
+          imported_from, import_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+         ON CONFLICT (account, id) DO UPDATE SET
+             sender_username = excluded.sender_username,
+             content = excluded.content,
+             kind = excluded.kind,
+             image_url = excluded.image_url,
+             import_key = excluded.import_key",
+    )?;
+    for message in messages {
+        statement.execute(params![
+            account,
+            message.id,
+            message.channel_id,
+            message.sender_id,
+            message.sender_username,
+            message.content,
+            kind_str(message.kind),
+            message.image_url,
+            message.created_at,
+            imported_from,
+            import_key,
+        ])?;
+    }
+    Ok(())
+}
+
+fn kind_str(kind: MessageKind) -> &'static str {
+    match kind {
+        MessageKind::User => "user",
+        MessageKind::Bot => "bot",
+        MessageKind::System => "system",
+    }
+}
+
+fn kind_from_str(kind: &str) -> MessageKind {
+    match kind {
+        "bot" => MessageKind::Bot,
+        "system" => MessageKind::System,
+        _ => MessageKind::User,
+    }
+}
+
+/// The message a socket frame carries, if it is one.
+pub fn frame_message(frame: &IncomingFrame) -> Option<Message> {
+    let IncomingFrame::Message {
+        id,
+        channel_id,
+        sender_id,
+        sender_username,
+        content,
+        message_type,
+        image_url,
+        timestamp,
+        created_at,
+    } = frame
+    else {
+        return None;
+    };
+    Some(Message {
+        id: *id,
+        channel_id: *channel_id,
+        sender_id: *sender_id,
+        sender_username: sender_username.clone().unwrap_or_default(),
+        content: content.clone(),
+        kind: message_type.unwrap_or(MessageKind::User),
+        image_url: image_url.clone(),
+        created_at: created_at.clone().or_else(|| timestamp.clone())?,
+    })
+}
+
+/// The frame the socket would have carried for `message`.
+pub fn message_frame(message: &Message) -> IncomingFrame {
+    IncomingFrame::Message {
+        id: message.id,
+        channel_id: message.channel_id,
+        sender_id: message.sender_id,
+        sender_username: Some(message.sender_username.clone()),
+        content: message.content.clone(),
+        message_type: Some(message.kind),
+        image_url: message.image_url.clone(),
+        timestamp: Some(message.created_at.clone()),
+        created_at: Some(message.created_at.clone()),
+    }
+}
+
+/// A page of `channel_id`'s history, from the cache where it can be.
+///
+/// The first page always asks the server, to catch up. Older pages come from the
+/// cache and only go to the server past the end of what is cached. When the server
+/// cannot be reached, whatever is cached is served instead.
+pub async fn page(
+    db: &HistoryDb,
+    api: &ApiClient,
+    account: &str,
+    channel_id: i64,
+    query: &MessagesQuery,
+) -> Result<HistoryPage> {
+    read_page(db, api, account, channel_id, query, true).await
+}
+
+/// Like [`page`], but a server that cannot be reached is an error: the cache may
+/// stop short of the newest messages, which a transcript must not hide.
+pub async fn page_online(
+    db: &HistoryDb,
+    api: &ApiClient,
+    account: &str,
+    channel_id: i64,
+    query: &MessagesQuery,
+) -> Result<HistoryPage> {
+    read_page(db, api, account, channel_id, query, false).await
+}
+
+async fn read_page(
+    db: &HistoryDb,
+    api: &ApiClient,
+    account: &str,
+    channel_id: i64,
+    query: &MessagesQuery,
+    serve_offline: bool,
+) -> Result<HistoryPage> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    let before = match &query.before {
+        Some(before) => match before.parse::<i64>() {
+            Ok(before) => Some(before),
+            // Not a message id; leave it to the server
+            Err(_) => return Ok(api.get_messages(channel_id, query).await?.into()),
+        },
+        None => None,
+    };
+
+    let fetched = match before {
+        None => {
+            let fetched = api.get_messages(channel_id, query).await;
+            if let Ok(page) = &fetched {
+                db.merge_latest(account, channel_id, page)?;
+            }
+            fetched.map(|_| ())
+        }
+        Some(_) => {
+            let (cached, more) = db.cached(account, channel_id, before, limit)?;
+            let complete = db
+                .channel(account, channel_id)?
+                .is_some_and(|(complete, _)| complete);
+            if cached.len() == limit as usize || (!more && complete) {
+                let has_more = more || !complete;
+                return Ok(HistoryPage {
+                    messages: cached,
+                    has_more,
+                });
+            }
+            // Continue below the oldest cached message
+            let cursor = cached.last().map(|cached| cached.message.id).or(before);
+            let older = MessagesQuery {
+                limit: Some(limit),
+                before: cursor.map(|id| id.to_string()),
+            };
+            let fetched = api.get_messages(channel_id, &older).await;
+            if let Ok(page) = &fetched {
+                db.merge_older(account, channel_id, page)?;
+            }
+            fetched.map(|_| ())
+        }
+    };
+
+    if let Err(err) = fetched {
+        match err {
+            ApiError::Network(_) if serve_offline => {
+                eprintln!("serving channel {channel_id} from the cache: {err}");
+            }
+            err => return Err(err.into()),
+        }
+    }
+    let (messages, more) = db.cached(account, channel_id, before, limit)?;
+    let complete = db
+        .channel(account, channel_id)?
+        .is_some_and(|(complete, _)| complete);
+    Ok(HistoryPage {
+        messages,
+        has_more: more || !complete,
+    })
+}
+
+/// Merge a socket frame into the cache.
+pub fn on_frame(app: &AppHandle, account: &str, frame: &IncomingFrame) {
+    let Some(message) = frame_message(frame) else {
+        return;
+    };
+    if let Err(err) = app.state::<HistoryDb>().record(account, &message) {
+        eprintln!("failed to cache message {}: {err}", message.id);
+    }
+}
+
+/// The newest cached page of a channel, without touching the network, so a
+/// channel switch can paint before [`history_page`] answers.
+#[tauri::command]
+pub fn history_cached(
+    accounts: State<'_, Accounts>,
+    db: State<'_, HistoryDb>,
+    channel_id: i64,
+    limit: Option<u32>,
+) -> Result<HistoryPage> {
+    let session = accounts.active().ok_or(crate::error::Error::SignedOut)?;
+    let (messages, more) = db.cached(
+        &session.id,
+        channel_id,
+        None,
+        limit.unwrap_or(DEFAULT_LIMIT),
+    )?;
+    let complete = db
+        .channel(&session.id, channel_id)?
+        .is_some_and(|(complete, _)| complete);
+    Ok(HistoryPage {
+        messages,
+        has_more: more || !complete,
+    })
+}
+
+/// A page of history through the cache: the newest without `before`, else the
+/// one older than that message id.
+#[tauri::command]
+pub async fn history_page(
+    accounts: State<'_, Accounts>,
+    db: State<'_, HistoryDb>,
+    channel_id: i64,
+    query: Option<MessagesQuery>,
+) -> Result<HistoryPage> {
+    let session = accounts.active().ok_or(crate::error::Error::SignedOut)?;
+    page(
+        &db,
+        &session.api,
+        &session.id,
+        channel_id,
+        &query.unwrap_or_default(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::ConnectionProfile;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const ACCOUNT: &str = "default:1";
+
+    fn message(id: i64) -> Message {
+        Message {
+            id,
+            channel_id: 3,
+            sender_id: 2,
+            sender_username: "bob".into(),
+            content: Some(format!("message {id}")),
+            kind: MessageKind::User,
+            image_url: None,
+            created_at: "2024-05-01T10:00:00Z".into(),
+        }
+    }
+
+    /// A server page: ids from `newest` down to `oldest`.
+    fn page_of(newest: i64, oldest: i64, has_more: bool) -> MessagePage {
+        MessagePage {
+            messages: (oldest..=newest).rev().map(message).collect(),
+            has_more,
+        }
+    }
+
+    fn ids(messages: &[CachedMessage]) -> Vec<i64> {
+        messages.iter().map(|cached| cached.message.id).collect()
+    }
+
+    #[test]
+    fn a_first_page_past_a_gap_replaces_the_run() {
+        let db = HistoryDb::in_memory();
+        db.merge_latest(ACCOUNT, 3, &page_of(20, 11, true)).unwrap();
+        db.merge_older(ACCOUNT, 3, &page_of(10, 6, false)).unwrap();
+        let (cached, more) = db.cached(ACCOUNT, 3, Some(8), 10).unwrap();
+        assert_eq!((ids(&cached), more), (vec![7, 6], false));
+
+        // Overlaps the run: extends it
+        db.merge_latest(ACCOUNT, 3, &page_of(25, 16, true)).unwrap();
+        assert_eq!(db.channel(ACCOUNT, 3).unwrap(), Some((true, true)));
+        assert_eq!(db.cached(ACCOUNT, 3, None, 100).unwrap().0.len(), 20);
+
+        // Starts past the newest cached message: the old run goes
+        db.merge_latest(ACCOUNT, 3, &page_of(60, 51, true)).unwrap();
+        let (cached, _) = db.cached(ACCOUNT, 3, None, 100).unwrap();
+        assert_eq!(ids(&cached), (51..=60).rev().collect::<Vec<_>>());
+        assert_eq!(db.channel(ACCOUNT, 3).unwrap(), Some((false, true)));
+    }
+
+    #[test]
+    fn socket_messages_join_only_live_channels() {
+        let db = HistoryDb::in_memory();
+        db.record(ACCOUNT, &message(1)).unwrap();
+        assert!(db.cached(ACCOUNT, 3, None, 10).unwrap().0.is_empty());
+
+        db.merge_latest(ACCOUNT, 3, &page_of(5, 1, false)).unwrap();
+        db.record(ACCOUNT, &message(6)).unwrap();
+        db.mark_stale(ACCOUNT).unwrap();
+        db.record(ACCOUNT, &message(9)).unwrap();
+        assert_eq!(ids(&db.cached(ACCOUNT, 3, None, 2).unwrap().0), [6, 5]);
+
+        db.forget_account(ACCOUNT).unwrap();
+        assert_eq!(db.channel(ACCOUNT, 3).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn backfills_below_the_cache_and_serves_it_offline() {
+        let server = MockServer::start().await;
+        let body =
+            |page: MessagePage| json!({ "messages": page.messages, "hasMore": page.has_more });
+        Mock::given(method("GET"))
+            .and(path("/api/channels/3/messages"))
+            .and(query_param("before", "11"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body(page_of(10, 6, false))))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/channels/3/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body(page_of(20, 11, true))))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let api = ApiClient::new(&ConnectionProfile::for_server(&format!(
+            "{}/",
+            server.uri()
+        )))
+        .unwrap();
+        let db = HistoryDb::in_memory();
+        let query = |before: Option<&str>| MessagesQuery {
+            limit: Some(10),
+            before: before.map(String::from),
+        };
+
+        let first = page(&db, &api, ACCOUNT, 3, &query(None)).await.unwrap();
+        assert_eq!(ids(&first.messages), (11..=20).rev().collect::<Vec<_>>());
+        assert!(first.has_more);
+        let older = page(&db, &api, ACCOUNT, 3, &query(Some("11")))
+            .await
+            .unwrap();
+        assert_eq!(
+            (ids(&older.messages), older.has_more),
+            (vec![10, 9, 8, 7, 6], false)
+        );
+        // Cached now: no second request
+        let again = page(&db, &api, ACCOUNT, 3, &query(Some("16")))
+            .await
+            .unwrap();
+        assert_eq!(ids(&again.messages), (6..=15).rev().collect::<Vec<_>>());
+
+        // Offline: the first page still comes from the cache
+        drop(server);
+        let offline = page(&db, &api, ACCOUNT, 3, &query(None)).await.unwrap();
+        assert_eq!(ids(&offline.messages), ids(&first.messages));
+    }
+}
@@ -0,0 +1,879 @@
+//! History from other chats, so a team moving over keeps its old conversations
+//! searchable. Reads Slack workspace exports (the zip from Settings → Import/Export)
+//! and Discord exports in DiscordChatExporter's JSON format, either one `.json`
+//! file or a zip of them.
+//!
+//! Imported messages only ever live in the local cache: they get negative ids, so
+//! they sort before anything the server has, and are tagged with the chat and the
+//! archive they came from. Importing an archive again replaces what it imported
+//! before; another Slack workspace or Discord server adds to it. Archive user and
+//! channel names are mapped onto closechat ones with an [`ImportMapping`] that is
+//! saved between runs.
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::oneshot;
+use zip::ZipArchive;
+
+use crate::accounts::Accounts;
+use crate::api::models::{ChannelKind, Message, MessageKind, User};
+use crate::error::{Error, Result};
+use crate::history::HistoryDb;
+use crate::store::JsonStore;
+use crate::tray::UnreadChannel;
+
+pub const IMPORT_MAPPING_FILE: &str = "import-mapping.json";
+
+/// Imported ids count up from here by creation time, far below any server id.
+const IMPORTED_ID_BASE: i64 = i64::MIN / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    Slack,
+    Discord,
+}
+
+impl ImportSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            ImportSource::Slack => "slack",
+            ImportSource::Discord => "discord",
+        }
+    }
+}
+
+/// A message as the archive has it, before names are mapped.
+#[derive(Debug, Clone, PartialEq)]
+struct SourceMessage {
+    channel: String,
+    author: String,
+    kind: MessageKind,
+    text: String,
+    attachment: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct Archive {
+    source: ImportSource,
+    /// Tells archives of one chat apart: the Slack workspace, or the Discord
+    /// server and channels.
+    key: String,
+    messages: Vec<SourceMessage>,
+}
+
+/// How archive names map onto closechat. Saved as [`IMPORT_MAPPING_FILE`], so
+/// it can also be edited by hand between runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportMapping {
+    /// Archive user name to closechat username. Unmapped users keep their
+    /// archive name.
+    #[serde(default)]
+    pub users: BTreeMap<String, String>,
+    /// Archive channel name to closechat channel id. Unmapped channels are skipped.
+    #[serde(default)]
+    pub channels: BTreeMap<String, i64>,
+}
+
+pub type ImportMappingStore = JsonStore<ImportMapping>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelCount {
+    pub name: String,
+    pub messages: usize,
+    pub channel_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserCount {
+    pub name: String,
+    pub messages: usize,
+    pub username: Option<String>,
+}
+
+/// What an import did, or on a dry run would do.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub source: ImportSource,
+    pub dry_run: bool,
+    /// Messages stored (or to be stored).
+    pub imported: usize,
+    /// Messages in channels with no mapping.
+    pub skipped: usize,
+    pub channels: Vec<ChannelCount>,
+    pub users: Vec<UserCount>,
+    /// The mapping used, with suggestions filled in for names closechat also has.
+    pub mapping: ImportMapping,
+}
+
+fn import_error(err: impl std::fmt::Display) -> Error {
+    Error::Import(err.to_string())
+}
+
+fn read_json<T: DeserializeOwned, R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Option<T>> {
+    let Ok(file) = zip.by_name(name) else {
+        return Ok(None);
+    };
+    serde_json::from_reader(file)
+        .map(Some)
+        .map_err(|err| import_error(format!("{name}: {err}")))
+}
+
+fn read_archive(path: &Path) -> Result<Archive> {
+    let file = File::open(path).map_err(import_error)?;
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if is_json {
+        return Ok(discord_archive(vec![read_discord(file)?]));
+    }
+    read_zip(ZipArchive::new(file).map_err(import_error)?)
+}
+
+fn read_zip<R: Read + Seek>(mut zip: ZipArchive<R>) -> Result<Archive> {
+    if let Some(root) = slack_root(&zip) {
+        read_slack(&mut zip, &root)
+    } else {
+        let mut exports = Vec::new();
+        for index in 0..zip.len() {
+            let entry = zip.by_index(index).map_err(import_error)?;
+            if entry.is_file() && entry.name().ends_with(".json") {
+                exports.push(read_discord(entry)?);
+            }
+        }
+        Ok(discord_archive(exports))
+    }
+}
+
+// ── Slack ──
+
+#[derive(Deserialize)]
+struct SlackUser {
+    id: String,
+    name: String,
+    #[serde(default)]
+    team_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SlackChannel {
+    #[serde(default)]
+    id: Option<String>,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct SlackFile {
+    #[serde(default)]
+    url_private: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SlackMessage {
+    ts: String,
+    #[serde(default)]
+    subtype: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    bot_id: Option<String>,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    files: Vec<SlackFile>,
+}
+
+/// Where a Slack export's `channels.json` sits: at the root, or inside the one
+/// folder the export was zipped with (`""` or `"<folder>/"`). `None` when the zip
+/// is not a Slack export.
+fn slack_root<R: Read + Seek>(zip: &ZipArchive<R>) -> Option<String> {
+    if zip.index_for_name("channels.json").is_some() {
+        return Some(String::new());
+    }
+    // macOS adds resource forks beside the folder when zipping it
+    let mut tops = zip
+        .file_names()
+        .filter(|name| !name.starts_with("__MACOSX/"))
+        .map(|name| name.split_once('/').map(|(top, _)| top));
+    let top = tops.next()??;
+    if !tops.all(|other| other == Some(top)) {
+        return None;
+    }
+    let root = format!("{top}/");
+    zip.index_for_name(&format!("{root}channels.json"))
+        .map(|_| root)
+}
+
+/// Public channels (`channels.json`) and private ones (`groups.json`), one folder
+/// of daily `<date>.json` files each, all under `root`. DMs are skipped: their
+/// folders are named by id and have nothing to map onto.
+fn read_slack<R: Read + Seek>(zip: &mut ZipArchive<R>, root: &str) -> Result<Archive> {
+    let users: Vec<SlackUser> = read_json(zip, &format!("{root}users.json"))?.unwrap_or_default();
+    let team = users.iter().find_map(|u| u.team_id.clone());
+    let users: HashMap<String, String> = users.into_iter().map(|u| (u.id, u.name)).collect();
+    let mut channels = HashSet::new();
+    let mut channel_ids = BTreeSet::new();
+    for list in ["channels.json", "groups.json"] {
+        let found: Vec<SlackChannel> =
+            read_json(zip, &format!("{root}{list}"))?.unwrap_or_default();
+        for channel in found {
+            channel_ids.insert(channel.id.unwrap_or_else(|| channel.name.clone()));
+            channels.insert(channel.name);
+        }
+    }
+    // Exports without a team id are told apart by their channels
+    let key = match team {
+        Some(team) => format!("slack:{team}"),
+        None => format!("slack:{:016x}", fnv(channel_ids.iter().map(String::as_str))),
+    };
+
+    let days: Vec<String> = zip
+        .file_names()
+        .filter_map(|name| name.strip_prefix(root))
+        .filter(|name| {
+            name.split_once('/').is_some_and(|(channel, day)| {
+                channels.contains(channel) && day.ends_with(".json") && !day.contains('/')
+            })
+        })
+        .map(String::from)
+        .collect();
+    let mut messages = Vec::new();
+    for day in days {
+        let channel = day
+            .split_once('/')
+            .map(|(c, _)| c.to_string())
+            .unwrap_or_default();
+        let found: Vec<SlackMessage> = read_json(zip, &format!("{root}{day}"))?.unwrap_or_default();
+        for message in found {
+            let Some(created_at) = slack_time(&message.ts) else {
+                continue;
+            };
+            let kind = match message.subtype.as_deref() {
+                Some("bot_message") => MessageKind::Bot,
+                Some(
+                    "channel_join" | "channel_leave" | "channel_topic" | "channel_purpose"
+                    | "channel_name" | "channel_archive" | "pinned_item",
+                ) => MessageKind::System,
+                _ if message.bot_id.is_some() => MessageKind::Bot,
+                _ => MessageKind::User,
+            };
+            let author = message
+                .user
+                .as_ref()
+                .and_then(|id| users.get(id).cloned())
+                .or(message.username)
+                .or(message.user)
+                .unwrap_or_else(|| "unknown".into());
+            messages.push(SourceMessage {
+                channel: channel.clone(),
+                author,
+                kind,
+                text: slack_text(&message.text, &users),
+                attachment: message.files.into_iter().find_map(|f| f.url_private),
+                created_at,
+            });
+        }
+    }
+    Ok(Archive {
+        source: ImportSource::Slack,
+        key,
+        messages,
+    })
+}
+
+/// `"1714557600.000200"`: seconds and microseconds.
+fn slack_time(ts: &str) -> Option<DateTime<Utc>> {
+    let (secs, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    let micros: u32 = format!("{micros:0<6}").get(..6)?.parse().ok()?;
+    DateTime::from_timestamp(secs.parse().ok()?, micros * 1000)
+}
+
+/// Slack's markup: `<@U123>` mentions, `<#C123|general>` channels, `<!here>`,
+/// `<https://…|label>` links and HTML-escaped `&`, `<`, `>`.
+fn slack_text(text: &str, users: &HashMap<String, String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            // A lone `<`: the rest is plain text
+            rest = &rest[start..];
+            break;
+        };
+        let inner = &rest[start + 1..start + end];
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (inner, None),
+        };
+        if let Some(id) = target.strip_prefix('@') {
+            let name = label.or(users.get(id).map(String::as_str)).unwrap_or(id);
+            out.push('@');
+            out.push_str(name);
+        } else if let Some(id) = target.strip_prefix('#') {
+            out.push('#');
+            out.push_str(label.unwrap_or(id));
+        } else if let Some(special) = target.strip_prefix('!') {
+            out.push('@');
+            out.push_str(label.unwrap_or(special));
+        } else {
+            match label {
+                Some(label) if label != target => {
+                    out.push_str(&format!("{label} ({target})"));
+                }
+                _ => out.push_str(target),
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// ── Discord ──
+
+#[derive(Deserialize)]
+struct DiscordExport {
+    #[serde(default)]
+    guild: Option<DiscordGuild>,
+    channel: DiscordChannel,
+    #[serde(default)]
+    messages: Vec<DiscordMessage>,
+}
+
+#[derive(Deserialize)]
+struct DiscordGuild {
+    #[serde(default)]
+    id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DiscordChannel {
+    #[serde(default)]
+    id: Option<String>,
+    name: String,
+}
+
+/// One exported channel: where it is, as `<server id>/<channel id>`, and its
+/// messages.
+struct DiscordChannelExport {
+    origin: String,
+    messages: Vec<SourceMessage>,
+}
+
+fn discord_archive(exports: Vec<DiscordChannelExport>) -> Archive {
+    let origins: BTreeSet<&str> = exports.iter().map(|e| e.origin.as_str()).collect();
+    Archive {
+        source: ImportSource::Discord,
+        key: format!("discord:{:016x}", fnv(origins)),
+        messages: exports.into_iter().flat_map(|e| e.messages).collect(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscordMessage {
+    #[serde(rename = "type", default)]
+    kind: String,
+    timestamp: DateTime<Utc>,
+    #[serde(default)]
+    content: String,
+    author: DiscordAuthor,
+    #[serde(default)]
+    attachments: Vec<DiscordAttachment>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscordAuthor {
+    name: String,
+    #[serde(default)]
+    is_bot: bool,
+}
+
+#[derive(Deserialize)]
+struct DiscordAttachment {
+    url: String,
+}
+
+fn read_discord(reader: impl Read) -> Result<DiscordChannelExport> {
+    let export: DiscordExport = serde_json::from_reader(reader).map_err(import_error)?;
+    let guild = export.guild.and_then(|guild| guild.id).unwrap_or_default();
+    let channel = export.channel.name;
+    let origin = format!(
+        "{guild}/{}",
+        export.channel.id.as_deref().unwrap_or(&channel)
+    );
+    let messages = export
+        .messages
+        .into_iter()
+        .map(|message| {
+            let kind = match message.kind.as_str() {
+                "" | "Default" | "Reply" if message.author.is_bot => MessageKind::Bot,
+                "" | "Default" | "Reply" => MessageKind::User,
+                _ => MessageKind::System,
+            };
+            let text = if message.content.is_empty() && kind == MessageKind::System {
+                format!("{} ({})", message.author.name, message.kind)
+            } else {
+                message.content
+            };
+            SourceMessage {
+                channel: channel.clone(),
+                author: message.author.name,
+                kind,
+                text,
+                attachment: message.attachments.into_iter().next().map(|a| a.url),
+                created_at: message.timestamp,
+            }
+        })
+        .collect();
+    Ok(DiscordChannelExport { origin, messages })
+}
+
+// ── Mapping ──
+
+/// Fill in archive names that closechat also has, leaving choices already made.
+fn suggest(
+    mapping: &mut ImportMapping,
+    archive: &Archive,
+    channels: &[UnreadChannel],
+    users: &[User],
+) {
+    for message in &archive.messages {
+        if !mapping.channels.contains_key(&message.channel) {
+            let found = channels.iter().find(|c| {
+                c.kind == ChannelKind::Channel && c.name.eq_ignore_ascii_case(&message.channel)
+            });
+            if let Some(channel) = found {
+                mapping.channels.insert(message.channel.clone(), channel.id);
+            }
+        }
+        if !mapping.users.contains_key(&message.author) {
+            let found = users
+                .iter()
+                .find(|u| u.username.eq_ignore_ascii_case(&message.author));
+            if let Some(user) = found {
+                mapping
+                    .users
+                    .insert(message.author.clone(), user.username.clone());
+            }
+        }
+    }
+}
+
+/// FNV-1a, so ids and keys stay the same across builds and re-imports.
+fn fnv<'a>(parts: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn fingerprint(message: &SourceMessage) -> u64 {
+    fnv([message.channel.as_str(), &message.author, &message.text])
+}
+
+/// Map the archive onto closechat messages. Importing the same archive again
+/// yields the same ids, so it updates rather than duplicates.
+fn plan(
+    archive: &Archive,
+    mapping: &ImportMapping,
+    users: &[User],
+    dry_run: bool,
+) -> (Vec<Message>, ImportReport) {
+    let mut channels: BTreeMap<&str, ChannelCount> = BTreeMap::new();
+    let mut authors: BTreeMap<&str, UserCount> = BTreeMap::new();
+    let mut messages = Vec::new();
+    let mut ids = HashSet::new();
+    let mut skipped = 0;
+
+    let mut sorted: Vec<&SourceMessage> = archive.messages.iter().collect();
+    sorted.sort_by_key(|m| m.created_at);
+    for source in sorted {
+        let channel_id = mapping.channels.get(&source.channel).copied();
+        let count = channels.entry(&source.channel).or_insert(ChannelCount {
+            name: source.channel.clone(),
+            messages: 0,
+            channel_id,
+        });
+        count.messages += 1;
+        let username = mapping.users.get(&source.author).cloned();
+        let author = authors.entry(&source.author).or_insert(UserCount {
+            name: source.author.clone(),
+            messages: 0,
+            username: username.clone(),
+        });
+        author.messages += 1;
+        let Some(channel_id) = channel_id else {
+            skipped += 1;
+            continue;
+        };
+
+        let millis = source.created_at.timestamp_millis();
+        let mut id = IMPORTED_ID_BASE + millis * 1000 + (fingerprint(source) % 1000) as i64;
+        while !ids.insert(id) {
+            id += 1;
+        }
+        let sender_id = username
+            .as_ref()
+            .and_then(|name| users.iter().find(|u| &u.username == name))
+            .map_or(0, |u| u.id);
+        messages.push(Message {
+            id,
+            channel_id,
+            sender_id,
+            sender_username: username.unwrap_or_else(|| source.author.clone()),
+            content: Some(source.text.clone()).filter(|text| !text.is_empty()),
+            kind: source.kind,
+            image_url: source.attachment.clone(),
+            created_at: source.created_at.to_rfc3339(),
+        });
+    }
+
+    let report = ImportReport {
+        source: archive.source,
+        dry_run,
+        imported: messages.len(),
+        skipped,
+        channels: channels.into_values().collect(),
+        users: authors.into_values().collect(),
+        mapping: mapping.clone(),
+    };
+    (messages, report)
+}
+
+// ── Commands ──
+
+/// Pick a Slack or Discord export; `None` if the dialog was cancelled.
+#[tauri::command]
+pub async fn import_pick_archive(app: AppHandle) -> Option<PathBuf> {
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("Import chat history")
+        .add_filter("Slack or Discord export", &["zip", "json"])
+        .pick_file(move |path| {
+            let _ = tx.send(path);
+        });
+    rx.await.ok().flatten()?.into_path().ok()
+}
+
+/// Import an archive into the active account's cache. `mapping` is used instead
+/// of the saved one; a dry run only reports what would be imported and saves
+/// nothing, the mapping included.
+#[tauri::command]
+pub async fn import_history(
+    accounts: State<'_, Accounts>,
+    db: State<'_, HistoryDb>,
+    store: State<'_, ImportMappingStore>,
+    path: PathBuf,
+    mapping: Option<ImportMapping>,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let session = accounts.active().ok_or(Error::SignedOut)?;
+    let archive = tauri::async_runtime::spawn_blocking(move || read_archive(&path))
+        .await
+        .map_err(import_error)??;
+    let users = session.api.list_users().await?;
+
+    // A dry run previews the passed mapping without saving it
+    let mut mapping = mapping.unwrap_or_else(|| store.get());
+    suggest(&mut mapping, &archive, &session.channels(), &users);
+    let (messages, report) = plan(&archive, &mapping, &users, dry_run);
+    if !dry_run {
+        db.store_imported(
+            &session.id,
+            archive.source.as_str(),
+            &archive.key,
+            &messages,
+        )?;
+        store.update(|saved| *saved = mapping);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::SearchQuery;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn slack_zip() -> Cursor<Vec<u8>> {
+        slack_workspace_zip("T1", "")
+    }
+
+    /// An export of workspace `team`, with its files under `folder`.
+    fn slack_workspace_zip(team: &str, folder: &str) -> Cursor<Vec<u8>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let users = format!(
+            r#"[{{"id":"U1","name":"alice","team_id":"{team}"}},{{"id":"U2","name":"bob","team_id":"{team}"}}]"#
+        );
+        let files = [
+            ("users.json", users.as_str()),
+            ("channels.json", r#"[{"id":"C1","name":"general"}]"#),
+            (
+                "general/2024-05-01.json",
+                r#"[
+                    {"type":"message","subtype":"channel_join","user":"U2","text":"<@U2> has joined the channel","ts":"1714557600.000100"},
+                    {"type":"message","user":"U1","text":"hi <@U2>, see <https://example.com|the doc> &amp; <#C1|general>","ts":"1714557660.000200"},
+                    {"type":"message","subtype":"bot_message","username":"deploybot","bot_id":"B1","text":"deployed","ts":"1714557720.000000"}
+                ]"#,
+            ),
+            (
+                "D123/2024-05-01.json",
+                r#"[{"type":"message","user":"U1","text":"dm","ts":"1714557600.0"}]"#,
+            ),
+        ];
+        for (name, body) in files {
+            zip.start_file(format!("{folder}{name}"), SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(body.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap()
+    }
+
+    #[test]
+    fn reads_slack_exports() {
+        let archive = read_zip(ZipArchive::new(slack_zip()).unwrap()).unwrap();
+        let summary: Vec<_> = archive
+            .messages
+            .iter()
+            .map(|m| {
+                (
+                    m.channel.as_str(),
+                    m.author.as_str(),
+                    m.kind,
+                    m.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "general",
+                    "bob",
+                    MessageKind::System,
+                    "@bob has joined the channel"
+                ),
+                (
+                    "general",
+                    "alice",
+                    MessageKind::User,
+                    "hi @bob, see the doc (https://example.com) & #general"
+                ),
+                ("general", "deploybot", MessageKind::Bot, "deployed"),
+            ]
+        );
+        assert_eq!(
+            archive.messages[1].created_at.to_rfc3339(),
+            "2024-05-01T10:01:00.000200+00:00"
+        );
+    }
+
+    #[test]
+    fn reads_slack_exports_zipped_with_their_folder() {
+        let texts = |archive: &Archive| -> Vec<(String, String)> {
+            let messages = archive.messages.iter();
+            messages
+                .map(|m| (m.channel.clone(), m.text.clone()))
+                .collect()
+        };
+        let flat = read_zip(ZipArchive::new(slack_zip()).unwrap()).unwrap();
+        let nested =
+            read_zip(ZipArchive::new(slack_workspace_zip("T1", "export/")).unwrap()).unwrap();
+        assert_eq!(nested.source, ImportSource::Slack);
+        assert_eq!(nested.key, flat.key);
+        assert_eq!(texts(&nested), texts(&flat));
+    }
+
+    #[test]
+    fn lone_angle_brackets_stay_as_text() {
+        let users = HashMap::from([("U1".to_string(), "alice".to_string())]);
+        assert_eq!(slack_text("a <@U1> b < c", &users), "a @alice b < c");
+        assert_eq!(slack_text("x < y", &users), "x < y");
+    }
+
+    #[test]
+    fn reads_discord_exports() {
+        let json = r#"{
+            "guild": {"name": "Old server"},
+            "channel": {"name": "general"},
+            "messages": [
+                {"type": "Default", "timestamp": "2024-05-01T10:00:00+00:00", "content": "hello",
+                 "author": {"name": "alice", "isBot": false},
+                 "attachments": [{"url": "https://cdn.example/a.png"}]},
+                {"type": "ChannelPinnedMessage", "timestamp": "2024-05-01T10:01:00+00:00", "content": "",
+                 "author": {"name": "bob"}}
+            ]
+        }"#;
+        let messages = read_discord(json.as_bytes()).unwrap().messages;
+        assert_eq!(messages[0].text, "hello");
+        assert_eq!(
+            messages[0].attachment.as_deref(),
+            Some("https://cdn.example/a.png")
+        );
+        assert_eq!(messages[1].kind, MessageKind::System);
+        assert_eq!(messages[1].text, "bob (ChannelPinnedMessage)");
+    }
+
+    #[test]
+    fn imported_history_is_mapped_and_searchable() {
+        let mut archive = read_zip(ZipArchive::new(slack_zip()).unwrap()).unwrap();
+        archive.messages.push(SourceMessage {
+            channel: "random".into(),
+            ..archive.messages[1].clone()
+        });
+        let users: Vec<User> =
+            serde_json::from_str(r#"[{"id":7,"username":"Alice","status":"online"}]"#).unwrap();
+        let channels = vec![UnreadChannel {
+            id: 3,
+            name: "General".into(),
+            kind: ChannelKind::Channel,
+            count: 0,
+        }];
+        let mut mapping = ImportMapping::default();
+        suggest(&mut mapping, &archive, &channels, &users);
+        assert_eq!(mapping.channels, BTreeMap::from([("general".into(), 3)]));
+        assert_eq!(
+            mapping.users,
+            BTreeMap::from([("alice".into(), "Alice".into())])
+        );
+
+        let (messages, report) = plan(&archive, &mapping, &users, false);
+        assert_eq!((report.imported, report.skipped), (3, 1));
+        assert!(messages.windows(2).all(|pair| pair[0].id < pair[1].id));
+        assert!(messages.iter().all(|m| m.id < 0 && m.channel_id == 3));
+        assert_eq!(
+            (messages[1].sender_id, messages[1].sender_username.as_str()),
+            (7, "Alice")
+        );
+        let (again, _) = plan(&archive, &mapping, &users, false);
+        assert_eq!(again, messages);
+
+        let db = HistoryDb::in_memory();
+        db.store_imported("me", "slack", &archive.key, &messages)
+            .unwrap();
+        let query = SearchQuery::parse("doc").unwrap();
+        let hits = db.search("me", &query, &channels, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].imported_from.as_deref(), Some("slack"));
+    }
+
+    #[test]
+    fn importing_again_follows_the_edited_mapping() {
+        let archive = read_zip(ZipArchive::new(slack_zip()).unwrap()).unwrap();
+        let users: Vec<User> =
+            serde_json::from_str(r#"[{"id":7,"username":"alice","status":"online"}]"#).unwrap();
+        let db = HistoryDb::in_memory();
+        let stored = || -> Vec<(i64, i64)> {
+            let conn = db.lock();
+            let mut statement = conn
+                .prepare(
+                    "SELECT channel_id, sender_id FROM messages
+                     WHERE account = 'me' AND sender_username = 'alice'",
+                )
+                .unwrap();
+            let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?)));
+            rows.unwrap().map(|row| row.unwrap()).collect()
+        };
+
+        let mut mapping = ImportMapping::default();
+        mapping.channels.insert("general".into(), 3);
+        let (messages, _) = plan(&archive, &mapping, &users, false);
+        db.store_imported("me", "slack", &archive.key, &messages)
+            .unwrap();
+        assert_eq!(stored(), [(3, 0)]);
+
+        mapping.channels.insert("general".into(), 4);
+        mapping.users.insert("alice".into(), "alice".into());
+        let (messages, _) = plan(&archive, &mapping, &users, false);
+        db.store_imported("me", "slack", &archive.key, &messages)
+            .unwrap();
+        assert_eq!(stored(), [(4, 7)]);
+
+        mapping.channels.clear();
+        let (messages, _) = plan(&archive, &mapping, &users, false);
+        db.store_imported("me", "slack", &archive.key, &messages)
+            .unwrap();
+        assert!(stored().is_empty());
+    }
+
+    #[test]
+    fn another_workspace_adds_to_the_first() {
+        let first = read_zip(ZipArchive::new(slack_workspace_zip("T1", "")).unwrap()).unwrap();
+        let mut second = read_zip(ZipArchive::new(slack_workspace_zip("T2", "")).unwrap()).unwrap();
+        for message in &mut second.messages {
+            message.text.push_str(" again");
+        }
+        assert_ne!(first.key, second.key);
+        let users: Vec<User> = Vec::new();
+        let mut mapping = ImportMapping::default();
+        mapping.channels.insert("general".into(), 3);
+        let db = HistoryDb::in_memory();
+        let stored = || -> i64 {
+            db.lock()
+                .query_row(
+                    "SELECT COUNT(*) FROM messages WHERE imported_from = 'slack'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+
+        for archive in [&first, &second] {
+            let (messages, _) = plan(archive, &mapping, &users, false);
+            db.store_imported("me", "slack", &archive.key, &messages)
+                .unwrap();
+        }
+        assert_eq!(stored(), 6);
+
+        // Only the first workspace's messages are replaced
+        mapping.channels.clear();
+        let (messages, _) = plan(&first, &mapping, &users, false);
+        db.store_imported("me", "slack", &first.key, &messages)
+            .unwrap();
+        assert_eq!(stored(), 3);
+    }
+
+    #[test]
+    fn discord_archives_are_told_apart_by_their_channels() {
+        let export = |guild: &str, channel: &str| {
+            format!(
+                r#"{{"guild": {{"id": "{guild}"}}, "channel": {{"id": "{channel}", "name": "general"}},
+                    "messages": []}}"#
+            )
+        };
+        let read = |json: String| read_discord(json.as_bytes()).unwrap();
+        let one = discord_archive(vec![read(export("1", "10"))]);
+        let same = discord_archive(vec![read(export("1", "10"))]);
+        let other_server = discord_archive(vec![read(export("2", "10"))]);
+        let both = discord_archive(vec![read(export("1", "10")), read(export("1", "11"))]);
+        assert_eq!(one.key, same.key);
+        assert_ne!(one.key, other_server.key);
+        assert_ne!(one.key, both.key);
+    }
+}
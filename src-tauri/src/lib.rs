@@ -1,101 +1,122 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use tauri::{
-    image::Image,
-    menu::{MenuBuilder, MenuItemBuilder},
-    tray::TrayIconBuilder,
-    Emitter, Listener, Manager,
-};
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
-
-static MINIMIZED: AtomicBool = AtomicBool::new(false);
-const NORMAL_WIDTH: f64 = 400.0;
-const NORMAL_HEIGHT: f64 = 500.0;
-const MINI_SIZE: f64 = 48.0;
-
-fn set_window_minimized(app: &tauri::AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.show();
-        let scale = window.scale_factor().unwrap_or(1.0);
-        let phys_size = (MINI_SIZE * scale) as i32;
-        let mini_size = tauri::PhysicalSize::new(phys_size, phys_size);
-
-        let _ = window.unmaximize();
-        let _ = window.set_resizable(false);
-        let _ = window.set_always_on_top(true);
-        let _ = window.set_skip_taskbar(true);
-        let _ = window.set_size(mini_size);
-
-        // Position at top-right of the current monitor
-        if let Some(monitor) = window.current_monitor().unwrap_or(None) {
-            let monitor_size = monitor.size();
-            let monitor_pos = monitor.position();
-            let margin_top = (20.0 * scale) as i32;
-            let margin_right = (20.0 * scale) as i32;
-            let x = monitor_pos.x + monitor_size.width as i32 - phys_size - margin_right;
-            let y = monitor_pos.y + margin_top;
-            let _ = window.set_position(tauri::PhysicalPosition::new(x, y));
-        }
-
-        MINIMIZED.store(true, Ordering::SeqCst);
-        let _ = app.emit("minimize-state-changed", true);
-    }
-}
-
-fn restore_window(app: &tauri::AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.show();
-        let scale = window.scale_factor().unwrap_or(1.0);
-        let phys_w = (NORMAL_WIDTH * scale) as i32;
-        let phys_h = (NORMAL_HEIGHT * scale) as i32;
-
-        let _ = window.set_resizable(true);
-        let _ = window.set_skip_taskbar(false);
-        let _ = window.set_size(tauri::PhysicalSize::new(phys_w, phys_h));
-
-        if let Some(monitor) = window.current_monitor().unwrap_or(None) {
-            let monitor_size = monitor.size();
-            let monitor_pos = monitor.position();
-            let margin_bottom = (60.0 * scale) as i32;
-            let margin_right = (20.0 * scale) as i32;
-            let x = monitor_pos.x + monitor_size.width as i32 - phys_w - margin_right;
-            let y = monitor_pos.y + monitor_size.height as i32 - phys_h - margin_bottom;
-            let _ = window.set_position(tauri::PhysicalPosition::new(x, y));
-        }
-
-        let _ = window.set_focus();
-        MINIMIZED.store(false, Ordering::SeqCst);
-        let _ = app.emit("minimize-state-changed", false);
-    }
-}
-
-fn toggle_mini(app: &tauri::AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let is_visible = window.is_visible().unwrap_or(true);
-        if !is_visible {
-            let _ = window.show();
-            let _ = window.set_focus();
-
-            if MINIMIZED.load(Ordering::SeqCst) {
-                restore_window(app);
-            }
-            return;
-        }
-    }
-
-    if MINIMIZED.load(Ordering::SeqCst) {
-        restore_window(app);
-    } else {
-        set_window_minimized(app);
-    }
-}
+mod accounts;
+mod api;
+mod backfill;
+mod badge;
+mod credentials;
+mod error;
+mod export;
+mod history;
+mod import;
+mod outbox;
+mod presence;
+mod profiles;
+mod proxy;
+mod search;
+mod settings;
+mod shortcuts;
+mod store;
+#[cfg(target_os = "linux")]
+mod system_watch;
+mod tls;
+mod tray;
+mod window;
+mod window_mode;
+mod window_state;
+mod ws;
+
+use accounts::Accounts;
+use credentials::Credentials;
+use history::HistoryDb;
+use import::ImportMappingStore;
+use outbox::Outbox;
+use presence::PresenceState;
+use profiles::SelectedProfile;
+use settings::SettingsStore;
+use shortcuts::ShortcutRegistry;
+use std::sync::Mutex;
+use tauri::{Listener, Manager};
+use window_mode::WindowModeMachine;
+use window_state::{PendingSave, WindowStateStore};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let startup_profile = profiles::parse_args(std::env::args().skip(1));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(shortcuts::handle_shortcut)
+                .build(),
+        )
+        .invoke_handler(tauri::generate_handler![
+            window::commands::get_window_state,
+            window::commands::set_mode,
+            window::commands::toggle_mini,
+            window::commands::set_always_on_top_policy,
+            window::commands::get_window_settings,
+            window::commands::set_window_settings,
+            shortcuts::get_shortcuts,
+            shortcuts::set_shortcut,
+            tray::set_unread_channels,
+            presence::get_presence,
+            presence::set_presence,
+            presence::report_auto_presence,
+            presence::push_presence,
+            profiles::get_profiles,
+            profiles::save_profile,
+            profiles::delete_profile,
+            profiles::switch_profile,
+            accounts::list_accounts,
+            accounts::migrate_tokens,
+            accounts::add_account,
+            accounts::remove_account,
+            accounts::switch_account,
+            api::commands::api_signup,
+            api::commands::api_login,
+            api::commands::api_verify_token,
+            api::commands::api_list_users,
+            api::commands::api_get_me,
+            api::commands::api_update_me,
+            api::commands::api_get_user,
+            api::commands::api_search_users,
+            api::commands::api_list_channels,
+            api::commands::api_create_channel,
+            api::commands::api_get_channel,
+            api::commands::api_join_channel,
+            api::commands::api_leave_channel,
+            api::commands::api_channel_members,
+            api::commands::api_join_by_invite_code,
+            api::commands::api_add_member,
+            api::commands::api_remove_member,
+            api::commands::api_create_invite,
+            api::commands::api_list_invites,
+            api::commands::api_revoke_invite,
+            api::commands::api_get_messages,
+            api::commands::api_send_message,
+            api::commands::api_mark_channel_read,
+            api::commands::api_get_or_create_dm,
+            api::commands::api_list_dms,
+            ws::ws_connect,
+            ws::ws_disconnect,
+            ws::ws_send,
+            ws::ws_latency,
+            ws::get_heartbeat_settings,
+            ws::set_heartbeat_settings,
+            outbox::outbox_send,
+            outbox::outbox_list,
+            outbox::outbox_retry,
+            outbox::outbox_discard,
+            history::history_cached,
+            history::history_page,
+            search::search_messages,
+            export::export_channel,
+            import::import_pick_archive,
+            import::import_history,
+        ])
         .setup(|app| {
             let window = app.get_webview_window("main").unwrap();
 
@@ -104,99 +125,71 @@ pub fn run() {
 
             let _ = window.set_skip_taskbar(false);
 
-            // Position window at bottom-right of screen
-            if let Some(monitor) = window.current_monitor().unwrap_or(None) {
-                let monitor_size = monitor.size();
-                let monitor_pos = monitor.position();
-                let scale = window.scale_factor().unwrap_or(1.0);
-
-                let win_width = 400.0;
-                let win_height = 500.0;
-
-                let phys_w = (win_width * scale) as i32;
-                let phys_h = (win_height * scale) as i32;
-
-                let margin_bottom = 60.0;
-                let margin_right = 20.0;
-
-                let phys_margin_b = (margin_bottom * scale) as i32;
-                let phys_margin_r = (margin_right * scale) as i32;
-
-                let x = monitor_pos.x + monitor_size.width as i32 - phys_w - phys_margin_r;
-                let y = monitor_pos.y + monitor_size.height as i32 - phys_h - phys_margin_b;
+            // Restore the geometry and mode from the last run
+            app.manage(SettingsStore::load(app.handle(), settings::SETTINGS_FILE));
+            app.manage(WindowStateStore::load(
+                app.handle(),
+                window_state::STATE_FILE,
+            ));
+            app.manage(PendingSave::default());
+            let saved = app.state::<WindowStateStore>().get();
+            let machine = WindowModeMachine::new(saved.display_mode, saved.minimized);
+            let mode = machine.mode();
+            app.manage(Mutex::new(machine));
+            window::apply_mode(&window, mode);
+
+            // Server from --profile/--server, else the last one switched to. Saved
+            // accounts sign back in with their stored tokens in the background.
+            let profile = profiles::resolve_startup(app.handle(), startup_profile);
+            app.manage(SelectedProfile::new(profile));
+            app.manage(Credentials::new(app.handle()));
+            app.manage(Accounts::default());
+            app.manage(HistoryDb::open(app.handle()));
+            // Messages queued before a restart go out once their account reconnects
+            app.manage(Outbox::load(app.handle()));
+            app.manage(ImportMappingStore::load(
+                app.handle(),
+                import::IMPORT_MAPPING_FILE,
+            ));
+            outbox::spawn(app.handle());
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move { accounts::restore(&handle).await });
+            // Reconnect right after resume or a network change, not after the backoff
+            #[cfg(target_os = "linux")]
+            system_watch::spawn(app.handle());
 
-                let _ = window.set_position(tauri::PhysicalPosition::new(x, y));
-            }
+            // Presence pinned from the tray survives restarts
+            let choice = app.state::<SettingsStore>().get().presence;
+            app.manage(Mutex::new(PresenceState::new(choice)));
 
             // System tray
-            let show_hide = MenuItemBuilder::with_id("show_hide", "Show / Hide").build(app)?;
-            let quit = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
-
-            let menu = MenuBuilder::new(app)
-                .item(&show_hide)
-                .separator()
-                .item(&quit)
-                .build()?;
-
-            let tray_icon = Image::from_path("icons/32x32.png").unwrap_or_else(|_| {
-                Image::from_bytes(include_bytes!("../icons/32x32.png"))
-                    .expect("failed to load embedded tray icon")
-            });
-
-            let _tray = TrayIconBuilder::new()
-                .icon(tray_icon)
-                .menu(&menu)
-                .tooltip("Close Chat")
-                .on_menu_event(|app, event| match event.id().as_ref() {
-                    "show_hide" => toggle_mini(app),
-                    "quit" => {
-                        app.exit(0);
-                    }
-                    _ => {}
-                })
-                .on_tray_icon_event(|tray, event| {
-                    if let tauri::tray::TrayIconEvent::Click {
-                        button: tauri::tray::MouseButton::Left,
-                        button_state: tauri::tray::MouseButtonState::Up,
-                        ..
-                    } = event
-                    {
-                        toggle_mini(tray.app_handle());
-                    }
-                })
-                .build(app)?;
-
-            // Global shortcut: Ctrl+\ to toggle mini mode
-            let shortcut = Shortcut::new(Some(Modifiers::CONTROL), Code::Backslash);
+            tray::init(app.handle())?;
             let handle = app.handle().clone();
-            app.global_shortcut()
-                .on_shortcut(shortcut, move |_app, _shortcut, event| {
-                    if event.state() == ShortcutState::Pressed {
-                        toggle_mini(&handle);
-                    }
-                })?;
+            app.listen(
+                tray::CONNECTION_STATUS_EVENT,
+                move |event| match serde_json::from_str(event.payload()) {
+                    Ok(status) => tray::set_connection_status(&handle, status),
+                    Err(err) => eprintln!("bad {} payload: {err}", tray::CONNECTION_STATUS_EVENT),
+                },
+            );
+
+            // Global shortcuts from settings. Bindings that are invalid or taken by
+            // another app are kept for the UI to report instead of failing startup.
+            app.manage(ShortcutRegistry::default());
+            let bindings = app.state::<SettingsStore>().get().shortcuts;
+            for issue in shortcuts::apply_bindings(app.handle(), &bindings) {
+                eprintln!(
+                    "shortcut {} for {:?} not registered: {}",
+                    issue.accelerator, issue.action, issue.reason
+                );
+            }
 
-            // Prevent close from quitting — hide to tray instead
+            // Prevent close from quitting — hide to tray instead.
+            // Also persist the window bounds whenever they change.
             let window_for_event = app.get_webview_window("main").unwrap();
-            let window_hide = window_for_event.clone();
-            window_for_event.on_window_event(move |event| {
-                if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                    api.prevent_close();
-                    let _ = window_hide.hide();
-                }
-            });
-
-            // Listen for minimize-window event from frontend
-            let app_handle = app.handle().clone();
-            app.listen("minimize-window", move |_event| {
-                set_window_minimized(&app_handle);
-            });
-
-            // Listen for restore-window event from frontend
-            let app_restore = app.handle().clone();
-            app.listen("restore-window", move |_event| {
-                restore_window(&app_restore);
-            });
+            let window_handle = window_for_event.clone();
+            window_for_event
+                .on_window_event(move |event| window::on_window_event(&window_handle, event));
 
             Ok(())
         })
@@ -0,0 +1,401 @@
+//! Messages waiting to be sent. Sends are accepted while offline, kept on disk and
+//! posted in order once the account's socket is connected again. Each carries an
+//! idempotency key, so a send that reached the server before a crash or a dropped
+//! response is not posted twice.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::accounts::Accounts;
+use crate::api::models::{Message, MessageKind};
+use crate::error::{Error, Result};
+use crate::history::HistoryDb;
+use crate::store::JsonStore;
+
+pub const OUTBOX_FILE: &str = "outbox.json";
+
+/// Emitted with an [`OutboxItem`] whenever one is queued, sent, fails or is dropped.
+pub const OUTBOX_CHANGED_EVENT: &str = "outbox-changed";
+
+/// How often to try again while messages are pending but nothing woke the task.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    Pending,
+    /// Posting now; can no longer be discarded.
+    Sending,
+    Sent,
+    /// Refused by the server; waits for a retry or a discard.
+    Failed,
+    /// Only reported, never stored.
+    Discarded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxItem {
+    /// Idempotency key, also used to refer to the item from the UI.
+    pub key: String,
+    pub account: String,
+    pub channel_id: i64,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<MessageKind>,
+    pub status: OutboxStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// The server's copy, once sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<Message>,
+}
+
+/// Pending and failed items in the order they were queued. Sent and discarded
+/// ones are dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutboxQueue {
+    items: Vec<OutboxItem>,
+}
+
+impl OutboxQueue {
+    fn push(&mut self, item: OutboxItem) {
+        self.items.push(item);
+    }
+
+    fn get(&self, key: &str) -> Option<&OutboxItem> {
+        self.items.iter().find(|item| item.key == key)
+    }
+
+    /// The oldest pending item of `account`; later ones wait behind it.
+    fn next_pending(&self, account: &str) -> Option<OutboxItem> {
+        self.items
+            .iter()
+            .find(|item| item.account == account && item.status == OutboxStatus::Pending)
+            .cloned()
+    }
+
+    /// Sends cut short by a quit go out again, under the same key.
+    fn resume_sending(&mut self) {
+        for item in &mut self.items {
+            if item.status == OutboxStatus::Sending {
+                item.status = OutboxStatus::Pending;
+            }
+        }
+    }
+
+    /// Take the item out unless it is being sent.
+    fn discard(&mut self, key: &str) -> Result<OutboxItem> {
+        match self.get(key).map(|item| item.status) {
+            None => Err(Error::NotFound(format!("no queued message {key}"))),
+            Some(OutboxStatus::Sending) => Err(Error::Busy(format!(
+                "message {key} is being sent and cannot be discarded"
+            ))),
+            Some(_) => Ok(self.remove(key).expect("found above")),
+        }
+    }
+
+    fn accounts_pending(&self) -> Vec<String> {
+        let mut accounts: Vec<String> = Vec::new();
+        for item in &self.items {
+            if item.status == OutboxStatus::Pending && !accounts.contains(&item.account) {
+                accounts.push(item.account.clone());
+            }
+        }
+        accounts
+    }
+
+    /// Take the item out, e.g. once sent or discarded.
+    fn remove(&mut self, key: &str) -> Option<OutboxItem> {
+        let index = self.items.iter().position(|item| item.key == key)?;
+        Some(self.items.remove(index))
+    }
+
+    fn set_status(
+        &mut self,
+        key: &str,
+        status: OutboxStatus,
+        error: Option<String>,
+    ) -> Option<OutboxItem> {
+        let item = self.items.iter_mut().find(|item| item.key == key)?;
+        item.status = status;
+        item.error = error;
+        Some(item.clone())
+    }
+
+    fn for_account(&self, account: &str) -> Vec<OutboxItem> {
+        self.items
+            .iter()
+            .filter(|item| item.account == account)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The queue on disk plus the signal that wakes the sending task.
+pub struct Outbox {
+    queue: JsonStore<OutboxQueue>,
+    wake: Arc<Notify>,
+}
+
+impl Outbox {
+    pub fn load(app: &AppHandle) -> Self {
+        let queue = JsonStore::load(app, OUTBOX_FILE);
+        queue.update(OutboxQueue::resume_sending);
+        Self {
+            queue,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Look for something to send, e.g. after a socket connected.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+}
+
+/// 128 random bits in hex, as a client-side message id.
+fn idempotency_key() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn emit(app: &AppHandle, item: &OutboxItem) {
+    let _ = app.emit(OUTBOX_CHANGED_EVENT, item);
+}
+
+/// Drop everything a signed-out account still had queued.
+pub fn forget_account(app: &AppHandle, account: &str) {
+    app.state::<Outbox>()
+        .queue
+        .update(|queue| queue.items.retain(|item| item.account != account));
+}
+
+/// Start the task that drains the queue. Must run after [`Outbox`] and
+/// [`Accounts`] are managed.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    let wake = app.state::<Outbox>().wake.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            flush(&app).await;
+            tokio::select! {
+                _ = wake.notified() => {}
+                _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+/// Send what each connected account has pending, oldest first. A network error,
+/// a 5xx or a 429 stops that account until the next wake; a refusal fails just
+/// that message.
+async fn flush(app: &AppHandle) {
+    let outbox = app.state::<Outbox>();
+    let accounts = app.state::<Accounts>();
+    for account in outbox.queue.get().accounts_pending() {
+        let Some(session) = accounts.get(&account) else {
+            continue;
+        };
+        if !session.ws.is_connected() {
+            continue;
+        }
+        while let Some(item) = outbox.queue.get().next_pending(&account) {
+            // Discarded in the meantime otherwise
+            let mut sending = None;
+            outbox.queue.update(|queue| {
+                sending = queue.set_status(&item.key, OutboxStatus::Sending, None);
+            });
+            let Some(sending) = sending else {
+                continue;
+            };
+            emit(app, &sending);
+            let sent = session
+                .api
+                .send_message_once(item.channel_id, &item.content, item.kind, &item.key)
+                .await;
+            match sent {
+                Ok(message) => {
+                    if let Err(err) = app.state::<HistoryDb>().record(&account, &message) {
+                        eprintln!("failed to cache message {}: {err}", message.id);
+                    }
+                    let mut done = None;
+                    outbox.queue.update(|queue| done = queue.remove(&item.key));
+                    if let Some(mut done) = done {
+                        done.status = OutboxStatus::Sent;
+                        done.message = Some(message);
+                        emit(app, &done);
+                    }
+                }
+                Err(err) if err.is_transient() => {
+                    eprintln!("outbox: {account} unavailable, will retry: {err}");
+                    let mut pending = None;
+                    outbox.queue.update(|queue| {
+                        pending = queue.set_status(&item.key, OutboxStatus::Pending, None);
+                    });
+                    if let Some(pending) = pending {
+                        emit(app, &pending);
+                    }
+                    break;
+                }
+                Err(err) => {
+                    let mut failed = None;
+                    outbox.queue.update(|queue| {
+                        failed = queue.set_status(
+                            &item.key,
+                            OutboxStatus::Failed,
+                            Some(err.to_string()),
+                        );
+                    });
+                    if let Some(failed) = failed {
+                        emit(app, &failed);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Queue a message from the active account. It goes out right away if the socket
+/// is up, else once it reconnects.
+#[tauri::command]
+pub fn outbox_send(
+    app: AppHandle,
+    accounts: State<'_, Accounts>,
+    outbox: State<'_, Outbox>,
+    channel_id: i64,
+    content: String,
+    kind: Option<MessageKind>,
+) -> Result<OutboxItem> {
+    let session = accounts.active().ok_or(Error::SignedOut)?;
+    let item = OutboxItem {
+        key: idempotency_key(),
+        account: session.id.clone(),
+        channel_id,
+        content,
+        kind,
+        status: OutboxStatus::Pending,
+        error: None,
+        created_at: Utc::now(),
+        message: None,
+    };
+    outbox.queue.update(|queue| queue.push(item.clone()));
+    emit(&app, &item);
+    outbox.wake();
+    Ok(item)
+}
+
+/// Pending and failed messages of the active account, oldest first.
+#[tauri::command]
+pub fn outbox_list(
+    accounts: State<'_, Accounts>,
+    outbox: State<'_, Outbox>,
+) -> Result<Vec<OutboxItem>> {
+    let session = accounts.active().ok_or(Error::SignedOut)?;
+    Ok(outbox.queue.get().for_account(&session.id))
+}
+
+/// Queue a failed message again, keeping its key and its place in line.
+#[tauri::command]
+pub fn outbox_retry(app: AppHandle, outbox: State<'_, Outbox>, key: String) -> Result<OutboxItem> {
+    let mut retried = None;
+    outbox.queue.update(|queue| {
+        retried = queue.set_status(&key, OutboxStatus::Pending, None);
+    });
+    let item = retried.ok_or_else(|| Error::NotFound(format!("no queued message {key}")))?;
+    emit(&app, &item);
+    outbox.wake();
+    Ok(item)
+}
+
+/// Drop a pending or failed message. One being sent is refused as `busy`: it
+/// may reach the server whatever happens now.
+#[tauri::command]
+pub fn outbox_discard(app: AppHandle, outbox: State<'_, Outbox>, key: String) -> Result<()> {
+    let mut discarded = Err(Error::NotFound(format!("no queued message {key}")));
+    outbox.queue.update(|queue| discarded = queue.discard(&key));
+    let mut item = discarded?;
+    item.status = OutboxStatus::Discarded;
+    emit(&app, &item);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(key: &str, account: &str) -> OutboxItem {
+        OutboxItem {
+            key: key.into(),
+            account: account.into(),
+            channel_id: 3,
+            content: format!("message {key}"),
+            kind: None,
+            status: OutboxStatus::Pending,
+            error: None,
+            created_at: Utc::now(),
+            message: None,
+        }
+    }
+
+    #[test]
+    fn sends_in_order_and_failures_do_not_block() {
+        let mut queue = OutboxQueue::default();
+        for (key, account) in [("a", "default:1"), ("b", "work:2"), ("c", "default:1")] {
+            queue.push(item(key, account));
+        }
+        assert_eq!(queue.accounts_pending(), ["default:1", "work:2"]);
+        assert_eq!(queue.next_pending("default:1").unwrap().key, "a");
+
+        queue.set_status("a", OutboxStatus::Failed, Some("not a member".into()));
+        assert_eq!(queue.next_pending("default:1").unwrap().key, "c");
+        queue.remove("c");
+        assert_eq!(queue.next_pending("default:1"), None);
+
+        // A retried message keeps its place ahead of newer ones
+        queue.push(item("d", "default:1"));
+        queue.set_status("a", OutboxStatus::Pending, None);
+        assert_eq!(queue.next_pending("default:1").unwrap().key, "a");
+        assert_eq!(
+            queue
+                .for_account("default:1")
+                .iter()
+                .map(|item| item.key.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "d"]
+        );
+    }
+
+    #[test]
+    fn survives_a_round_trip_to_disk() {
+        let mut queue = OutboxQueue::default();
+        let mut failed = item("a", "default:1");
+        failed.status = OutboxStatus::Failed;
+        failed.error = Some("HTTP 500".into());
+        queue.push(failed.clone());
+        let json = serde_json::to_string(&queue).unwrap();
+        let loaded: OutboxQueue = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.get("a"), Some(&failed));
+    }
+
+    #[test]
+    fn messages_being_sent_are_not_discarded() {
+        let mut queue = OutboxQueue::default();
+        queue.push(item("a", "default:1"));
+        queue.push(item("b", "default:1"));
+        queue.set_status("a", OutboxStatus::Sending, None);
+
+        assert!(matches!(queue.discard("a"), Err(Error::Busy(_))));
+        assert_eq!(queue.get("a").unwrap().status, OutboxStatus::Sending);
+        assert_eq!(queue.discard("b").unwrap().key, "b");
+        assert!(matches!(queue.discard("b"), Err(Error::NotFound(_))));
+
+        // Left sending by a quit: sent again on the next start
+        queue.resume_sending();
+        assert_eq!(queue.next_pending("default:1").unwrap().key, "a");
+    }
+}
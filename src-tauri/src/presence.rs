@@ -0,0 +1,210 @@
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::accounts::Accounts;
+use crate::api::models::UpdateMe;
+use crate::error::Result;
+use crate::settings::SettingsStore;
+use crate::tray;
+
+/// Emitted with a [`PresenceView`] whenever the effective status or the choice changes.
+pub const PRESENCE_CHANGED_EVENT: &str = "presence-changed";
+
+/// Status shown to other users.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Presence {
+    #[default]
+    Online,
+    Idle,
+    Dnd,
+    /// Shown to others as offline.
+    Invisible,
+}
+
+impl Presence {
+    /// Value the server's `status` field takes.
+    pub fn server_status(self) -> &'static str {
+        match self {
+            Presence::Online => "online",
+            Presence::Idle => "idle",
+            Presence::Dnd => "dnd",
+            Presence::Invisible => "offline",
+        }
+    }
+}
+
+/// What the user picked: follow window focus, or pin a status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceChoice {
+    #[default]
+    Auto,
+    Online,
+    Idle,
+    Dnd,
+    Invisible,
+}
+
+impl PresenceChoice {
+    pub const ALL: [PresenceChoice; 5] = [
+        PresenceChoice::Online,
+        PresenceChoice::Idle,
+        PresenceChoice::Dnd,
+        PresenceChoice::Invisible,
+        PresenceChoice::Auto,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PresenceChoice::Auto => "Auto",
+            PresenceChoice::Online => "Online",
+            PresenceChoice::Idle => "Idle",
+            PresenceChoice::Dnd => "Do Not Disturb",
+            PresenceChoice::Invisible => "Invisible",
+        }
+    }
+
+    fn pinned(self) -> Option<Presence> {
+        match self {
+            PresenceChoice::Auto => None,
+            PresenceChoice::Online => Some(Presence::Online),
+            PresenceChoice::Idle => Some(Presence::Idle),
+            PresenceChoice::Dnd => Some(Presence::Dnd),
+            PresenceChoice::Invisible => Some(Presence::Invisible),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceView {
+    pub choice: PresenceChoice,
+    pub status: Presence,
+    /// `status` as the server spells it.
+    pub server_status: &'static str,
+}
+
+/// The user's choice plus the status Auto currently resolves to. The UI keeps
+/// reporting focus changes for Auto; they only take effect while nothing is pinned.
+#[derive(Debug, Default)]
+pub struct PresenceState {
+    choice: PresenceChoice,
+    auto: Presence,
+}
+
+impl PresenceState {
+    pub fn new(choice: PresenceChoice) -> Self {
+        Self {
+            choice,
+            auto: Presence::default(),
+        }
+    }
+
+    pub fn view(&self) -> PresenceView {
+        let status = self.choice.pinned().unwrap_or(self.auto);
+        PresenceView {
+            choice: self.choice,
+            status,
+            server_status: status.server_status(),
+        }
+    }
+}
+
+pub fn current(app: &AppHandle) -> PresenceView {
+    app.state::<Mutex<PresenceState>>().lock().unwrap().view()
+}
+
+/// Send `view.status` to the server for every signed-in account, in the background.
+fn push_status(app: &AppHandle, view: PresenceView) {
+    let update = UpdateMe {
+        status: Some(view.server_status.to_string()),
+        ..UpdateMe::default()
+    };
+    for session in app.state::<Accounts>().all() {
+        let update = update.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = session.api.update_me(&update).await {
+                eprintln!("failed to update status for {}: {err}", session.id);
+            }
+        });
+    }
+}
+
+/// Show every account as offline, e.g. before the machine sleeps. The real
+/// status goes out again once the sockets reconnect.
+pub async fn push_offline(app: &AppHandle) {
+    let update = UpdateMe {
+        status: Some(Presence::Invisible.server_status().to_string()),
+        ..UpdateMe::default()
+    };
+    let sessions = app.state::<Accounts>().all();
+    join_all(sessions.iter().map(|session| async {
+        if let Err(err) = session.api.update_me(&update).await {
+            eprintln!("failed to show {} as offline: {err}", session.id);
+        }
+    }))
+    .await;
+}
+
+/// Apply `f` to the state; if anything visible changed, persist the choice,
+/// rebuild the tray menu, tell the frontend and the server.
+fn update(app: &AppHandle, f: impl FnOnce(&mut PresenceState)) -> PresenceView {
+    let (before, after) = {
+        let state = app.state::<Mutex<PresenceState>>();
+        let mut state = state.lock().unwrap();
+        let before = state.view();
+        f(&mut state);
+        (before, state.view())
+    };
+    if before != after {
+        app.state::<SettingsStore>()
+            .update(|settings| settings.presence = after.choice);
+        tray::refresh_menu(app);
+        let _ = app.emit(PRESENCE_CHANGED_EVENT, after);
+        if before.status != after.status {
+            push_status(app, after);
+        }
+    }
+    after
+}
+
+pub fn set_choice(app: &AppHandle, choice: PresenceChoice) -> PresenceView {
+    update(app, |state| state.choice = choice)
+}
+
+/// Do Not Disturb on, or back to Auto.
+pub fn toggle_dnd(app: &AppHandle) -> PresenceView {
+    update(app, |state| {
+        state.choice = match state.choice {
+            PresenceChoice::Dnd => PresenceChoice::Auto,
+            _ => PresenceChoice::Dnd,
+        }
+    })
+}
+
+#[tauri::command]
+pub fn get_presence(state: State<'_, Mutex<PresenceState>>) -> Result<PresenceView> {
+    Ok(state.lock().unwrap().view())
+}
+
+#[tauri::command]
+pub fn set_presence(app: AppHandle, choice: PresenceChoice) -> Result<PresenceView> {
+    Ok(set_choice(&app, choice))
+}
+
+/// What Auto should resolve to, from window focus and the mini bubble.
+#[tauri::command]
+pub fn report_auto_presence(app: AppHandle, status: Presence) -> Result<PresenceView> {
+    Ok(update(&app, |state| state.auto = status))
+}
+
+/// Send the current status again, e.g. after reconnecting.
+#[tauri::command]
+pub fn push_presence(app: AppHandle) -> Result<PresenceView> {
+    let view = current(&app);
+    push_status(&app, view);
+    Ok(view)
+}
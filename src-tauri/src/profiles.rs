@@ -0,0 +1,354 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::accounts::{self, Accounts};
+use crate::api;
+use crate::error::{Error, Result};
+use crate::proxy::ProxyOptions;
+use crate::settings::SettingsStore;
+
+/// Emitted with the newly selected [`ConnectionProfile`] when the webview should
+/// start over on another server. The frontend reloads.
+pub const PROFILE_CHANGED_EVENT: &str = "profile-changed";
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TlsOptions {
+    /// Skip certificate checks, for self-hosted servers with self-signed certs.
+    pub accept_invalid_certs: bool,
+    /// PEM files with roots to trust besides the system's, e.g. an internal CA.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ca_certificates: Vec<PathBuf>,
+    /// `sha256/<base64>` hashes of SubjectPublicKeyInfo, as HPKP and curl's
+    /// `--pinnedpubkey` take them. If set, some certificate in the server's
+    /// chain must match one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<String>,
+}
+
+/// A server to talk to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionProfile {
+    pub name: String,
+    pub base_url: String,
+    /// Used as-is instead of deriving `ws(s)://<base>/ws`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ws_url: Option<String>,
+    #[serde(default)]
+    pub tls: TlsOptions,
+    #[serde(default)]
+    pub proxy: ProxyOptions,
+}
+
+impl Default for ConnectionProfile {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PROFILE.into(),
+            base_url: api::DEFAULT_BASE_URL.into(),
+            ws_url: None,
+            tls: TlsOptions::default(),
+            proxy: ProxyOptions::default(),
+        }
+    }
+}
+
+impl ConnectionProfile {
+    /// An unsaved profile for `--server <url>`, named after the host.
+    pub fn for_server(base_url: &str) -> Self {
+        let name = Url::parse(base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| base_url.to_string());
+        Self {
+            name,
+            base_url: base_url.trim_end_matches('/').into(),
+            ..Self::default()
+        }
+    }
+
+    /// The override, or the base URL with its scheme swapped to `ws`/`wss` and `/ws`
+    /// appended. Does not carry the token.
+    pub fn websocket_url(&self) -> String {
+        if let Some(ws_url) = &self.ws_url {
+            return ws_url.clone();
+        }
+        let base = self.base_url.trim_end_matches('/');
+        match base.split_once("://") {
+            Some(("https", rest)) => format!("wss://{rest}/ws"),
+            Some(("http", rest)) => format!("ws://{rest}/ws"),
+            _ => format!("{base}/ws"),
+        }
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("profile name must not be empty".into());
+        }
+        check_url(&self.base_url, &["http", "https"])?;
+        if let Some(ws_url) = &self.ws_url {
+            check_url(ws_url, &["ws", "wss"])?;
+        }
+        self.tls.validate()?;
+        self.proxy.validate()
+    }
+}
+
+fn check_url(raw: &str, schemes: &[&str]) -> std::result::Result<(), String> {
+    let url = Url::parse(raw).map_err(|err| format!("{raw}: {err}"))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("{raw}: expected {}", schemes.join(" or ")));
+    }
+    Ok(())
+}
+
+/// Profile picked on the command line, overriding the saved one for this run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupProfile {
+    Named(String),
+    Server(String),
+}
+
+/// Reads `--profile <name>` or `--server <url>`, also in `--flag=value` form.
+/// The last one given wins.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Option<StartupProfile> {
+    let mut args = args.into_iter();
+    let mut picked = None;
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let make = match flag.as_str() {
+            "--profile" => StartupProfile::Named,
+            "--server" => StartupProfile::Server,
+            _ => continue,
+        };
+        match inline.or_else(|| args.next()) {
+            Some(value) if !value.is_empty() => picked = Some(make(value)),
+            _ => eprintln!("{flag} needs a value"),
+        }
+    }
+    picked
+}
+
+/// Saved profiles, with the built-in default when none are configured.
+pub fn saved(app: &AppHandle) -> Vec<ConnectionProfile> {
+    let profiles = app.state::<SettingsStore>().get().profiles;
+    if profiles.is_empty() {
+        vec![ConnectionProfile::default()]
+    } else {
+        profiles
+    }
+}
+
+fn find(app: &AppHandle, name: &str) -> Option<ConnectionProfile> {
+    saved(app).into_iter().find(|profile| profile.name == name)
+}
+
+/// A saved profile, or the selected one if it was only given on the command line.
+pub fn lookup(app: &AppHandle, name: &str) -> Option<ConnectionProfile> {
+    let selected = app.state::<SelectedProfile>().get();
+    if selected.name == name {
+        Some(selected)
+    } else {
+        find(app, name)
+    }
+}
+
+/// The server new sign-ins go to; follows the active account.
+pub struct SelectedProfile(RwLock<ConnectionProfile>);
+
+impl SelectedProfile {
+    pub fn new(profile: ConnectionProfile) -> Self {
+        Self(RwLock::new(profile))
+    }
+
+    pub fn get(&self) -> ConnectionProfile {
+        self.0.read().unwrap().clone()
+    }
+}
+
+/// Select `profile`, remembering it for the next run if it is a saved one.
+pub fn select(app: &AppHandle, profile: ConnectionProfile) {
+    if find(app, &profile.name).is_some() {
+        let name = profile.name.clone();
+        app.state::<SettingsStore>()
+            .update(|settings| settings.active_profile = name);
+    }
+    *app.state::<SelectedProfile>().0.write().unwrap() = profile;
+}
+
+/// The profile to start with: the command line's, else the last one switched to.
+pub fn resolve_startup(app: &AppHandle, startup: Option<StartupProfile>) -> ConnectionProfile {
+    match startup {
+        Some(StartupProfile::Server(url)) => {
+            let profile = ConnectionProfile::for_server(&url);
+            match profile.validate() {
+                Ok(()) => return profile,
+                Err(err) => eprintln!("ignoring --server: {err}"),
+            }
+        }
+        Some(StartupProfile::Named(name)) => match find(app, &name) {
+            Some(profile) => return profile,
+            None => eprintln!("no profile named {name}, using the saved one"),
+        },
+        None => {}
+    }
+    let active = app.state::<SettingsStore>().get().active_profile;
+    find(app, &active).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesView {
+    pub profiles: Vec<ConnectionProfile>,
+    /// Not necessarily saved, e.g. one given with `--server`.
+    pub active: ConnectionProfile,
+}
+
+#[tauri::command]
+pub fn get_profiles(app: AppHandle) -> Result<ProfilesView> {
+    Ok(ProfilesView {
+        profiles: saved(&app),
+        active: app.state::<SelectedProfile>().get(),
+    })
+}
+
+/// Add a profile or replace the one with the same name. Accounts on an edited
+/// profile reconnect with the new settings.
+#[tauri::command]
+pub fn save_profile(app: AppHandle, profile: ConnectionProfile) -> Result<ProfilesView> {
+    profile.validate().map_err(Error::InvalidSettings)?;
+    // Anything that can reject the profile runs before it is applied or saved.
+    let prepared = api::PreparedProfile::new(&profile)?;
+    for session in app.state::<Accounts>().all() {
+        let current = session.api.profile();
+        if current.name == profile.name && current != profile {
+            session.api.set_profile(&prepared);
+            session.ws.reconnect();
+        }
+    }
+    let mut profiles = saved(&app);
+    match profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile.clone(),
+        None => profiles.push(profile.clone()),
+    }
+    app.state::<SettingsStore>()
+        .update(|settings| settings.profiles = profiles);
+    let selected = app.state::<SelectedProfile>().get();
+    if selected.name == profile.name && selected != profile {
+        select(&app, profile.clone());
+        let _ = app.emit(PROFILE_CHANGED_EVENT, &profile);
+    }
+    get_profiles(app)
+}
+
+#[tauri::command]
+pub async fn delete_profile(app: AppHandle, name: String) -> Result<ProfilesView> {
+    if app.state::<SelectedProfile>().get().name == name {
+        return Err(Error::InvalidSettings(
+            "switch to another profile before deleting this one".into(),
+        ));
+    }
+    let in_use = app
+        .state::<Accounts>()
+        .all()
+        .iter()
+        .any(|session| session.api.profile().name == name);
+    if in_use {
+        return Err(Error::InvalidSettings(
+            "sign out of the accounts on this profile before deleting it".into(),
+        ));
+    }
+    accounts::forget_profile(&app, &name).await?;
+    let mut profiles = saved(&app);
+    profiles.retain(|profile| profile.name != name);
+    app.state::<SettingsStore>()
+        .update(|settings| settings.profiles = profiles);
+    get_profiles(app)
+}
+
+/// Select a profile. The first account signed in there becomes active, or the
+/// sign-in screen shows if there is none. Other accounts stay connected.
+#[tauri::command]
+pub fn switch_profile(app: AppHandle, name: String) -> Result<ConnectionProfile> {
+    let profile =
+        find(&app, &name).ok_or_else(|| Error::NotFound(format!("no profile named {name}")))?;
+    if app.state::<SelectedProfile>().get() == profile {
+        return Ok(profile);
+    }
+    let accounts = app.state::<Accounts>();
+    let account = accounts
+        .all()
+        .into_iter()
+        .find(|session| session.api.profile().name == name)
+        .map(|session| session.id.clone());
+    // The reload below picks up the new account; no need for a second one
+    accounts::switch(&app, account, false)?;
+    select(&app, profile.clone());
+    let _ = app.emit(PROFILE_CHANGED_EVENT, &profile);
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn reads_profile_and_server_flags() {
+        assert_eq!(parse_args(args(&["closechat"])), None);
+        assert_eq!(
+            parse_args(args(&["closechat", "--profile", "staging"])),
+            Some(StartupProfile::Named("staging".into()))
+        );
+        assert_eq!(
+            parse_args(args(&["closechat", "--server=https://chat.example.com"])),
+            Some(StartupProfile::Server("https://chat.example.com".into()))
+        );
+        assert_eq!(
+            parse_args(args(&[
+                "closechat",
+                "--profile",
+                "a",
+                "--profile=b",
+                "--server"
+            ])),
+            Some(StartupProfile::Named("b".into()))
+        );
+    }
+
+    #[test]
+    fn websocket_url_follows_the_base_unless_overridden() {
+        let mut profile = ConnectionProfile::for_server("http://localhost:8080/");
+        assert_eq!(profile.name, "localhost");
+        assert_eq!(profile.websocket_url(), "ws://localhost:8080/ws");
+        profile.base_url = "https://chat.example.com/api-root".into();
+        assert_eq!(
+            profile.websocket_url(),
+            "wss://chat.example.com/api-root/ws"
+        );
+        profile.ws_url = Some("wss://rt.example.com/socket".into());
+        assert_eq!(profile.websocket_url(), "wss://rt.example.com/socket");
+    }
+
+    #[test]
+    fn validates_urls() {
+        let mut profile = ConnectionProfile::default();
+        assert!(profile.validate().is_ok());
+        profile.ws_url = Some("https://rt.example.com".into());
+        assert!(profile.validate().is_err());
+        profile.ws_url = None;
+        profile.base_url = "chat.example.com".into();
+        assert!(profile.validate().is_err());
+    }
+}
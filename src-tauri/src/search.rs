@@ -0,0 +1,383 @@
+//! Full-text search over cached history (see [`crate::history`]), with an FTS5
+//! index kept current by triggers, so messages are searchable as soon as a page,
+//! a socket frame or the outbox stores them.
+//!
+//! Queries are words and quoted phrases plus filters:
+//! `from:alice in:#deploys "rollback" before:2026-09-01 after:2026-08-01`.
+
+use chrono::{Days, NaiveDate};
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+use serde::Serialize;
+use tauri::State;
+
+use crate::accounts::Accounts;
+use crate::api::models::ChannelKind;
+use crate::error::{Error, Result};
+use crate::history::HistoryDb;
+use crate::tray::UnreadChannel;
+
+/// Marks the start and end of each match in a [`SearchHit::snippet`]. Control
+/// characters, so they cannot clash with message text.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+const DEFAULT_LIMIT: u32 = 50;
+
+/// Tokens in the snippet around the first match.
+const SNIPPET_TOKENS: u32 = 16;
+
+/// The index, added to databases created before search existed, indexing what
+/// they already hold.
+pub(crate) const SCHEMA: &str = "
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+    content,
+    content = 'messages',
+    content_rowid = 'rowid',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE OF content ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+    INSERT INTO messages_fts (rowid, content) VALUES (new.rowid, new.content);
+END;
+INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Words and phrases that must all appear.
+    pub terms: Vec<String>,
+    pub from: Option<String>,
+    /// As typed: `#name` for a channel, `@name` for a DM, else either.
+    pub channel: Option<String>,
+    pub before: Option<NaiveDate>,
+    pub after: Option<NaiveDate>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut query = SearchQuery::default();
+        for token in tokenize(input) {
+            let (filter, value) = match token.split_once(':') {
+                Some((filter, value)) if !token.starts_with('"') && !value.is_empty() => {
+                    (filter.to_ascii_lowercase(), value.trim_matches('"'))
+                }
+                _ => (String::new(), token.as_str()),
+            };
+            match filter.as_str() {
+                "from" => query.from = Some(value.trim_start_matches('@').to_string()),
+                "in" => query.channel = Some(value.to_string()),
+                "before" => query.before = Some(parse_date(value)?),
+                "after" => query.after = Some(parse_date(value)?),
+                _ => {
+                    let term = token.trim_matches('"');
+                    if term.chars().any(char::is_alphanumeric) {
+                        query.terms.push(term.to_string());
+                    }
+                }
+            }
+        }
+        Ok(query)
+    }
+
+    /// The terms as an FTS5 expression: each a quoted phrase, all required.
+    fn match_expression(&self) -> Option<String> {
+        if self.terms.is_empty() {
+            return None;
+        }
+        let phrases: Vec<String> = self
+            .terms
+            .iter()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        Some(phrases.join(" "))
+    }
+}
+
+/// Split on whitespace, keeping `"quoted phrases"` (also after `filter:`) whole.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| Error::InvalidQuery(format!("{value} is not a date like 2026-09-01")))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub channel_id: i64,
+    pub message_id: i64,
+    /// Channel name, or the other user's for a DM, when the channel is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_name: Option<String>,
+    pub sender_username: String,
+    pub created_at: String,
+    /// Text around the match, with matches between [`MATCH_START`] and [`MATCH_END`].
+    pub snippet: String,
+    /// Lower is better; 0 when the query has no words to rank by.
+    pub rank: f64,
+    /// Where imported history came from, e.g. `"slack"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported_from: Option<String>,
+}
+
+/// The ids `in:` refers to among the account's channels.
+fn channel_ids(name: &str, channels: &[UnreadChannel]) -> Vec<i64> {
+    let (kind, name) = match name.chars().next() {
+        Some('#') => (Some(ChannelKind::Channel), &name[1..]),
+        Some('@') => (Some(ChannelKind::Dm), &name[1..]),
+        _ => (None, name),
+    };
+    channels
+        .iter()
+        .filter(|channel| kind.is_none_or(|kind| channel.kind == kind))
+        .filter(|channel| channel.name.eq_ignore_ascii_case(name))
+        .map(|channel| channel.id)
+        .collect()
+}
+
+impl HistoryDb {
+    /// Best hits first, or newest first when only filters were given. `channels`
+    /// names the hits and resolves `in:`.
+    pub fn search(
+        &self,
+        account: &str,
+        query: &SearchQuery,
+        channels: &[UnreadChannel],
+        limit: u32,
+    ) -> Result<Vec<SearchHit>> {
+        let mut params = vec![Value::from(account.to_string())];
+        let mut filters = vec!["m.account = ?1".to_string()];
+        let mut param = |filters: &mut Vec<String>, sql: &str, value: Value| {
+            params.push(value);
+            filters.push(sql.replace('?', &format!("?{}", params.len())));
+        };
+        if let Some(from) = &query.from {
+            param(
+                &mut filters,
+                "m.sender_username = ? COLLATE NOCASE",
+                from.clone().into(),
+            );
+        }
+        if let Some(name) = &query.channel {
+            let ids = channel_ids(name, channels);
+            if ids.is_empty() {
+                return Err(Error::InvalidQuery(format!("no channel {name}")));
+            }
+            let list: Vec<String> = ids.iter().map(i64::to_string).collect();
+            filters.push(format!("m.channel_id IN ({})", list.join(", ")));
+        }
+        if let Some(before) = query.before {
+            param(&mut filters, "m.created_at < ?", before.to_string().into());
+        }
+        if let Some(after) = query.after {
+            let next = after + Days::new(1);
+            param(&mut filters, "m.created_at >= ?", next.to_string().into());
+        }
+
+        let sql = match query.match_expression() {
+            Some(expression) => {
+                param(&mut filters, "messages_fts MATCH ?", expression.into());
+                let (start, end) = (MATCH_START as u32, MATCH_END as u32);
+                format!(
+                    "SELECT m.channel_id, m.id, m.sender_username, m.created_at,
+                            snippet(messages_fts, 0, char({start}), char({end}), '…', {SNIPPET_TOKENS}),
+                            bm25(messages_fts), m.imported_from
+                     FROM messages_fts JOIN messages m ON m.rowid = messages_fts.rowid
+                     WHERE {}
+                     ORDER BY bm25(messages_fts), m.id DESC
+                     LIMIT {limit}",
+                    filters.join(" AND "),
+                )
+            }
+            None => format!(
+                "SELECT m.channel_id, m.id, m.sender_username, m.created_at,
+                        substr(coalesce(m.content, ''), 1, 200), 0.0, m.imported_from
+                 FROM messages m
+                 WHERE {}
+                 ORDER BY m.id DESC
+                 LIMIT {limit}",
+                filters.join(" AND "),
+            ),
+        };
+
+        let conn = self.lock();
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(params), |row| {
+            Ok(SearchHit {
+                channel_id: row.get(0)?,
+                message_id: row.get(1)?,
+                channel_name: None,
+                sender_username: row.get(2)?,
+                created_at: row.get(3)?,
+                snippet: row.get(4)?,
+                rank: row.get(5)?,
+                imported_from: row.get(6)?,
+            })
+        })?;
+        let mut hits = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        for hit in &mut hits {
+            hit.channel_name = channels
+                .iter()
+                .find(|channel| channel.id == hit.channel_id)
+                .map(|channel| channel.name.clone());
+        }
+        Ok(hits)
+    }
+}
+
+/// Search the active account's cached history.
+#[tauri::command]
+pub fn search_messages(
+    accounts: State<'_, Accounts>,
+    db: State<'_, HistoryDb>,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<SearchHit>> {
+    let session = accounts.active().ok_or(Error::SignedOut)?;
+    let query = SearchQuery::parse(&query)?;
+    db.search(
+        &session.id,
+        &query,
+        &session.channels(),
+        limit.unwrap_or(DEFAULT_LIMIT),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::models::{Message, MessageKind, MessagePage};
+
+    const ACCOUNT: &str = "default:1";
+
+    fn message(id: i64, channel_id: i64, sender: &str, content: &str, day: u32) -> Message {
+        Message {
+            id,
+            channel_id,
+            sender_id: 2,
+            sender_username: sender.into(),
+            content: Some(content.into()),
+            kind: MessageKind::User,
+            image_url: None,
+            created_at: format!("2026-08-{day:02}T10:00:00Z"),
+        }
+    }
+
+    fn channel(id: i64, name: &str, kind: ChannelKind) -> UnreadChannel {
+        UnreadChannel {
+            id,
+            name: name.into(),
+            kind,
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn parses_filters_and_phrases() {
+        let query =
+            SearchQuery::parse(r#"from:@Alice in:#deploys "roll back" now before:2026-09-01"#)
+                .unwrap();
+        assert_eq!(
+            query,
+            SearchQuery {
+                terms: vec!["roll back".into(), "now".into()],
+                from: Some("Alice".into()),
+                channel: Some("#deploys".into()),
+                before: NaiveDate::from_ymd_opt(2026, 9, 1),
+                after: None,
+            }
+        );
+        assert_eq!(
+            query.match_expression().as_deref(),
+            Some(r#""roll back" "now""#)
+        );
+        assert!(matches!(
+            SearchQuery::parse("before:yesterday"),
+            Err(Error::InvalidQuery(_))
+        ));
+        // A colon inside a phrase is just text
+        assert_eq!(
+            SearchQuery::parse(r#""eta: 5m""#).unwrap().terms,
+            ["eta: 5m"]
+        );
+    }
+
+    #[test]
+    fn finds_cached_and_live_messages() {
+        let db = HistoryDb::in_memory();
+        let page = MessagePage {
+            messages: vec![
+                message(3, 7, "alice", "rollback done, all green", 20),
+                message(2, 8, "alice", "rollback the staging deploy?", 10),
+                message(1, 7, "bob", "starting the rollback of the rollback", 5),
+            ],
+            has_more: false,
+        };
+        db.merge_latest(ACCOUNT, 7, &page).unwrap();
+        let channels = [
+            channel(7, "deploys", ChannelKind::Channel),
+            channel(8, "alice", ChannelKind::Dm),
+        ];
+        let search = |input: &str| -> Vec<i64> {
+            let query = SearchQuery::parse(input).unwrap();
+            let hits = db.search(ACCOUNT, &query, &channels, 10).unwrap();
+            hits.iter().map(|hit| hit.message_id).collect()
+        };
+
+        assert_eq!(search("rollback").len(), 3);
+        assert_eq!(search("from:alice in:#deploys rollback"), [3]);
+        assert_eq!(search("in:@alice"), [2]);
+        assert_eq!(search("rollback before:2026-08-10"), [1]);
+        assert_eq!(search("rollback after:2026-08-10"), [3]);
+        assert_eq!(search(r#""all green""#), [3]);
+
+        let hits = db
+            .search(
+                ACCOUNT,
+                &SearchQuery::parse("green").unwrap(),
+                &channels,
+                10,
+            )
+            .unwrap();
+        assert_eq!(hits[0].channel_name.as_deref(), Some("deploys"));
+        assert!(hits[0]
+            .snippet
+            .contains(&format!("{MATCH_START}green{MATCH_END}")));
+
+        // Socket messages are indexed as they are stored
+        db.record(ACCOUNT, &message(4, 7, "carol", "pager is green again", 21))
+            .unwrap();
+        assert_eq!(search("pager"), [4]);
+        db.forget_account(ACCOUNT).unwrap();
+        assert!(search("rollback").is_empty());
+    }
+}
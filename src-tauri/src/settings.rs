@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::accounts::SavedAccount;
+use crate::presence::PresenceChoice;
+use crate::profiles::{self, ConnectionProfile};
+use crate::shortcuts::{self, ShortcutBindings};
+use crate::store::JsonStore;
+use crate::window_mode::WindowMode;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+pub type SettingsStore = JsonStore<Settings>;
+
+/// User preferences, as opposed to [`crate::window_state::WindowState`] which
+/// only records where things were left.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub shortcuts: ShortcutBindings,
+    /// Status pinned from the tray, kept until set back to Auto.
+    pub presence: PresenceChoice,
+    /// Servers to choose from; empty means just the built-in default.
+    pub profiles: Vec<ConnectionProfile>,
+    /// Name of the profile to start with, unless overridden on the command line.
+    pub active_profile: String,
+    /// Accounts to sign back in on start; their tokens are in the keyring.
+    pub accounts: Vec<SavedAccount>,
+    /// Account the webview showed last, made active again once it is restored.
+    pub active_account: Option<String>,
+    pub heartbeat: HeartbeatSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window: WindowSettings::default(),
+            shortcuts: shortcuts::default_bindings(),
+            presence: PresenceChoice::default(),
+            profiles: Vec::new(),
+            active_profile: profiles::DEFAULT_PROFILE.into(),
+            accounts: Vec::new(),
+            active_account: None,
+            heartbeat: HeartbeatSettings::default(),
+        }
+    }
+}
+
+/// How often the socket is pinged, and how long a pong may take before the
+/// connection is given up as dead and reopened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HeartbeatSettings {
+    pub interval_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for HeartbeatSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 15,
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl HeartbeatSettings {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=600).contains(&self.interval_secs) || !(1..=600).contains(&self.timeout_secs) {
+            return Err("heartbeat interval and timeout must be between 1 and 600 seconds".into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowSettings {
+    pub mini: MiniSettings,
+    pub compact: CompactSettings,
+    pub always_on_top: AlwaysOnTopPolicy,
+}
+
+/// Which window modes float above other windows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlwaysOnTopPolicy {
+    /// Compact window and mini bubble float, fullscreen does not.
+    #[default]
+    CompactAndMini,
+    MiniOnly,
+    Always,
+    Never,
+}
+
+impl AlwaysOnTopPolicy {
+    pub fn applies_to(self, mode: WindowMode) -> bool {
+        match self {
+            AlwaysOnTopPolicy::CompactAndMini => {
+                matches!(mode, WindowMode::Compact | WindowMode::Mini)
+            }
+            AlwaysOnTopPolicy::MiniOnly => mode == WindowMode::Mini,
+            AlwaysOnTopPolicy::Always => true,
+            AlwaysOnTopPolicy::Never => false,
+        }
+    }
+}
+
+/// Where a window sits on its monitor. `Free` keeps wherever it was last dragged to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Free,
+}
+
+impl Anchor {
+    /// `self`, or `fallback` when there is no corner to pin to.
+    pub fn or_corner(self, fallback: Anchor) -> Anchor {
+        match self {
+            Anchor::Free => fallback,
+            corner => corner,
+        }
+    }
+}
+
+/// Distance in logical pixels from the anchored vertical (`x`) and horizontal (`y`)
+/// monitor edges.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Margins {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MiniSettings {
+    pub anchor: Anchor,
+    pub margins: Margins,
+    /// Bubble width and height in logical pixels.
+    pub size: f64,
+}
+
+impl Default for MiniSettings {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::TopRight,
+            margins: Margins { x: 20.0, y: 20.0 },
+            size: 48.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompactSettings {
+    pub anchor: Anchor,
+    /// Also used for the first placement when the anchor is `Free`.
+    pub margins: Margins,
+}
+
+impl Default for CompactSettings {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::Free,
+            margins: Margins { x: 20.0, y: 60.0 },
+        }
+    }
+}
+
+pub const MIN_MINI_SIZE: f64 = 24.0;
+pub const MAX_MINI_SIZE: f64 = 256.0;
+
+impl WindowSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let size = self.mini.size;
+        if !(MIN_MINI_SIZE..=MAX_MINI_SIZE).contains(&size) {
+            return Err(format!(
+                "mini size must be between {MIN_MINI_SIZE} and {MAX_MINI_SIZE}, got {size}"
+            ));
+        }
+        for margins in [self.mini.margins, self.compact.margins] {
+            if !(margins.x >= 0.0 && margins.y >= 0.0) {
+                return Err("margins must not be negative".into());
+            }
+        }
+        Ok(())
+    }
+}
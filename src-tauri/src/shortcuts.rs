@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+use crate::error::{Error, Result};
+use crate::presence;
+use crate::settings::SettingsStore;
+use crate::window;
+use crate::window_mode::{WindowMode, WindowModeMachine};
+
+/// Emitted for actions the frontend carries out (focusing the input, switching channel...).
+pub const SHORTCUT_TRIGGERED_EVENT: &str = "shortcut-triggered";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShortcutAction {
+    ToggleMini,
+    ShowHide,
+    FocusInput,
+    NextUnread,
+    ToggleDnd,
+}
+
+/// Accelerator strings such as `"Ctrl+Shift+K"`, keyed by action. Actions that are
+/// missing or bound to an empty string have no shortcut.
+pub type ShortcutBindings = BTreeMap<ShortcutAction, String>;
+
+pub fn default_bindings() -> ShortcutBindings {
+    BTreeMap::from([(ShortcutAction::ToggleMini, "Ctrl+Backslash".to_string())])
+}
+
+/// Why a binding is not active. Reported to the UI rather than failing startup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutIssue {
+    pub action: ShortcutAction,
+    pub accelerator: String,
+    pub reason: String,
+}
+
+/// Maps registered OS shortcuts back to the action they trigger.
+#[derive(Default)]
+pub struct ShortcutRegistry {
+    actions: Mutex<HashMap<u32, ShortcutAction>>,
+    issues: Mutex<Vec<ShortcutIssue>>,
+}
+
+/// Global handler installed on the plugin; dispatches by shortcut id.
+pub fn handle_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let action = app
+        .state::<ShortcutRegistry>()
+        .actions
+        .lock()
+        .unwrap()
+        .get(&shortcut.id())
+        .copied();
+    if let Some(action) = action {
+        if let Err(err) = run_action(app, action) {
+            eprintln!("shortcut {action:?} failed: {err}");
+        }
+    }
+}
+
+fn run_action(app: &AppHandle, action: ShortcutAction) -> Result<()> {
+    match action {
+        ShortcutAction::ToggleMini => {
+            window::toggle_mini(app)?;
+        }
+        ShortcutAction::ShowHide => {
+            let target = {
+                let machine = app.state::<Mutex<WindowModeMachine>>();
+                let machine = machine.lock().unwrap();
+                match machine.mode() {
+                    WindowMode::Hidden => machine.show_target(),
+                    _ => WindowMode::Hidden,
+                }
+            };
+            window::set_mode(app, target)?;
+        }
+        ShortcutAction::FocusInput | ShortcutAction::NextUnread => {
+            // Both need the full window in front of the user first.
+            window::restore(app)?;
+            let _ = app.emit(SHORTCUT_TRIGGERED_EVENT, action);
+        }
+        ShortcutAction::ToggleDnd => {
+            presence::toggle_dnd(app);
+        }
+    }
+    Ok(())
+}
+
+/// Replace every registered shortcut with `bindings`. Bindings that cannot be
+/// parsed, clash with each other or are already taken by another application are
+/// skipped and returned.
+pub fn apply_bindings(app: &AppHandle, bindings: &ShortcutBindings) -> Vec<ShortcutIssue> {
+    let registry = app.state::<ShortcutRegistry>();
+    let global = app.global_shortcut();
+    let mut actions = registry.actions.lock().unwrap();
+    let _ = global.unregister_all();
+    actions.clear();
+
+    let mut issues = Vec::new();
+    for (&action, accelerator) in bindings {
+        if accelerator.trim().is_empty() {
+            continue;
+        }
+        let issue = |reason: String| ShortcutIssue {
+            action,
+            accelerator: accelerator.clone(),
+            reason,
+        };
+        let shortcut = match Shortcut::from_str(accelerator) {
+            Ok(shortcut) => shortcut,
+            Err(err) => {
+                issues.push(issue(format!("invalid accelerator: {err}")));
+                continue;
+            }
+        };
+        if let Some(other) = actions.get(&shortcut.id()) {
+            issues.push(issue(format!("already bound to {other:?}")));
+            continue;
+        }
+        match global.register(shortcut) {
+            Ok(()) => {
+                actions.insert(shortcut.id(), action);
+            }
+            Err(err) => issues.push(issue(format!("already in use: {err}"))),
+        }
+    }
+    *registry.issues.lock().unwrap() = issues.clone();
+    issues
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutsView {
+    pub bindings: ShortcutBindings,
+    pub issues: Vec<ShortcutIssue>,
+}
+
+#[tauri::command]
+pub fn get_shortcuts(
+    settings: State<'_, SettingsStore>,
+    registry: State<'_, ShortcutRegistry>,
+) -> Result<ShortcutsView> {
+    Ok(ShortcutsView {
+        bindings: settings.get().shortcuts,
+        issues: registry.issues.lock().unwrap().clone(),
+    })
+}
+
+/// Bind `action` to `accelerator` (empty to unbind), persist it and re-register
+/// everything. A binding already taken by another application is still saved so it
+/// starts working once freed; it comes back in the returned issues.
+#[tauri::command]
+pub fn set_shortcut(
+    app: AppHandle,
+    action: ShortcutAction,
+    accelerator: String,
+) -> Result<Vec<ShortcutIssue>> {
+    let accelerator = accelerator.trim().to_string();
+    if !accelerator.is_empty() {
+        Shortcut::from_str(&accelerator)
+            .map_err(|err| Error::InvalidShortcut(format!("{accelerator}: {err}")))?;
+    }
+    let settings = app.state::<SettingsStore>();
+    settings.update(|settings| {
+        settings.shortcuts.insert(action, accelerator);
+    });
+    Ok(apply_bindings(&app, &settings.get().shortcuts))
+}
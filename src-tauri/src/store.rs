@@ -0,0 +1,52 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::PathBuf, sync::Mutex};
+use tauri::Manager;
+
+/// A value persisted as a JSON file in the app config dir. Missing or unreadable
+/// files load as `T::default()`.
+pub struct JsonStore<T> {
+    path: Option<PathBuf>,
+    value: Mutex<T>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default + Clone> JsonStore<T> {
+    pub fn load(app: &tauri::AppHandle, file_name: &str) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(file_name));
+        let value = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            value: Mutex::new(value),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        self.value.lock().unwrap().clone()
+    }
+
+    /// Apply `f` to the value and write it to disk if anything changed.
+    pub fn update(&self, f: impl FnOnce(&mut T)) {
+        let mut value = self.value.lock().unwrap();
+        let before = serde_json::to_string(&*value).ok();
+        f(&mut value);
+        let after = serde_json::to_string(&*value).ok();
+        if before == after {
+            return;
+        }
+        if let (Some(path), Some(json)) = (&self.path, after) {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            if let Err(err) = fs::write(path, json) {
+                eprintln!("failed to save {}: {err}", path.display());
+            }
+        }
+    }
+}
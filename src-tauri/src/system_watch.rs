@@ -0,0 +1,348 @@
+//! Reacts to the machine sleeping and the network coming back instead of
+//! waiting out the socket backoff. logind's `PrepareForSleep` and
+//! NetworkManager's state arrive over the system bus; [`SignalSource`] hides
+//! that so the reactions can be driven by a fake source in tests.
+//!
+//! Before sleeping every account is shown as offline and its socket closed,
+//! under a logind delay lock so the requests get out. On resume, or when the
+//! network is back, every socket reconnects right away.
+
+use futures_util::StreamExt;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use zbus::proxy::{PropertyStream, SignalStream};
+use zbus::zvariant::OwnedFd;
+use zbus::{Connection, Proxy};
+
+use crate::accounts::Accounts;
+use crate::presence;
+
+/// Emitted after waking up or getting back online, once the sockets were told
+/// to reconnect; the presence of other users is likely stale by then.
+pub const SYSTEM_RESUMED_EVENT: &str = "system-resumed";
+
+/// `NM_STATE_CONNECTED_GLOBAL`: NetworkManager reaches the internet, or has
+/// its connectivity check turned off and is connected.
+const NM_CONNECTED_GLOBAL: u32 = 70;
+
+/// How long going offline may hold up sleep; under logind's default
+/// `InhibitDelayMaxSec` of 5 seconds, after which it sleeps anyway.
+const SLEEP_GRACE: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemEvent {
+    /// The machine is about to suspend or hibernate.
+    Sleeping,
+    Resumed,
+    /// Whether the network now reaches the internet.
+    Online(bool),
+}
+
+/// Where [`SystemEvent`]s come from.
+pub trait SignalSource {
+    /// The next event, or `None` once the source is gone.
+    async fn next(&mut self) -> Option<SystemEvent>;
+
+    /// Done with [`SystemEvent::Sleeping`]; the machine may go to sleep.
+    fn ready_to_sleep(&mut self) {}
+}
+
+/// What the watcher does to the app.
+trait Respond {
+    async fn sleep(&self);
+    fn reconnect(&self);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Sleep,
+    Reconnect,
+}
+
+/// Turns events into actions. The network only counts as restored after it
+/// was seen lost, and not while asleep: resuming reconnects by itself.
+#[derive(Debug)]
+struct Watcher {
+    asleep: bool,
+    online: bool,
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self {
+            asleep: false,
+            online: true,
+        }
+    }
+}
+
+impl Watcher {
+    fn react(&mut self, event: SystemEvent) -> Option<Action> {
+        match event {
+            SystemEvent::Sleeping => {
+                self.asleep = true;
+                Some(Action::Sleep)
+            }
+            SystemEvent::Resumed => {
+                self.asleep = false;
+                Some(Action::Reconnect)
+            }
+            SystemEvent::Online(online) => {
+                let restored = online && !self.online && !self.asleep;
+                self.online = online;
+                restored.then_some(Action::Reconnect)
+            }
+        }
+    }
+}
+
+async fn run(mut source: impl SignalSource, respond: &impl Respond) {
+    let mut watcher = Watcher::default();
+    while let Some(event) = source.next().await {
+        match watcher.react(event) {
+            Some(Action::Sleep) => {
+                // A request hanging on a dropped network must not hold the
+                // lock, nor the resume queued behind it
+                if tokio::time::timeout(SLEEP_GRACE, respond.sleep())
+                    .await
+                    .is_err()
+                {
+                    eprintln!("going offline took over {SLEEP_GRACE:?}, sleeping anyway");
+                }
+                source.ready_to_sleep();
+            }
+            Some(Action::Reconnect) => respond.reconnect(),
+            None => {}
+        }
+    }
+}
+
+impl Respond for AppHandle {
+    async fn sleep(&self) {
+        for session in self.state::<Accounts>().all() {
+            session.ws.disconnect();
+        }
+        presence::push_offline(self).await;
+    }
+
+    fn reconnect(&self) {
+        for session in self.state::<Accounts>().all() {
+            session.ws.reconnect();
+        }
+        let _ = self.emit(SYSTEM_RESUMED_EVENT, ());
+    }
+}
+
+/// logind and NetworkManager on the system bus.
+pub struct DbusSource {
+    login: Proxy<'static>,
+    sleep: SignalStream<'static>,
+    /// Missing where NetworkManager is not running.
+    network: Option<PropertyStream<'static, u32>>,
+    /// Delays sleep until [`SignalSource::ready_to_sleep`].
+    inhibitor: Option<OwnedFd>,
+}
+
+impl DbusSource {
+    pub async fn connect() -> zbus::Result<Self> {
+        let bus = Connection::system().await?;
+        let login = Proxy::new(
+            &bus,
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+        )
+        .await?;
+        let sleep = login.receive_signal("PrepareForSleep").await?;
+        let network = match Proxy::new(
+            &bus,
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+        )
+        .await
+        {
+            Ok(network) => Some(network.receive_property_changed("State").await),
+            Err(err) => {
+                eprintln!("not watching NetworkManager: {err}");
+                None
+            }
+        };
+        let mut source = Self {
+            login,
+            sleep,
+            network,
+            inhibitor: None,
+        };
+        source.inhibit().await;
+        Ok(source)
+    }
+
+    async fn inhibit(&mut self) {
+        let args = ("sleep", "Close Chat", "Showing you as offline", "delay");
+        match self.login.call("Inhibit", &args).await {
+            Ok(fd) => self.inhibitor = Some(fd),
+            Err(err) => eprintln!("failed to take a sleep delay lock: {err}"),
+        }
+    }
+}
+
+impl SignalSource for DbusSource {
+    async fn next(&mut self) -> Option<SystemEvent> {
+        loop {
+            tokio::select! {
+                signal = self.sleep.next() => {
+                    match signal?.body().deserialize::<bool>() {
+                        Ok(true) => return Some(SystemEvent::Sleeping),
+                        Ok(false) => {
+                            // The lock is given up before each sleep
+                            self.inhibit().await;
+                            return Some(SystemEvent::Resumed);
+                        }
+                        Err(err) => eprintln!("bad PrepareForSleep signal: {err}"),
+                    }
+                }
+                changed = next_change(&mut self.network) => match changed {
+                    Some(state) => return Some(SystemEvent::Online(state == NM_CONNECTED_GLOBAL)),
+                    // NetworkManager went away; sleep is still watched
+                    None => self.network = None,
+                },
+            }
+        }
+    }
+
+    fn ready_to_sleep(&mut self) {
+        self.inhibitor = None;
+    }
+}
+
+/// The next value of `stream`, `None` if it ended, never if there is none.
+async fn next_change(stream: &mut Option<PropertyStream<'static, u32>>) -> Option<u32> {
+    let Some(stream) = stream else {
+        return std::future::pending().await;
+    };
+    loop {
+        match stream.next().await?.get().await {
+            Ok(value) => return Some(value),
+            Err(err) => eprintln!("bad NetworkManager state: {err}"),
+        }
+    }
+}
+
+/// Watch the system bus for the rest of the run. Without one, e.g. in a
+/// container, the sockets just keep relying on their backoff and heartbeat.
+pub fn spawn(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match DbusSource::connect().await {
+            Ok(source) => run(source, &app).await,
+            Err(err) => eprintln!("not watching for sleep and network changes: {err}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc;
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    struct FakeSource {
+        events: mpsc::UnboundedReceiver<SystemEvent>,
+        log: Log,
+    }
+
+    impl SignalSource for FakeSource {
+        async fn next(&mut self) -> Option<SystemEvent> {
+            self.events.recv().await
+        }
+
+        fn ready_to_sleep(&mut self) {
+            self.log.lock().unwrap().push("ready to sleep");
+        }
+    }
+
+    struct Recorder {
+        log: Log,
+        /// Going offline never finishes, like a request on a dead network.
+        hang: bool,
+    }
+
+    impl Respond for Recorder {
+        async fn sleep(&self) {
+            tokio::task::yield_now().await;
+            if self.hang {
+                std::future::pending::<()>().await;
+            }
+            self.log.lock().unwrap().push("offline");
+        }
+
+        fn reconnect(&self) {
+            self.log.lock().unwrap().push("reconnect");
+        }
+    }
+
+    async fn respond_to(events: &[SystemEvent]) -> Vec<&'static str> {
+        respond_with(events, false).await
+    }
+
+    async fn respond_with(events: &[SystemEvent], hang: bool) -> Vec<&'static str> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::unbounded_channel();
+        for event in events {
+            tx.send(*event).unwrap();
+        }
+        drop(tx);
+        let source = FakeSource {
+            events: rx,
+            log: log.clone(),
+        };
+        let recorder = Recorder {
+            log: log.clone(),
+            hang,
+        };
+        run(source, &recorder).await;
+        drop(recorder);
+        Arc::try_unwrap(log).unwrap().into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn sleep_goes_offline_before_letting_go_and_resume_reconnects() {
+        let log = respond_to(&[SystemEvent::Sleeping, SystemEvent::Resumed]).await;
+        assert_eq!(log, ["offline", "ready to sleep", "reconnect"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hung_sleep_still_lets_go_and_resume_reconnects() {
+        let log = respond_with(&[SystemEvent::Sleeping, SystemEvent::Resumed], true).await;
+        assert_eq!(log, ["ready to sleep", "reconnect"]);
+    }
+
+    #[tokio::test]
+    async fn reconnects_once_the_network_is_back() {
+        let log = respond_to(&[
+            // Initial state: nothing was lost
+            SystemEvent::Online(true),
+            SystemEvent::Online(false),
+            SystemEvent::Online(false),
+            SystemEvent::Online(true),
+            SystemEvent::Online(true),
+        ])
+        .await;
+        assert_eq!(log, ["reconnect"]);
+    }
+
+    #[tokio::test]
+    async fn network_changes_while_asleep_leave_it_to_resume() {
+        let log = respond_to(&[
+            SystemEvent::Sleeping,
+            SystemEvent::Online(false),
+            SystemEvent::Online(true),
+            SystemEvent::Resumed,
+        ])
+        .await;
+        assert_eq!(log, ["offline", "ready to sleep", "reconnect"]);
+    }
+}
@@ -0,0 +1,374 @@
+//! rustls setup following a profile's [`TlsOptions`], shared by the REST client
+//! and the WebSocket: extra PEM roots on top of the system's, SPKI pins, or no
+//! certificate checks at all. Profiles without any of these keep each client's
+//! defaults.
+
+use base64::Engine as _;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{self, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, OtherError, RootCertStore, SignatureScheme};
+use std::error::Error as StdError;
+use std::sync::Arc;
+use tokio_tungstenite::Connector;
+
+use crate::profiles::TlsOptions;
+
+/// Prefix of a pin, as in HPKP: `sha256/<base64 of the SPKI's SHA-256>`.
+const PIN_PREFIX: &str = "sha256/";
+
+/// ALPN for the REST client, which reqwest leaves to a preconfigured config.
+const HTTP_ALPN: &[&[u8]] = &[b"h2", b"http/1.1"];
+
+/// The server's chain held none of the profile's pinned keys.
+#[derive(Debug, thiserror::Error)]
+#[error("certificate for {host} does not match the profile's pinned keys")]
+pub struct PinMismatch {
+    pub host: String,
+}
+
+fn spki_sha256(cert: &CertificateDer<'_>) -> Option<Vec<u8>> {
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    let spki = cert.subject_public_key_info();
+    Some(
+        ring::digest::digest(&ring::digest::SHA256, spki.as_ref())
+            .as_ref()
+            .to_vec(),
+    )
+}
+
+fn parse_pin(pin: &str) -> Result<Vec<u8>, String> {
+    pin.strip_prefix(PIN_PREFIX)
+        .and_then(|hash| base64::engine::general_purpose::STANDARD.decode(hash).ok())
+        .filter(|hash| hash.len() == 32)
+        .ok_or_else(|| format!("{pin}: expected {PIN_PREFIX}<base64 SHA-256>"))
+}
+
+fn load_roots(options: &TlsOptions) -> Result<Vec<CertificateDer<'static>>, String> {
+    let mut roots = Vec::new();
+    for path in &options.ca_certificates {
+        let found = CertificateDer::pem_file_iter(path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        if found.is_empty() {
+            return Err(format!("{}: no PEM certificates", path.display()));
+        }
+        roots.extend(found);
+    }
+    Ok(roots)
+}
+
+impl TlsOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        for pin in &self.pins {
+            parse_pin(pin)?;
+        }
+        load_roots(self).map(drop)
+    }
+
+    fn is_default(&self) -> bool {
+        !self.accept_invalid_certs && self.ca_certificates.is_empty() && self.pins.is_empty()
+    }
+}
+
+/// `None` keeps the client's defaults.
+fn client_config(options: &TlsOptions, alpn: &[&[u8]]) -> Result<Option<ClientConfig>, String> {
+    if options.is_default() {
+        return Ok(None);
+    }
+    let provider = Arc::new(crypto::ring::default_provider());
+    let chain = if options.accept_invalid_certs {
+        None
+    } else {
+        let mut roots = RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs();
+        roots.add_parsable_certificates(native.certs);
+        for root in load_roots(options)? {
+            roots
+                .add(root)
+                .map_err(|err| format!("invalid CA certificate: {err}"))?;
+        }
+        let verifier =
+            WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|err| err.to_string())?;
+        Some(verifier)
+    };
+    let pins = options
+        .pins
+        .iter()
+        .map(|pin| parse_pin(pin))
+        .collect::<Result<_, _>>()?;
+    let verifier = Verifier {
+        chain,
+        pins,
+        provider: provider.clone(),
+    };
+    let mut config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|err| err.to_string())?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+    Ok(Some(config))
+}
+
+/// For `reqwest::ClientBuilder::tls_backend_preconfigured`.
+pub fn http_config(options: &TlsOptions) -> Result<Option<ClientConfig>, String> {
+    client_config(options, HTTP_ALPN)
+}
+
+/// `None` keeps tokio-tungstenite's defaults (native roots).
+pub fn websocket_connector(options: &TlsOptions) -> Result<Option<Connector>, String> {
+    let config = client_config(options, &[])?;
+    Ok(config.map(|config| Connector::Rustls(Arc::new(config))))
+}
+
+/// The [`PinMismatch`] behind a failed request or connection, if that is what
+/// failed. rustls reports it wrapped in an I/O error, which `source()` skips.
+pub fn pin_mismatch<'a>(err: &'a (dyn StdError + 'static)) -> Option<&'a PinMismatch> {
+    let mut next = Some(err);
+    while let Some(err) = next {
+        if let Some(found) = err.downcast_ref::<PinMismatch>() {
+            return Some(found);
+        }
+        if let Some(rustls::Error::Other(OtherError(inner))) = err.downcast_ref::<rustls::Error>() {
+            return pin_mismatch(inner.as_ref());
+        }
+        if let Some(inner) = err
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+        {
+            if let Some(found) = pin_mismatch(inner) {
+                return Some(found);
+            }
+        }
+        next = err.source();
+    }
+    None
+}
+
+/// Checks the chain against the roots (unless certificate checks are off), then
+/// that some certificate in it carries a pinned key. Handshake signatures are
+/// always checked.
+#[derive(Debug)]
+struct Verifier {
+    chain: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<Vec<u8>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl Verifier {
+    fn pinned(&self, cert: &CertificateDer<'_>) -> bool {
+        spki_sha256(cert).is_some_and(|hash| self.pins.contains(&hash))
+    }
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(chain) = &self.chain {
+            chain.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        if !self.pins.is_empty()
+            && !std::iter::once(end_entity)
+                .chain(intermediates)
+                .any(|cert| self.pinned(cert))
+        {
+            let mismatch = PinMismatch {
+                host: server_name.to_str().into_owned(),
+            };
+            return Err(rustls::Error::Other(OtherError(Arc::new(mismatch))));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::PrivateKeyDer;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+    fn spki_pin(cert: &CertificateDer<'_>) -> Option<String> {
+        let hash = spki_sha256(cert)?;
+        Some(format!(
+            "{PIN_PREFIX}{}",
+            base64::engine::general_purpose::STANDARD.encode(hash)
+        ))
+    }
+
+    /// The CA's PEM in a temp file, removed on drop.
+    struct TempPem(PathBuf);
+
+    impl Drop for TempPem {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    struct Server {
+        port: u16,
+        ca_file: TempPem,
+        leaf_pin: String,
+        ca_pin: String,
+    }
+
+    /// A TLS server for `chat.test` with a certificate from a fresh CA, answering
+    /// each connection with "ok".
+    async fn server(connections: usize) -> Server {
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let issuer = rcgen::Issuer::new(ca_params, ca_key);
+        let leaf_key = rcgen::KeyPair::generate().unwrap();
+        let leaf = rcgen::CertificateParams::new(vec!["chat.test".to_string()])
+            .unwrap()
+            .signed_by(&leaf_key, &issuer)
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("closechat-ca-{}.pem", rand::random::<u64>()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(ca.pem().as_bytes())
+            .unwrap();
+
+        let config =
+            rustls::ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(
+                    vec![leaf.der().clone(), ca.der().clone()],
+                    PrivateKeyDer::try_from(leaf_key.serialize_der()).unwrap(),
+                )
+                .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            for _ in 0..connections {
+                let (socket, _) = listener.accept().await.unwrap();
+                if let Ok(mut tls) = acceptor.accept(socket).await {
+                    let _ = tls.write_all(b"ok").await;
+                    let _ = tls.shutdown().await;
+                }
+            }
+        });
+        Server {
+            port,
+            ca_file: TempPem(path),
+            leaf_pin: spki_pin(leaf.der()).unwrap(),
+            ca_pin: spki_pin(ca.der()).unwrap(),
+        }
+    }
+
+    async fn handshake(port: u16, options: &TlsOptions) -> std::io::Result<()> {
+        let config = client_config(options, &[]).unwrap().unwrap();
+        let socket = TcpStream::connect(("127.0.0.1", port)).await?;
+        let name = ServerName::try_from("chat.test").unwrap();
+        let mut tls = TlsConnector::from(Arc::new(config))
+            .connect(name, socket)
+            .await?;
+        let mut reply = String::new();
+        tls.read_to_string(&mut reply).await?;
+        assert_eq!(reply, "ok");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn trusts_extra_roots_and_enforces_pins() {
+        let server = server(4).await;
+        let trusting = TlsOptions {
+            ca_certificates: vec![server.ca_file.0.clone()],
+            ..TlsOptions::default()
+        };
+        assert!(trusting.validate().is_ok());
+        handshake(server.port, &trusting).await.unwrap();
+
+        // Any key in the chain may be pinned
+        let pinned = TlsOptions {
+            pins: vec![server.leaf_pin.clone(), server.ca_pin.clone()],
+            ..trusting.clone()
+        };
+        handshake(server.port, &pinned).await.unwrap();
+
+        let wrong_pin = TlsOptions {
+            pins: vec![format!("{PIN_PREFIX}{}", "A".repeat(43) + "=")],
+            ..trusting.clone()
+        };
+        let err = handshake(server.port, &wrong_pin).await.unwrap_err();
+        let mismatch = pin_mismatch(&err).expect("pin mismatch");
+        assert_eq!(mismatch.host, "chat.test");
+
+        // Without the CA the chain fails first, which is not a pin problem
+        let untrusted = TlsOptions {
+            pins: vec![server.leaf_pin.clone()],
+            ..TlsOptions::default()
+        };
+        let err = handshake(server.port, &untrusted).await.unwrap_err();
+        assert!(pin_mismatch(&err).is_none(), "{err}");
+    }
+
+    #[test]
+    fn rejects_malformed_pins_and_bundles() {
+        let bad_pin = TlsOptions {
+            pins: vec!["sha1/abc".into()],
+            ..TlsOptions::default()
+        };
+        assert!(bad_pin.validate().unwrap_err().contains("sha1/abc"));
+        let missing = TlsOptions {
+            ca_certificates: vec!["/nonexistent/ca.pem".into()],
+            ..TlsOptions::default()
+        };
+        assert!(missing.validate().is_err());
+    }
+}
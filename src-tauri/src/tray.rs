@@ -0,0 +1,359 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{
+    image::Image,
+    menu::{CheckMenuItemBuilder, Menu, MenuBuilder, MenuEvent, MenuItemBuilder, SubmenuBuilder},
+    tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent},
+    AppHandle, Emitter, Manager, Wry,
+};
+
+use crate::accounts::{self, Accounts, Session};
+use crate::api::models::ChannelKind;
+use crate::badge;
+use crate::error::Result;
+use crate::presence::{self, PresenceChoice};
+use crate::window::{self, toggle_mini};
+use crate::window_state;
+
+pub const TRAY_ID: &str = "main";
+
+/// Emitted with the channel id when an unread entry is picked from the tray menu.
+pub const SWITCH_CHANNEL_EVENT: &str = "tray-switch-channel";
+
+/// Sent with a [`ConnectionStatus`] whenever the connection state, the signed-in
+/// user or the last received message changes.
+pub const CONNECTION_STATUS_EVENT: &str = "connection-status";
+
+/// Longer lists are cut off; the window is the place to go through everything.
+const MAX_UNREAD_ITEMS: usize = 10;
+
+const UNREAD_ITEM_PREFIX: &str = "unread:";
+const PRESENCE_ITEM_PREFIX: &str = "presence:";
+const ACCOUNT_ITEM_PREFIX: &str = "account:";
+
+fn presence_item_id(choice: PresenceChoice) -> String {
+    format!("{PRESENCE_ITEM_PREFIX}{}", choice.label())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnreadChannel {
+    pub id: i64,
+    /// Channel name, or the other user's name for a DM.
+    pub name: String,
+    pub kind: ChannelKind,
+    pub count: u32,
+}
+
+impl UnreadChannel {
+    fn label(&self) -> String {
+        let sigil = match self.kind {
+            ChannelKind::Channel => '#',
+            ChannelKind::Dm => '@',
+        };
+        format!("{sigil}{} ({})", self.name, self.count)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectionState {
+    Connected,
+    /// Dropped unexpectedly; retrying with backoff.
+    Reconnecting,
+    /// No network, or not trying to connect.
+    #[default]
+    Offline,
+    SignedOut,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConnectionStatus {
+    pub state: ConnectionState,
+    pub username: Option<String>,
+    pub last_message_at: Option<DateTime<Utc>>,
+}
+
+impl ConnectionStatus {
+    fn tooltip(&self, latency: Option<Duration>) -> String {
+        let label = match self.state {
+            ConnectionState::Connected => "connected",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Offline => "offline",
+            ConnectionState::SignedOut => "signed out",
+        };
+        let mut tooltip = match &self.username {
+            Some(username) if self.state != ConnectionState::SignedOut => {
+                format!("Close Chat: {label} as @{username}")
+            }
+            _ => format!("Close Chat: {label}"),
+        };
+        if let (ConnectionState::Connected, Some(latency)) = (self.state, latency) {
+            tooltip.push_str(&format!(" ({} ms)", latency.as_millis()));
+        }
+        if let Some(at) = self.last_message_at {
+            let at = at.with_timezone(&Local);
+            let format = if at.date_naive() == Local::now().date_naive() {
+                "%H:%M"
+            } else {
+                "%b %-d, %H:%M"
+            };
+            tooltip.push_str(&format!("\nLast message {}", at.format(format)));
+        }
+        tooltip
+    }
+}
+
+/// What the tray currently shows, so it can be rebuilt when any part changes.
+/// Unread counts live with each account in [`Accounts`].
+pub struct TrayState {
+    /// Icon without any badge.
+    icon: Image<'static>,
+    connection: Mutex<ConnectionStatus>,
+}
+
+fn account_label(session: &Session) -> String {
+    let profile = session.api.profile().name;
+    let label = format!("@{} ({profile})", session.user().username);
+    match session.total_unread() {
+        0 => label,
+        unread => format!("{label}: {unread} unread"),
+    }
+}
+
+fn load_icon() -> Image<'static> {
+    Image::from_path("icons/32x32.png").unwrap_or_else(|_| {
+        Image::from_bytes(include_bytes!("../icons/32x32.png"))
+            .expect("failed to load embedded tray icon")
+    })
+}
+
+/// The base icon with the connection state and the unread count across all
+/// accounts drawn on top.
+fn badged_icon(state: &TrayState, total_unread: u32) -> Image<'static> {
+    let icon = &state.icon;
+    let (width, height) = (icon.width(), icon.height());
+    let mut rgba = icon.rgba().to_vec();
+    match state.connection.lock().unwrap().state {
+        ConnectionState::Connected => {
+            badge::draw_status_dot(&mut rgba, width, height, badge::DOT_CONNECTED);
+        }
+        ConnectionState::Reconnecting => {
+            badge::draw_status_dot(&mut rgba, width, height, badge::DOT_RECONNECTING);
+        }
+        ConnectionState::Offline => {
+            badge::desaturate(&mut rgba);
+            badge::draw_status_dot(&mut rgba, width, height, badge::DOT_OFFLINE);
+        }
+        ConnectionState::SignedOut => badge::desaturate(&mut rgba),
+    }
+    let rgba = badge::render_badge(&rgba, width, height, total_unread);
+    Image::new_owned(rgba, width, height)
+}
+
+/// Unread channels of the active account, the accounts when there are several,
+/// then the fixed items.
+fn build_menu(app: &AppHandle, sessions: &[Arc<Session>]) -> tauri::Result<Menu<Wry>> {
+    let mut menu = MenuBuilder::new(app);
+    let active = app.state::<Accounts>().active();
+    let unread = active.as_ref().map(|s| s.unread()).unwrap_or_default();
+    if !unread.is_empty() {
+        let header = MenuItemBuilder::with_id("unread_header", "Unread")
+            .enabled(false)
+            .build(app)?;
+        menu = menu.item(&header);
+        for channel in unread.iter().take(MAX_UNREAD_ITEMS) {
+            menu = menu.text(
+                format!("{UNREAD_ITEM_PREFIX}{}", channel.id),
+                channel.label(),
+            );
+        }
+        menu = menu.separator();
+    }
+
+    // Tauri has no radio items; check boxes with exactly one checked look the same
+    if sessions.len() > 1 {
+        let header = MenuItemBuilder::with_id("accounts_header", "Accounts")
+            .enabled(false)
+            .build(app)?;
+        menu = menu.item(&header);
+        for session in sessions {
+            let is_active = active.as_ref().is_some_and(|a| a.id == session.id);
+            let item = CheckMenuItemBuilder::with_id(
+                format!("{ACCOUNT_ITEM_PREFIX}{}", session.id),
+                account_label(session),
+            )
+            .checked(is_active)
+            .build(app)?;
+            menu = menu.item(&item);
+        }
+        menu = menu.separator();
+    }
+
+    let chosen = presence::current(app).choice;
+    let mut status = SubmenuBuilder::new(app, "Status");
+    for choice in PresenceChoice::ALL {
+        if choice == PresenceChoice::Auto {
+            status = status.separator();
+        }
+        let item = CheckMenuItemBuilder::with_id(presence_item_id(choice), choice.label())
+            .checked(choice == chosen)
+            .build(app)?;
+        status = status.item(&item);
+    }
+    menu = menu.item(&status.build()?).separator();
+
+    menu.text("show_hide", "Show / Hide")
+        .separator()
+        .text("quit", "Quit")
+        .build()
+}
+
+/// Rebuild the tray menu, e.g. after the presence choice changed.
+pub fn refresh_menu(app: &AppHandle) {
+    if let Err(err) = refresh(app) {
+        eprintln!("failed to update tray: {err}");
+    }
+}
+
+/// Rebuild the tray menu and icon from [`TrayState`].
+fn refresh(app: &AppHandle) -> tauri::Result<()> {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    let state = app.state::<TrayState>();
+    let sessions = app.state::<Accounts>().all();
+    let total_unread = sessions.iter().map(|s| s.total_unread()).sum();
+    tray.set_menu(Some(build_menu(app, &sessions)?))?;
+    tray.set_icon(Some(badged_icon(&state, total_unread)))?;
+    set_tooltip(app, &tray)
+}
+
+fn set_tooltip(app: &AppHandle, tray: &TrayIcon) -> tauri::Result<()> {
+    let latency = app
+        .state::<Accounts>()
+        .active()
+        .and_then(|session| session.ws.latency());
+    let tooltip = app
+        .state::<TrayState>()
+        .connection
+        .lock()
+        .unwrap()
+        .tooltip(latency);
+    tray.set_tooltip(Some(tooltip))
+}
+
+/// Show the active account's latest round-trip time in the tooltip.
+pub fn refresh_tooltip(app: &AppHandle) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(err) = set_tooltip(app, &tray) {
+            eprintln!("failed to update tray tooltip: {err}");
+        }
+    }
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    if let Some(channel_id) = id.strip_prefix(UNREAD_ITEM_PREFIX) {
+        let Ok(channel_id) = channel_id.parse::<i64>() else {
+            return;
+        };
+        if let Err(err) = window::restore(app) {
+            eprintln!("{err}");
+        }
+        let _ = app.emit(SWITCH_CHANNEL_EVENT, channel_id);
+        return;
+    }
+    if let Some(account) = id.strip_prefix(ACCOUNT_ITEM_PREFIX) {
+        if let Err(err) = window::restore(app) {
+            eprintln!("{err}");
+        }
+        if let Err(err) = accounts::switch(app, Some(account.to_string()), true) {
+            eprintln!("{err}");
+        }
+        // Re-checks the right item even when the account did not change
+        refresh_menu(app);
+        return;
+    }
+    if let Some(choice) = PresenceChoice::ALL
+        .into_iter()
+        .find(|&choice| presence_item_id(choice) == id)
+    {
+        // Re-checks the right item even when the choice did not change
+        presence::set_choice(app, choice);
+        refresh_menu(app);
+        return;
+    }
+    match id {
+        "show_hide" => {
+            let _ = toggle_mini(app);
+        }
+        "quit" => {
+            if let Some(window) = app.get_webview_window("main") {
+                window_state::flush_save(&window);
+            }
+            app.exit(0);
+        }
+        _ => {}
+    }
+}
+
+/// Create the tray icon. Must run after the window mode machine and [`Accounts`]
+/// are managed.
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let state = TrayState {
+        icon: load_icon(),
+        connection: Mutex::default(),
+    };
+    let icon = badged_icon(&state, 0);
+    let tooltip = state.connection.lock().unwrap().tooltip(None);
+    app.manage(state);
+
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(icon)
+        .menu(&build_menu(app, &[])?)
+        .tooltip(tooltip)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                let _ = toggle_mini(tray.app_handle());
+            }
+        })
+        .build(app)?;
+    Ok(())
+}
+
+/// Replace the active account's unread counts, which the webview keeps while it
+/// shows that account. Only entries with unread messages are listed; order is kept
+/// as given.
+#[tauri::command]
+pub fn set_unread_channels(app: AppHandle, channels: Vec<UnreadChannel>) -> Result<()> {
+    let Some(session) = app.state::<Accounts>().active() else {
+        return Ok(());
+    };
+    if session.set_unread(channels) {
+        accounts::changed(&app);
+    }
+    Ok(())
+}
+
+/// Handler for [`CONNECTION_STATUS_EVENT`].
+pub fn set_connection_status(app: &AppHandle, status: ConnectionStatus) {
+    {
+        let state = app.state::<TrayState>();
+        let mut connection = state.connection.lock().unwrap();
+        if *connection == status {
+            return;
+        }
+        *connection = status;
+    }
+    refresh_menu(app);
+}
@@ -0,0 +1,248 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, PhysicalSize, State, WebviewWindow, WindowEvent};
+
+use crate::error::{Error, Result};
+use crate::settings::{AlwaysOnTopPolicy, Anchor, SettingsStore, WindowSettings};
+use crate::window_mode::{WindowMode, WindowModeMachine, MODE_CHANGED_EVENT};
+use crate::window_state::{self, Bounds, DisplayMode, WindowStateStore};
+
+const NORMAL_WIDTH: f64 = 400.0;
+const NORMAL_HEIGHT: f64 = 500.0;
+
+// Compact placement: the saved bounds when anchored `Free`, otherwise the saved size
+// pinned to the configured corner. First launch, or a saved monitor that is no
+// longer connected, falls back to the default size at the bottom-right.
+fn place_compact(window: &WebviewWindow) {
+    let compact = window.state::<SettingsStore>().get().window.compact;
+    let saved = window.state::<WindowStateStore>().get().geometry;
+    if compact.anchor == Anchor::Free {
+        if let Some(geometry) = &saved {
+            if window_state::apply_geometry(window, geometry) {
+                return;
+            }
+        }
+    }
+    let size = saved
+        .map(|geometry| PhysicalSize::new(geometry.width, geometry.height))
+        .unwrap_or_else(|| {
+            let scale = window.scale_factor().unwrap_or(1.0);
+            PhysicalSize::new(
+                (NORMAL_WIDTH * scale) as u32,
+                (NORMAL_HEIGHT * scale) as u32,
+            )
+        });
+    window_state::place_anchored(
+        window,
+        compact.anchor.or_corner(Anchor::BottomRight),
+        compact.margins,
+        size,
+    );
+}
+
+fn set_window_minimized(window: &WebviewWindow) {
+    let _ = window.show();
+    let settings = window.state::<SettingsStore>().get().window;
+    let mini = settings.mini;
+    let scale = window.scale_factor().unwrap_or(1.0);
+    let phys_size = (mini.size * scale) as u32;
+    let mini_size = PhysicalSize::new(phys_size, phys_size);
+
+    let _ = window.unmaximize();
+    let _ = window.set_resizable(false);
+    let _ = window.set_always_on_top(settings.always_on_top.applies_to(WindowMode::Mini));
+    let _ = window.set_skip_taskbar(true);
+
+    let saved = window.state::<WindowStateStore>().get().mini_position;
+    let restored = mini.anchor == Anchor::Free
+        && saved.is_some_and(|geometry| window_state::apply_position(window, &geometry, mini_size));
+    if !restored {
+        window_state::place_anchored(
+            window,
+            mini.anchor.or_corner(Anchor::TopRight),
+            mini.margins,
+            mini_size,
+        );
+    }
+}
+
+fn set_window_display(window: &WebviewWindow, mode: DisplayMode) {
+    let policy = window.state::<SettingsStore>().get().window.always_on_top;
+    let _ = window.show();
+    let _ = window.set_skip_taskbar(false);
+    let _ = window.set_always_on_top(policy.applies_to(mode.into()));
+    match mode {
+        DisplayMode::Fullscreen => {
+            let _ = window.set_resizable(true);
+            place_compact(window);
+            let _ = window.maximize();
+        }
+        DisplayMode::Compact => {
+            let _ = window.unmaximize();
+            place_compact(window);
+            let _ = window.set_resizable(false);
+        }
+    }
+    let _ = window.set_focus();
+}
+
+pub fn apply_mode(window: &WebviewWindow, mode: WindowMode) {
+    match mode {
+        WindowMode::Compact => set_window_display(window, DisplayMode::Compact),
+        WindowMode::Fullscreen => set_window_display(window, DisplayMode::Fullscreen),
+        WindowMode::Mini => set_window_minimized(window),
+        WindowMode::Hidden => {
+            let _ = window.hide();
+        }
+    }
+}
+
+/// Move the window to `to` if the state machine allows it, persist the new mode
+/// and tell the frontend.
+pub fn set_mode(app: &AppHandle, to: WindowMode) -> Result<()> {
+    let window = app.get_webview_window("main").ok_or(Error::NoWindow)?;
+    let (change, display_mode, minimized) = {
+        let machine = app.state::<Mutex<WindowModeMachine>>();
+        let mut machine = machine.lock().unwrap();
+        let change = machine.transition(to)?;
+        (change, machine.display_mode(), machine.is_minimized())
+    };
+    let Some(change) = change else {
+        return Ok(());
+    };
+
+    // Bounds from the mode being left, before it places the window elsewhere
+    window_state::flush_save(&window);
+    app.state::<WindowStateStore>().update(|state| {
+        state.display_mode = display_mode;
+        state.minimized = minimized;
+    });
+    apply_mode(&window, to);
+    let _ = app.emit(MODE_CHANGED_EVENT, change);
+    Ok(())
+}
+
+pub fn current_mode(app: &AppHandle) -> WindowMode {
+    app.state::<Mutex<WindowModeMachine>>()
+        .lock()
+        .unwrap()
+        .mode()
+}
+
+/// Bring the full window back from Mini or Hidden, in the last display mode.
+pub fn restore(app: &AppHandle) -> Result<()> {
+    let display = app
+        .state::<Mutex<WindowModeMachine>>()
+        .lock()
+        .unwrap()
+        .display_mode();
+    set_mode(app, display.into())
+}
+
+/// Mini toggle from the shortcut or tray. Returns the mode the window ended up in.
+pub fn toggle_mini(app: &AppHandle) -> Result<WindowMode> {
+    let target = app
+        .state::<Mutex<WindowModeMachine>>()
+        .lock()
+        .unwrap()
+        .toggle_mini_target();
+    set_mode(app, target)?;
+    Ok(target)
+}
+
+/// Close hides to the tray instead of quitting; moves and resizes are persisted
+/// once they settle.
+pub fn on_window_event(window: &WebviewWindow, event: &WindowEvent) {
+    match event {
+        WindowEvent::CloseRequested { api, .. } => {
+            api.prevent_close();
+            if let Err(err) = set_mode(window.app_handle(), WindowMode::Hidden) {
+                eprintln!("{err}");
+            }
+        }
+        WindowEvent::Moved(_) | WindowEvent::Resized(_) => {
+            match current_mode(window.app_handle()) {
+                WindowMode::Compact | WindowMode::Fullscreen => {
+                    window_state::schedule_save(window, Bounds::Geometry);
+                }
+                WindowMode::Mini => {
+                    let anchor = window.state::<SettingsStore>().get().window.mini.anchor;
+                    if anchor == Anchor::Free {
+                        window_state::schedule_save(window, Bounds::MiniPosition);
+                    }
+                }
+                WindowMode::Hidden => {}
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Commands the frontend uses to drive the window. Rust stays the owner of the
+/// mode state machine; these only request transitions.
+pub mod commands {
+    use super::*;
+
+    #[derive(Debug, Clone, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct WindowStateView {
+        pub mode: WindowMode,
+        /// Compact/fullscreen mode the window returns to from Mini or Hidden.
+        pub display_mode: DisplayMode,
+        pub always_on_top: AlwaysOnTopPolicy,
+    }
+
+    #[tauri::command]
+    pub fn get_window_state(
+        machine: State<'_, Mutex<WindowModeMachine>>,
+        settings: State<'_, SettingsStore>,
+    ) -> Result<WindowStateView> {
+        let machine = machine.lock().unwrap();
+        Ok(WindowStateView {
+            mode: machine.mode(),
+            display_mode: machine.display_mode(),
+            always_on_top: settings.get().window.always_on_top,
+        })
+    }
+
+    #[tauri::command]
+    pub fn set_mode(app: AppHandle, mode: WindowMode) -> Result<()> {
+        super::set_mode(&app, mode)
+    }
+
+    #[tauri::command]
+    pub fn toggle_mini(app: AppHandle) -> Result<WindowMode> {
+        super::toggle_mini(&app)
+    }
+
+    #[tauri::command]
+    pub fn set_always_on_top_policy(app: AppHandle, policy: AlwaysOnTopPolicy) -> Result<()> {
+        let window = app.get_webview_window("main").ok_or(Error::NoWindow)?;
+        app.state::<SettingsStore>()
+            .update(|settings| settings.window.always_on_top = policy);
+        window.set_always_on_top(policy.applies_to(current_mode(&app)))?;
+        Ok(())
+    }
+
+    #[tauri::command]
+    pub fn get_window_settings(store: State<'_, SettingsStore>) -> Result<WindowSettings> {
+        Ok(store.get().window)
+    }
+
+    #[tauri::command]
+    pub fn set_window_settings(app: AppHandle, settings: WindowSettings) -> Result<()> {
+        settings.validate().map_err(Error::InvalidSettings)?;
+        let window = app.get_webview_window("main").ok_or(Error::NoWindow)?;
+        app.state::<SettingsStore>()
+            .update(|stored| stored.window = settings);
+
+        // Re-place the window so the new anchor/size shows up right away
+        match current_mode(&app) {
+            WindowMode::Mini => set_window_minimized(&window),
+            WindowMode::Compact => set_window_display(&window, DisplayMode::Compact),
+            WindowMode::Fullscreen => set_window_display(&window, DisplayMode::Fullscreen),
+            WindowMode::Hidden => {}
+        }
+        Ok(())
+    }
+}
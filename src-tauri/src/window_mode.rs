@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::window_state::DisplayMode;
+
+pub const MODE_CHANGED_EVENT: &str = "window-mode-changed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    Compact,
+    Fullscreen,
+    Mini,
+    Hidden,
+}
+
+impl From<DisplayMode> for WindowMode {
+    fn from(mode: DisplayMode) -> Self {
+        match mode {
+            DisplayMode::Compact => WindowMode::Compact,
+            DisplayMode::Fullscreen => WindowMode::Fullscreen,
+        }
+    }
+}
+
+/// Payload of [`MODE_CHANGED_EVENT`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModeChange {
+    pub from: WindowMode,
+    pub to: WindowMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalTransition {
+    pub from: WindowMode,
+    pub to: WindowMode,
+}
+
+impl fmt::Display for IllegalTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot switch window from {:?} to {:?}",
+            self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for IllegalTransition {}
+
+/// Single source of truth for what the main window is currently showing.
+///
+/// Mini and Hidden both remember where they came from: leaving the bubble always
+/// goes back to the display mode it was entered from, and showing a hidden window
+/// brings back whatever was hidden.
+#[derive(Debug, Clone)]
+pub struct WindowModeMachine {
+    mode: WindowMode,
+    display: DisplayMode,
+    before_hidden: WindowMode,
+}
+
+impl WindowModeMachine {
+    pub fn new(display: DisplayMode, minimized: bool) -> Self {
+        let mode = if minimized {
+            WindowMode::Mini
+        } else {
+            display.into()
+        };
+        Self {
+            mode,
+            display,
+            before_hidden: mode,
+        }
+    }
+
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    /// The compact/fullscreen mode the window returns to when it leaves Mini.
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display
+    }
+
+    /// Whether the window is (or, while hidden, will come back as) the mini bubble.
+    pub fn is_minimized(&self) -> bool {
+        self.mode == WindowMode::Mini
+            || (self.mode == WindowMode::Hidden && self.before_hidden == WindowMode::Mini)
+    }
+
+    pub fn can_transition(&self, to: WindowMode) -> bool {
+        use WindowMode::*;
+        match (self.mode, to) {
+            (Mini | Hidden, Compact | Fullscreen) => to == self.display.into(),
+            (Hidden, Mini) => self.before_hidden == Mini,
+            _ => true,
+        }
+    }
+
+    /// Move to `to`. Returns `Ok(None)` when already there.
+    pub fn transition(&mut self, to: WindowMode) -> Result<Option<ModeChange>, IllegalTransition> {
+        let from = self.mode;
+        if from == to {
+            return Ok(None);
+        }
+        if !self.can_transition(to) {
+            return Err(IllegalTransition { from, to });
+        }
+        match to {
+            WindowMode::Compact => self.display = DisplayMode::Compact,
+            WindowMode::Fullscreen => self.display = DisplayMode::Fullscreen,
+            WindowMode::Hidden => self.before_hidden = from,
+            WindowMode::Mini => {}
+        }
+        self.mode = to;
+        Ok(Some(ModeChange { from, to }))
+    }
+
+    /// Where a hidden window comes back to: whatever was showing before it was hidden.
+    pub fn show_target(&self) -> WindowMode {
+        match self.mode {
+            WindowMode::Hidden => self.before_hidden,
+            mode => mode,
+        }
+    }
+
+    /// Target of the default toggle-mini shortcut and the tray: a visible window goes to the bubble,
+    /// the bubble or a hidden window comes back to its display mode.
+    pub fn toggle_mini_target(&self) -> WindowMode {
+        match self.mode {
+            WindowMode::Compact | WindowMode::Fullscreen => WindowMode::Mini,
+            WindowMode::Mini | WindowMode::Hidden => self.display.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use WindowMode::*;
+
+    fn change(from: WindowMode, to: WindowMode) -> Result<Option<ModeChange>, IllegalTransition> {
+        Ok(Some(ModeChange { from, to }))
+    }
+
+    #[test]
+    fn starts_in_saved_mode() {
+        assert_eq!(
+            WindowModeMachine::new(DisplayMode::Fullscreen, false).mode(),
+            Fullscreen
+        );
+        let m = WindowModeMachine::new(DisplayMode::Fullscreen, true);
+        assert_eq!(m.mode(), Mini);
+        assert_eq!(m.display_mode(), DisplayMode::Fullscreen);
+    }
+
+    #[test]
+    fn switches_between_display_modes() {
+        let mut m = WindowModeMachine::new(DisplayMode::Compact, false);
+        assert_eq!(m.transition(Fullscreen), change(Compact, Fullscreen));
+        assert_eq!(m.display_mode(), DisplayMode::Fullscreen);
+        assert_eq!(m.transition(Compact), change(Fullscreen, Compact));
+        assert_eq!(m.display_mode(), DisplayMode::Compact);
+    }
+
+    #[test]
+    fn same_mode_is_a_no_op() {
+        let mut m = WindowModeMachine::new(DisplayMode::Compact, false);
+        assert_eq!(m.transition(Compact), Ok(None));
+    }
+
+    #[test]
+    fn mini_returns_to_the_mode_it_came_from() {
+        let mut m = WindowModeMachine::new(DisplayMode::Fullscreen, false);
+        assert_eq!(m.transition(Mini), change(Fullscreen, Mini));
+        assert_eq!(
+            m.transition(Compact),
+            Err(IllegalTransition {
+                from: Mini,
+                to: Compact
+            })
+        );
+        assert_eq!(m.transition(Fullscreen), change(Mini, Fullscreen));
+    }
+
+    #[test]
+    fn hidden_restores_what_was_hidden() {
+        let mut m = WindowModeMachine::new(DisplayMode::Compact, false);
+        m.transition(Mini).unwrap();
+        assert_eq!(m.transition(Hidden), change(Mini, Hidden));
+        assert!(m.is_minimized());
+        assert_eq!(m.show_target(), Mini);
+        assert_eq!(m.transition(Mini), change(Hidden, Mini));
+
+        m.transition(Compact).unwrap();
+        m.transition(Hidden).unwrap();
+        assert!(!m.is_minimized());
+        assert_eq!(
+            m.transition(Mini),
+            Err(IllegalTransition {
+                from: Hidden,
+                to: Mini
+            })
+        );
+        assert_eq!(
+            m.transition(Fullscreen),
+            Err(IllegalTransition {
+                from: Hidden,
+                to: Fullscreen
+            })
+        );
+        assert_eq!(m.transition(Compact), change(Hidden, Compact));
+    }
+
+    #[test]
+    fn toggle_mini_targets() {
+        let mut m = WindowModeMachine::new(DisplayMode::Fullscreen, false);
+        assert_eq!(m.toggle_mini_target(), Mini);
+        m.transition(Mini).unwrap();
+        assert_eq!(m.toggle_mini_target(), Fullscreen);
+        m.transition(Hidden).unwrap();
+        // Toggling a hidden bubble brings the full window back, as before.
+        assert_eq!(m.toggle_mini_target(), Fullscreen);
+        assert!(m.can_transition(m.toggle_mini_target()));
+    }
+}
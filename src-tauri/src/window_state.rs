@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, Monitor, PhysicalPosition, PhysicalSize, WebviewWindow};
+
+use crate::settings::{Anchor, Margins};
+use crate::store::JsonStore;
+
+pub const STATE_FILE: &str = "window-state.json";
+
+/// How long moves and resizes must pause before the bounds are written; a drag
+/// reports every pixel.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    #[default]
+    Compact,
+    Fullscreen,
+}
+
+/// Last normal (not mini, not maximized) bounds of the main window, in physical pixels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub monitor: Option<String>,
+}
+
+/// Everything needed to bring the window back the way it was left.
+/// Mini mode is tracked by `minimized` so that restoring from the bubble
+/// returns to whichever display mode it was entered from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowState {
+    pub geometry: Option<Geometry>,
+    /// Where the mini bubble was last dragged to, used with [`Anchor::Free`].
+    pub mini_position: Option<Geometry>,
+    pub display_mode: DisplayMode,
+    pub minimized: bool,
+}
+
+pub type WindowStateStore = JsonStore<WindowState>;
+
+/// Which bounds a pending save records, decided by the mode when the window moved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bounds {
+    Geometry,
+    MiniPosition,
+}
+
+/// The save waiting out [`SAVE_DELAY`], numbered so that only the last one
+/// scheduled runs.
+#[derive(Debug, Default)]
+pub struct PendingSave(Mutex<(u64, Option<Bounds>)>);
+
+impl PendingSave {
+    fn schedule(&self, bounds: Bounds) -> u64 {
+        let mut pending = self.0.lock().unwrap();
+        pending.0 += 1;
+        pending.1 = Some(bounds);
+        pending.0
+    }
+
+    /// The bounds to save if `generation` is still the latest and not flushed.
+    fn take_if_latest(&self, generation: u64) -> Option<Bounds> {
+        let mut pending = self.0.lock().unwrap();
+        if pending.0 == generation {
+            pending.1.take()
+        } else {
+            None
+        }
+    }
+
+    fn take(&self) -> Option<Bounds> {
+        self.0.lock().unwrap().1.take()
+    }
+}
+
+/// Save `bounds` once the window has stayed put for [`SAVE_DELAY`].
+pub fn schedule_save(window: &WebviewWindow, bounds: Bounds) {
+    let generation = window.state::<PendingSave>().schedule(bounds);
+    let window = window.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SAVE_DELAY).await;
+        if let Some(bounds) = window.state::<PendingSave>().take_if_latest(generation) {
+            save(&window, bounds);
+        }
+    });
+}
+
+/// Write a pending save now, e.g. before the mode changes or the app quits.
+pub fn flush_save(window: &WebviewWindow) {
+    if let Some(bounds) = window.state::<PendingSave>().take() {
+        save(window, bounds);
+    }
+}
+
+fn save(window: &WebviewWindow, bounds: Bounds) {
+    match bounds {
+        Bounds::Geometry => save_geometry(window),
+        Bounds::MiniPosition => save_mini_position(window),
+    }
+}
+
+/// Record the window's current bounds, unless it is maximized (which should not
+/// overwrite the size the user chose). Callers skip this in mini mode.
+pub fn save_geometry(window: &WebviewWindow) {
+    if window.is_maximized().unwrap_or(false) {
+        return;
+    }
+    if let Some(geometry) = current_geometry(window) {
+        window
+            .state::<WindowStateStore>()
+            .update(|state| state.geometry = Some(geometry));
+    }
+}
+
+/// Record where the mini bubble currently sits.
+pub fn save_mini_position(window: &WebviewWindow) {
+    if let Some(geometry) = current_geometry(window) {
+        window
+            .state::<WindowStateStore>()
+            .update(|state| state.mini_position = Some(geometry));
+    }
+}
+
+fn current_geometry(window: &WebviewWindow) -> Option<Geometry> {
+    let (Ok(pos), Ok(size)) = (window.outer_position(), window.inner_size()) else {
+        return None;
+    };
+    let monitor = window
+        .current_monitor()
+        .ok()
+        .flatten()
+        .and_then(|m| m.name().cloned());
+    Some(Geometry {
+        x: pos.x,
+        y: pos.y,
+        width: size.width,
+        height: size.height,
+        monitor,
+    })
+}
+
+/// Move and resize the window to the saved geometry. Returns `false` when the
+/// monitor it was saved on is no longer connected, so the caller can fall back to
+/// its default placement.
+pub fn apply_geometry(window: &WebviewWindow, geometry: &Geometry) -> bool {
+    apply_position(
+        window,
+        geometry,
+        PhysicalSize::new(geometry.width, geometry.height),
+    )
+}
+
+/// Like [`apply_geometry`], but resizes to `size` instead of the saved size.
+pub fn apply_position(
+    window: &WebviewWindow,
+    geometry: &Geometry,
+    size: PhysicalSize<u32>,
+) -> bool {
+    let monitors = window.available_monitors().unwrap_or_default();
+    let Some(monitor) = find_monitor(&monitors, geometry) else {
+        return false;
+    };
+    // Clamp to the monitor in case its resolution shrank since the last run.
+    let bounds = monitor.size();
+    let origin = monitor.position();
+    let width = size.width.min(bounds.width);
+    let height = size.height.min(bounds.height);
+    let max_x = origin.x + bounds.width as i32 - width as i32;
+    let max_y = origin.y + bounds.height as i32 - height as i32;
+    let x = geometry.x.clamp(origin.x, max_x);
+    let y = geometry.y.clamp(origin.y, max_y);
+
+    let _ = window.set_size(PhysicalSize::new(width, height));
+    let _ = window.set_position(PhysicalPosition::new(x, y));
+    true
+}
+
+fn find_monitor<'a>(monitors: &'a [Monitor], geometry: &Geometry) -> Option<&'a Monitor> {
+    if let Some(name) = &geometry.monitor {
+        return monitors.iter().find(|m| m.name() == Some(name));
+    }
+    monitors.iter().find(|m| {
+        let pos = m.position();
+        let size = m.size();
+        geometry.x >= pos.x
+            && geometry.y >= pos.y
+            && geometry.x < pos.x + size.width as i32
+            && geometry.y < pos.y + size.height as i32
+    })
+}
+
+/// Size the window and pin it to `anchor` on its current monitor. `Anchor::Free`
+/// has no corner of its own: callers restore free positions with [`apply_position`]
+/// and resolve the fallback with [`Anchor::or_corner`].
+pub fn place_anchored(
+    window: &WebviewWindow,
+    anchor: Anchor,
+    margins: Margins,
+    size: PhysicalSize<u32>,
+) {
+    let _ = window.set_size(size);
+    let monitor = window
+        .current_monitor()
+        .unwrap_or(None)
+        .or_else(|| window.primary_monitor().unwrap_or(None));
+    let Some(monitor) = monitor else {
+        return;
+    };
+    let scale = window.scale_factor().unwrap_or(1.0);
+    let origin = monitor.position();
+    let bounds = monitor.size();
+    let margin_x = (margins.x * scale) as i32;
+    let margin_y = (margins.y * scale) as i32;
+    let left = origin.x + margin_x;
+    let right = origin.x + bounds.width as i32 - size.width as i32 - margin_x;
+    let top = origin.y + margin_y;
+    let bottom = origin.y + bounds.height as i32 - size.height as i32 - margin_y;
+    let (x, y) = match anchor {
+        Anchor::TopLeft => (left, top),
+        Anchor::TopRight | Anchor::Free => (right, top),
+        Anchor::BottomLeft => (left, bottom),
+        Anchor::BottomRight => (right, bottom),
+    };
+    let _ = window.set_position(PhysicalPosition::new(x, y));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_latest_scheduled_save_runs_once() {
+        let pending = PendingSave::default();
+        let first = pending.schedule(Bounds::Geometry);
+        let second = pending.schedule(Bounds::MiniPosition);
+        assert_eq!(pending.take_if_latest(first), None);
+        assert_eq!(pending.take_if_latest(second), Some(Bounds::MiniPosition));
+        assert_eq!(pending.take_if_latest(second), None);
+
+        let third = pending.schedule(Bounds::Geometry);
+        assert_eq!(pending.take(), Some(Bounds::Geometry));
+        assert_eq!(pending.take_if_latest(third), None);
+    }
+}
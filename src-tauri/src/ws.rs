@@ -0,0 +1,534 @@
+//! WebSocket connection to `/ws`, owned by Rust so messages and unread counts keep
+//! flowing while the webview is hidden or throttled. Frames are parsed into typed
+//! enums and forwarded to the frontend as events. Every signed-in account has its
+//! own socket task; events carry the account id. Messages missed while a socket
+//! was down are backfilled on reconnect, see [`crate::backfill`].
+
+use closechat_protocol::ws::{IncomingFrame, OutgoingFrame};
+use futures_util::{SinkExt, StreamExt};
+use rand::Rng;
+use reqwest::Url;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+use tokio_tungstenite::MaybeTlsStream;
+
+use crate::accounts::{self, Accounts};
+use crate::api::ApiClient;
+use crate::backfill::GapTracker;
+use crate::error::{Error, Result};
+use crate::history::{self, HistoryDb};
+use crate::outbox::Outbox;
+use crate::settings::{HeartbeatSettings, SettingsStore};
+use crate::{proxy, tls, tray};
+
+/// Emitted with an [`AccountFrame`] for every [`IncomingFrame`].
+pub const WS_FRAME_EVENT: &str = "ws-frame";
+
+/// Emitted with a [`WsStatus`] whenever the socket connects, drops or gives up.
+pub const WS_STATE_EVENT: &str = "ws-state";
+
+/// Emitted with a [`WsLatency`] after every pong, and without a latency once the
+/// socket drops.
+pub const WS_LATENCY_EVENT: &str = "ws-latency";
+
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WsState {
+    Connecting,
+    Connected,
+    /// Dropped; the next attempt is `retry_in_ms` away.
+    Reconnecting,
+    /// Disconnected on purpose, e.g. signed out.
+    Offline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsStatus {
+    pub account: String,
+    pub state: WsState,
+    /// Failed attempts since the last successful connection.
+    pub attempt: u32,
+    pub retry_in_ms: Option<u64>,
+    /// Set when retrying will not help without a change to the profile: the
+    /// server's certificate does not match its pins, or its proxy is unusable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WsLatency {
+    pub account: String,
+    /// Round trip of the last heartbeat.
+    pub latency_ms: Option<u64>,
+}
+
+/// The server's flat `{ type, ... }` frame plus the account it arrived on.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountFrame<'a> {
+    pub account: &'a str,
+    #[serde(flatten)]
+    pub frame: &'a IncomingFrame,
+}
+
+/// Exponential backoff with "equal jitter": the delay is drawn from the upper half
+/// of the exponential step, so clients that dropped together spread out.
+#[derive(Debug, Default)]
+struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    fn next_delay(&mut self) -> Duration {
+        let step = BACKOFF_BASE
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(BACKOFF_MAX);
+        self.attempt += 1;
+        let half = step / 2;
+        half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+    }
+}
+
+/// Pings the socket every `interval` and expects the pong within `timeout`. A
+/// half-open connection, e.g. after the laptop slept or Wi-Fi changed, would
+/// otherwise look connected until the OS gives up on it minutes later.
+#[derive(Debug)]
+struct Heartbeat {
+    settings: HeartbeatSettings,
+    nonce: u64,
+    /// Nonce and send time of the unanswered ping.
+    pending: Option<(u64, Instant)>,
+}
+
+impl Heartbeat {
+    fn new(settings: HeartbeatSettings) -> Self {
+        Self {
+            settings,
+            nonce: 0,
+            pending: None,
+        }
+    }
+
+    fn ping(&mut self, now: Instant) -> WsMessage {
+        self.nonce += 1;
+        self.pending = Some((self.nonce, now));
+        WsMessage::Ping(self.nonce.to_be_bytes().to_vec().into())
+    }
+
+    /// The round trip, if `payload` answers the outstanding ping.
+    fn pong(&mut self, payload: &[u8], now: Instant) -> Option<Duration> {
+        let (nonce, sent) = self.pending?;
+        if payload != nonce.to_be_bytes() {
+            return None;
+        }
+        self.pending = None;
+        Some(now - sent)
+    }
+
+    /// When the connection counts as dead unless the pong arrives first.
+    fn deadline(&self) -> Option<Instant> {
+        self.pending.map(|(_, sent)| sent + self.settings.timeout())
+    }
+}
+
+enum Control {
+    Connect,
+    Disconnect,
+    Send(OutgoingFrame),
+}
+
+/// Handle to the socket task started in `run()`. The task ends when this is dropped.
+pub struct WsClient {
+    control: mpsc::UnboundedSender<Control>,
+    connected: Arc<AtomicBool>,
+    latency: Arc<Mutex<Option<Duration>>>,
+}
+
+impl WsClient {
+    /// Connect, or retry right away if waiting out a backoff.
+    pub fn connect(&self) {
+        let _ = self.control.send(Control::Connect);
+    }
+
+    /// Close the socket and stop reconnecting until the next [`WsClient::connect`].
+    pub fn disconnect(&self) {
+        let _ = self.control.send(Control::Disconnect);
+    }
+
+    /// Drop the current connection and open a new one, e.g. after the profile changed.
+    pub fn reconnect(&self) {
+        self.disconnect();
+        self.connect();
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// Round trip of the last heartbeat while connected.
+    pub fn latency(&self) -> Option<Duration> {
+        *self.latency.lock().unwrap()
+    }
+
+    fn send(&self, frame: OutgoingFrame) -> Result<()> {
+        if !self.is_connected() {
+            return Err(Error::NotConnected);
+        }
+        let _ = self.control.send(Control::Send(frame));
+        Ok(())
+    }
+}
+
+/// What the task needs to know about its account.
+struct Link {
+    app: AppHandle,
+    account: String,
+    api: Arc<ApiClient>,
+    connected: Arc<AtomicBool>,
+    latency: Arc<Mutex<Option<Duration>>>,
+    gaps: GapTracker,
+}
+
+/// Start the task for `account`. It stays idle until [`WsClient::connect`].
+pub fn spawn(app: &AppHandle, account: String, api: Arc<ApiClient>) -> WsClient {
+    let (control, rx) = mpsc::unbounded_channel();
+    let connected = Arc::new(AtomicBool::new(false));
+    let latency = Arc::new(Mutex::new(None));
+    let link = Link {
+        app: app.clone(),
+        account,
+        api,
+        connected: connected.clone(),
+        latency: latency.clone(),
+        gaps: GapTracker::default(),
+    };
+    tauri::async_runtime::spawn(run(link, rx));
+    WsClient {
+        control,
+        connected,
+        latency,
+    }
+}
+
+fn emit_state(
+    link: &Link,
+    state: WsState,
+    attempt: u32,
+    retry_in: Option<Duration>,
+    error: Option<String>,
+) {
+    let status = WsStatus {
+        account: link.account.clone(),
+        state,
+        attempt,
+        retry_in_ms: retry_in.map(|delay| delay.as_millis() as u64),
+        error,
+    };
+    let _ = link.app.emit(WS_STATE_EVENT, status);
+}
+
+async fn run(link: Link, mut rx: mpsc::UnboundedReceiver<Control>) {
+    loop {
+        match rx.recv().await {
+            None => return,
+            Some(Control::Connect) => {}
+            // Nothing to send on
+            Some(Control::Disconnect | Control::Send(_)) => continue,
+        }
+        let keep_running = session(&link, &mut rx).await;
+        emit_state(&link, WsState::Offline, 0, None, None);
+        if !keep_running {
+            return;
+        }
+    }
+}
+
+/// Connect and keep reconnecting until told to stop. Returns `false` once the
+/// control channel is gone, i.e. the app is shutting down.
+async fn session(link: &Link, rx: &mut mpsc::UnboundedReceiver<Control>) -> bool {
+    let mut backoff = Backoff::default();
+    loop {
+        emit_state(link, WsState::Connecting, backoff.attempt, None, None);
+        let mut error = None;
+        match connect(&link.api).await {
+            Ok(stream) => {
+                backoff = Backoff::default();
+                link.connected.store(true, Ordering::SeqCst);
+                let db = link.app.state::<HistoryDb>();
+                if let Err(err) = link.gaps.reconnected(&db, &link.account) {
+                    eprintln!("failed to mark cached history stale: {err}");
+                }
+                // What was posted while disconnected goes out before live traffic
+                for frame in link.gaps.backfill(&link.api).await {
+                    deliver(link, &frame);
+                }
+                emit_state(link, WsState::Connected, 0, None, None);
+                link.app.state::<Outbox>().wake();
+                let end = pump(link, stream, rx).await;
+                link.connected.store(false, Ordering::SeqCst);
+                report_latency(link, None);
+                match end {
+                    PumpEnd::Dropped => {}
+                    PumpEnd::Disconnect => return true,
+                    PumpEnd::Shutdown => return false,
+                }
+            }
+            Err(err) => {
+                eprintln!("websocket connect failed: {err}");
+                error = tls::pin_mismatch(&err)
+                    .map(|mismatch| mismatch.to_string())
+                    .or_else(|| proxy::unusable(&err).map(|unusable| unusable.to_string()));
+            }
+        }
+
+        let delay = backoff.next_delay();
+        emit_state(
+            link,
+            WsState::Reconnecting,
+            backoff.attempt,
+            Some(delay),
+            error,
+        );
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                control = rx.recv() => match control {
+                    None => return false,
+                    Some(Control::Disconnect) => return true,
+                    // Retry right away, e.g. the network came back
+                    Some(Control::Connect) => break,
+                    Some(Control::Send(_)) => {}
+                },
+            }
+        }
+    }
+}
+
+type Socket = tokio_tungstenite::WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Open the socket, through the profile's or the environment's proxy if any.
+pub(crate) async fn connect(api: &ApiClient) -> std::result::Result<Socket, WsError> {
+    let url = api.websocket_url();
+    let profile = api.profile();
+    let connector = tls::websocket_connector(&profile.tls)
+        .map_err(|err| WsError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, err)))?;
+    let proxy = match Url::parse(&url) {
+        Ok(parsed) => profile
+            .proxy
+            .for_url(&parsed)
+            .map_err(|err| WsError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, err)))?
+            .map(|proxy| (proxy, parsed)),
+        Err(_) => None,
+    };
+    let Some((proxy, parsed)) = proxy else {
+        let (stream, _) =
+            tokio_tungstenite::connect_async_tls_with_config(url.as_str(), None, false, connector)
+                .await?;
+        return Ok(stream);
+    };
+    let host = parsed.host_str().unwrap_or_default();
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let tunnel = proxy::connect(&proxy, host, port).await?;
+    let (stream, _) =
+        tokio_tungstenite::client_async_tls_with_config(url.as_str(), tunnel, None, connector)
+            .await?;
+    Ok(stream)
+}
+
+enum PumpEnd {
+    Dropped,
+    Disconnect,
+    Shutdown,
+}
+
+async fn pump<S>(
+    link: &Link,
+    stream: tokio_tungstenite::WebSocketStream<S>,
+    rx: &mut mpsc::UnboundedReceiver<Control>,
+) -> PumpEnd
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut write, mut read) = stream.split();
+    let mut heartbeat = Heartbeat::new(link.app.state::<SettingsStore>().get().heartbeat);
+    let interval = heartbeat.settings.interval();
+    let mut ticks = tokio::time::interval_at(Instant::now() + interval, interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let deadline = heartbeat.deadline();
+        tokio::select! {
+            _ = ticks.tick(), if deadline.is_none() => {
+                if let Err(err) = write.send(heartbeat.ping(Instant::now())).await {
+                    eprintln!("websocket ping failed: {err}");
+                    return PumpEnd::Dropped;
+                }
+            }
+            _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                eprintln!("no pong within {:?}, reconnecting", heartbeat.settings.timeout());
+                return PumpEnd::Dropped;
+            }
+            message = read.next() => match message {
+                Some(Ok(WsMessage::Text(text))) => forward(link, &text),
+                Some(Ok(WsMessage::Pong(payload))) => {
+                    if let Some(latency) = heartbeat.pong(&payload, Instant::now()) {
+                        report_latency(link, Some(latency));
+                    }
+                }
+                Some(Ok(WsMessage::Close(_))) | None => return PumpEnd::Dropped,
+                Some(Ok(_)) => {}
+                Some(Err(err)) => {
+                    eprintln!("websocket error: {err}");
+                    return PumpEnd::Dropped;
+                }
+            },
+            control = rx.recv() => match control {
+                None | Some(Control::Disconnect) => {
+                    let _ = write.send(WsMessage::Close(None)).await;
+                    return if control.is_none() { PumpEnd::Shutdown } else { PumpEnd::Disconnect };
+                }
+                Some(Control::Connect) => {}
+                Some(Control::Send(frame)) => {
+                    let Ok(json) = serde_json::to_string(&frame) else { continue };
+                    if let Err(err) = write.send(WsMessage::text(json)).await {
+                        eprintln!("websocket send failed: {err}");
+                        return PumpEnd::Dropped;
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn forward(link: &Link, text: &str) {
+    if let Some(frame) = link.gaps.admit(text) {
+        deliver(link, &frame);
+    }
+}
+
+fn report_latency(link: &Link, latency: Option<Duration>) {
+    *link.latency.lock().unwrap() = latency;
+    let event = WsLatency {
+        account: link.account.clone(),
+        latency_ms: latency.map(|latency| latency.as_millis() as u64),
+    };
+    let _ = link.app.emit(WS_LATENCY_EVENT, event);
+    tray::refresh_tooltip(&link.app);
+}
+
+fn deliver(link: &Link, frame: &IncomingFrame) {
+    accounts::on_frame(&link.app, &link.account, frame);
+    history::on_frame(&link.app, &link.account, frame);
+    let event = AccountFrame {
+        account: &link.account,
+        frame,
+    };
+    let _ = link.app.emit(WS_FRAME_EVENT, event);
+}
+
+/// Connect the active account's socket.
+#[tauri::command]
+pub fn ws_connect(accounts: State<'_, Accounts>) -> Result<()> {
+    if let Some(session) = accounts.active() {
+        session.ws.connect();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn ws_disconnect(accounts: State<'_, Accounts>) -> Result<()> {
+    if let Some(session) = accounts.active() {
+        session.ws.disconnect();
+    }
+    Ok(())
+}
+
+/// Send on the active account's socket.
+#[tauri::command]
+pub fn ws_send(accounts: State<'_, Accounts>, frame: OutgoingFrame) -> Result<()> {
+    let session = accounts.active().ok_or(Error::SignedOut)?;
+    session.ws.send(frame)
+}
+
+/// Round trip of the active account's last heartbeat, in milliseconds.
+#[tauri::command]
+pub fn ws_latency(accounts: State<'_, Accounts>) -> Option<u64> {
+    let latency = accounts.active()?.ws.latency()?;
+    Some(latency.as_millis() as u64)
+}
+
+#[tauri::command]
+pub fn get_heartbeat_settings(store: State<'_, SettingsStore>) -> HeartbeatSettings {
+    store.get().heartbeat
+}
+
+/// Applies from each socket's next connection.
+#[tauri::command]
+pub fn set_heartbeat_settings(
+    store: State<'_, SettingsStore>,
+    settings: HeartbeatSettings,
+) -> Result<()> {
+    settings.validate().map_err(Error::InvalidSettings)?;
+    store.update(|stored| stored.heartbeat = settings);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat() -> Heartbeat {
+        Heartbeat::new(HeartbeatSettings {
+            interval_secs: 15,
+            timeout_secs: 10,
+        })
+    }
+
+    fn payload(ping: WsMessage) -> Vec<u8> {
+        match ping {
+            WsMessage::Ping(payload) => payload.to_vec(),
+            other => panic!("expected a ping, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn pong_answering_the_ping_measures_the_round_trip() {
+        let mut heartbeat = heartbeat();
+        let sent = Instant::now();
+        let ping = payload(heartbeat.ping(sent));
+        assert_eq!(heartbeat.deadline(), Some(sent + Duration::from_secs(10)));
+
+        let later = sent + Duration::from_millis(42);
+        assert_eq!(heartbeat.pong(b"unsolicited", later), None);
+        assert!(heartbeat.deadline().is_some());
+        assert_eq!(
+            heartbeat.pong(&ping, later),
+            Some(Duration::from_millis(42))
+        );
+        assert_eq!(heartbeat.deadline(), None);
+        assert_eq!(heartbeat.pong(&ping, later), None);
+    }
+
+    #[test]
+    fn late_pong_for_an_earlier_ping_is_ignored() {
+        let mut heartbeat = heartbeat();
+        let now = Instant::now();
+        let first = payload(heartbeat.ping(now));
+        let second = payload(heartbeat.ping(now));
+        assert_ne!(first, second);
+        assert_eq!(heartbeat.pong(&first, now), None);
+        assert!(heartbeat.pong(&second, now).is_some());
+    }
+}
@@ -1,5 +1,5 @@
-import { useEffect, useCallback, useRef } from 'react';
-import { getCurrentWindow, LogicalSize, PhysicalPosition } from '@tauri-apps/api/window';
+import { useEffect, useCallback, useRef, useState } from 'react';
+import { getCurrentWindow, PhysicalPosition } from '@tauri-apps/api/window';
 import { listen } from '@tauri-apps/api/event';
 import { useApp } from './context/AppContext';
 import AuthOverlay from './components/AuthOverlay';
@@ -7,6 +7,12 @@ import Sidebar from './components/Sidebar';
 import ChatArea from './components/ChatArea';
 import PeoplePanel from './components/PeoplePanel';
 import MiniView from './components/MiniView';
+import * as windowApi from './lib/window';
+import type { CommandError, WindowModeChange } from './lib/window';
+import * as shortcutsApi from './lib/shortcuts';
+import type { ShortcutAction } from './lib/shortcuts';
+import * as trayApi from './lib/tray';
+import { getWsLatency, onWsLatency } from './lib/ws';
 
 function AppContent() {
   const {
@@ -24,7 +30,10 @@ function AppContent() {
     isMinimized,
     setIsMinimized,
     setPrevDisplayMode,
-    prevDisplayMode,
+    addMessage,
+    channels,
+    activeChannelId,
+    switchToChannel,
   } = useApp();
 
   // Load users & members when people panel opens
@@ -35,24 +44,18 @@ function AppContent() {
     }
   }, [peopleOpen, loadUsers, loadChannelMembers]);
 
-  // Apply Tauri window settings when display mode changes OR when restoring from mini.
-  // isMinimized is included so that when it flips to false, this re-runs and correctly
-  // applies fullscreen/compact sizing (displayMode itself doesn't change while minimized).
+  // Ask Rust to apply the display mode when it changes. Rust owns the window mode
+  // state machine and answers with window-mode-changed; requests it considers
+  // illegal (e.g. resizing while the bubble is showing) are rejected.
+  // Skipped until the mode Rust restored at startup has been pulled in below, so a
+  // stale localStorage mode can't pop the bubble open on launch.
+  const [windowSynced, setWindowSynced] = useState(false);
   useEffect(() => {
-    if (isMinimized) return;
-    const win = getCurrentWindow();
-    if (displayMode === 'fullscreen') {
-      win.setResizable(true).catch(console.error);
-      win.setAlwaysOnTop(false).catch(console.error);
-      win.maximize().catch(console.error);
-    } else {
-      win.setAlwaysOnTop(true).catch(console.error);
-      win.unmaximize()
-        .then(() => win.setSize(new LogicalSize(400, 500)))
-        .then(() => win.setResizable(false))
-        .catch(console.error);
-    }
-  }, [displayMode, isMinimized]);
+    if (!windowSynced || isMinimized) return;
+    windowApi.setMode(displayMode).catch((err: CommandError) => {
+      addMessage('', `system: ${err.message}`, 'system');
+    });
+  }, [displayMode, isMinimized, windowSynced, addMessage]);
 
   // Drag handler: call Tauri's startDragging on mousedown
   const handleDragMouseDown = useCallback((e: React.MouseEvent) => {
@@ -68,30 +71,90 @@ function AppContent() {
     getCurrentWindow().minimize().catch(console.error);
   }, []);
 
-  // Refs so the event listener callback always sees current values
-  const displayModeRef2 = useRef(displayMode);
-  displayModeRef2.current = displayMode;
-  const prevDisplayModeRef = useRef(prevDisplayMode);
-  prevDisplayModeRef.current = prevDisplayMode;
+  // Rust restores the window from the last run before the webview is listening,
+  // so pull the saved mode once on mount instead of relying on events.
+  useEffect(() => {
+    windowApi.getWindowState().then((state) => {
+      setDisplayMode(state.displayMode);
+      setIsMinimized(state.mode === 'mini');
+    }).catch(console.error).finally(() => setWindowSynced(true));
+  }, [setIsMinimized, setDisplayMode]);
 
-  // Listen for minimize-state-changed from Rust (Ctrl+\ shortcut or tray click).
+  // Listen for window-mode-changed from Rust (Ctrl+\ shortcut, tray click or our own requests).
   // Must live here (always mounted), not inside MiniView (only mounted when minimized).
   useEffect(() => {
     let unlisten: (() => void) | undefined;
-    listen<boolean>('minimize-state-changed', (event) => {
-      const minimized = event.payload;
-      if (minimized) {
-        setPrevDisplayMode(displayModeRef2.current);
-      } else {
-        setDisplayMode(prevDisplayModeRef.current);
+    listen<WindowModeChange>('window-mode-changed', (event) => {
+      const { from, to } = event.payload;
+      if (to === 'mini') {
+        if (from === 'compact' || from === 'fullscreen') setPrevDisplayMode(from);
+        setIsMinimized(true);
+      } else if (to === 'compact' || to === 'fullscreen') {
+        setDisplayMode(to);
+        setIsMinimized(false);
       }
-      setIsMinimized(minimized);
     }).then((fn) => { unlisten = fn; });
     return () => { unlisten?.(); };
   }, [setIsMinimized, setPrevDisplayMode, setDisplayMode]);
 
+  // Report shortcuts Rust could not register at startup (taken by another app, bad accelerator).
+  useEffect(() => {
+    shortcutsApi.getShortcuts().then(({ issues }) => {
+      issues.forEach((issue) => addMessage('', `system: ${shortcutsApi.describeIssue(issue)}`, 'system'));
+    }).catch(console.error);
+  }, [addMessage]);
+
+  // Latest channel state for the Rust event listeners below, which subscribe once.
+  const channelNavRef = useRef({ channels, activeChannelId, switchToChannel });
+  channelNavRef.current = { channels, activeChannelId, switchToChannel };
+
+  // Shortcut actions that need the UI. Rust has already brought the window back.
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    listen<ShortcutAction>('shortcut-triggered', (event) => {
+      switch (event.payload) {
+        case 'focus-input':
+          // Wait a frame so the input is mounted after leaving mini mode
+          requestAnimationFrame(() => document.getElementById('messageInput')?.focus());
+          break;
+        case 'next-unread': {
+          const { channels, activeChannelId, switchToChannel } = channelNavRef.current;
+          const start = channels.findIndex((ch) => ch.id === activeChannelId);
+          const ordered = [...channels.slice(start + 1), ...channels.slice(0, start + 1)];
+          const next = ordered.find((ch) => (ch.unreadCount || 0) > 0);
+          if (next) switchToChannel(next);
+          break;
+        }
+      }
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // Keep the tray's Unread section in step with the channel list.
+  useEffect(() => {
+    trayApi.setUnreadChannels(trayApi.toUnreadChannels(channels)).catch(console.error);
+  }, [channels]);
+
+  // Unread entry picked from the tray; Rust has already restored the window.
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    listen<number>('tray-switch-channel', (event) => {
+      const channel = channelNavRef.current.channels.find((ch) => ch.id === event.payload);
+      if (channel) channelNavRef.current.switchToChannel(channel);
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // Heartbeat round trip of the active account's socket, shown in the header.
+  const [latencyMs, setLatencyMs] = useState<number | null>(null);
+  useEffect(() => {
+    if (!isAuthenticated) return;
+    getWsLatency().then(setLatencyMs).catch(console.error);
+    return onWsLatency(setLatencyMs);
+  }, [isAuthenticated, currentUser]);
+
   const handleHide = useCallback(() => {
-    getCurrentWindow().hide().catch(console.error);
+    windowApi.setMode('hidden').catch(console.error);
   }, []);
 
   // Compact mode: no startDragging on title bar (header below handles drag),
@@ -252,6 +315,9 @@ function AppContent() {
             </div>
             <div className="header-right">
               <span className="channel">{channelDisplayName}</span>
+              {latencyMs !== null && (
+                <span className="latency" title="Round trip to the server">{latencyMs} ms</span>
+              )}
               <div className="status-group" title="Show online users" onClick={togglePeople}>
                 <div className="status-dot"></div>
                 <span className="material-icons user-icon">group</span>
@@ -32,21 +32,21 @@ export default function AuthOverlay() {
     loginUserRef.current?.focus();
   }, []);
 
-  // Auto-login: verify stored token on startup
+  // Auto-login: Rust restored an account on startup
   const autoLoginAttempted = useRef(false);
   useEffect(() => {
     if (autoLoginAttempted.current) return;
     autoLoginAttempted.current = true;
 
-    const token = api.getToken();
-    if (token) {
+    if (api.hasSession()) {
       api.verifyToken()
         .then((user) => {
           setCurrentUser(user);
           initApp();
         })
-        .catch(() => {
-          api.clearToken();
+        .catch((err: { kind?: string }) => {
+          // Keep the account through network trouble; only a rejected token ends it
+          if (err.kind === 'unauthorized') api.clearToken();
         });
     }
   }, [setCurrentUser, initApp]);
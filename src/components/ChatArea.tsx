@@ -1,6 +1,9 @@
-import { useState, useRef, useEffect, useLayoutEffect, useCallback } from 'react';
+import { Fragment, useState, useRef, useEffect, useLayoutEffect, useCallback, type ReactNode } from 'react';
 import { useApp } from '../context/AppContext';
+import { getDateStr, getTimestamp } from '../context/chatUtils';
 import * as api from '../lib/api';
+import { EXPORT_FORMATS, exportChannel, onExportProgress, type ExportFormat } from '../lib/export';
+import { importHistory, pickImportArchive, type ImportMapping, type ImportReport } from '../lib/import';
 import EmojiPicker from './EmojiPicker';
 
 // ── Command definitions for autocomplete ──
@@ -13,6 +16,9 @@ const COMMANDS = [
   { name: '/join', desc: 'Join or create a channel', usage: '<channel>' },
   { name: '/leave', desc: 'Leave current channel' },
   { name: '/search', desc: 'Search users', usage: '<query>' },
+  { name: '/find', desc: 'Search cached messages', usage: '<query>' },
+  { name: '/export', desc: 'Save channel history to a file', usage: '[json|markdown|html|log] [from] [to]' },
+  { name: '/import', desc: 'Import Slack or Discord history', usage: '[map <name> <#channel|@user|->|run]' },
   { name: '/members', desc: 'List channel members' },
   { name: '/invite', desc: 'Create invite link (admin)', usage: '[maxUses] [expiresHrs]' },
   { name: '/invites', desc: 'List active invites (admin)' },
@@ -29,6 +35,21 @@ function escapeHtml(text: string): string {
   return div.innerHTML;
 }
 
+// Search snippets mark their matches; show those as <mark>.
+function highlightMatches(text: string): ReactNode {
+  if (!text.includes(api.SEARCH_MATCH_START)) return text;
+  return text.split(api.SEARCH_MATCH_START).map((part, index) => {
+    if (index === 0) return part;
+    const [match, rest] = part.split(api.SEARCH_MATCH_END);
+    return (
+      <Fragment key={index}>
+        <mark>{match}</mark>
+        {rest}
+      </Fragment>
+    );
+  });
+}
+
 function formatDateLabel(dateStr: string): string {
   const [y, mo, d] = dateStr.split('-').map(Number);
   const date = new Date(y, mo - 1, d);
@@ -51,6 +72,8 @@ export default function ChatArea() {
     addMessage,
     clearMessages,
     sendChatMessage,
+    retryChatMessage,
+    discardChatMessage,
     channels,
     loadChannels,
     loadChannelMembers,
@@ -69,6 +92,10 @@ export default function ChatArea() {
   const [cmdSelectedIndex, setCmdSelectedIndex] = useState(0);
   const [cmdVisible, setCmdVisible] = useState(false);
   const [emojiPickerOpen, setEmojiPickerOpen] = useState(false);
+  // Messages collected by a running export, shown above the input
+  const [exportCount, setExportCount] = useState<number | null>(null);
+  // The archive picked by /import and its last dry run, until it is imported
+  const importRef = useRef<{ path: string; report: ImportReport } | null>(null);
 
   const chatAreaRef = useRef<HTMLElement>(null);
   const inputRef = useRef<HTMLInputElement>(null);
@@ -98,6 +125,15 @@ export default function ChatArea() {
     el.scrollTop = el.scrollHeight;
   }, [messages]);
 
+  useEffect(() => {
+    const unlisten = onExportProgress((progress) => {
+      setExportCount(progress.done ? null : progress.messages);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   // Scroll listener — load more when near top
   useEffect(() => {
     const el = chatAreaRef.current;
@@ -137,6 +173,9 @@ export default function ChatArea() {
         addMessage('', 'system: /join <channel> - Join a channel', 'system');
         addMessage('', 'system: /leave - Leave current channel', 'system');
         addMessage('', 'system: /search <query> - Search users by username', 'system');
+        addMessage('', 'system: /find <query> - Search messages, e.g. from:alice in:#deploys "rollback" before:2026-09-01', 'system');
+        addMessage('', 'system: /export [json|markdown|html|log] [from] [to] - Save channel history, dates as YYYY-MM-DD', 'system');
+        addMessage('', 'system: /import - Preview a Slack or Discord export; /import map <name> <#channel|@user|->, then /import run', 'system');
         addMessage('', 'system: /members - List members of current channel', 'system');
         addMessage('', 'system: /invite [maxUses] [expiresHrs] - Create invite link (admin)', 'system');
         addMessage('', 'system: /invites - List active invites (admin)', 'system');
@@ -218,6 +257,38 @@ export default function ChatArea() {
         }
         return true;
 
+      case '/find':
+        if (parts.length > 1) {
+          handleFindMessages(parts.slice(1).join(' '));
+        } else {
+          addMessage('', 'system: Usage: /find <words> [from:user] [in:#channel] [before:date] [after:date]', 'system');
+        }
+        return true;
+
+      case '/export': {
+        const format = (parts[1] || 'markdown').toLowerCase();
+        if (!activeChannelId) {
+          addMessage('', 'system: No channel selected', 'system');
+        } else if (!EXPORT_FORMATS.includes(format as ExportFormat)) {
+          addMessage('', `system: Usage: /export [${EXPORT_FORMATS.join('|')}] [from] [to]`, 'system');
+        } else {
+          handleExport(format as ExportFormat, parts[2], parts[3]);
+        }
+        return true;
+      }
+
+      case '/import':
+        if (!parts[1]) {
+          handleImportPick();
+        } else if (parts[1] === 'map' && parts.length === 4) {
+          handleImportMap(parts[2], parts[3]);
+        } else if (parts[1] === 'run') {
+          handleImportRun();
+        } else {
+          addMessage('', 'system: Usage: /import [map <name> <#channel|@user|->|run]', 'system');
+        }
+        return true;
+
       case '/members':
         if (activeChannelId) {
           handleListMembers();
@@ -331,6 +402,114 @@ export default function ChatArea() {
     }
   }
 
+  async function handleFindMessages(query: string) {
+    try {
+      const hits = await api.searchMessages(query, 20);
+      if (hits.length === 0) {
+        addMessage('', `system: No cached messages match "${query}"`, 'system');
+        return;
+      }
+      addMessage('', `system: Messages matching "${query}" (${hits.length}):`, 'system');
+      hits.forEach((hit) => {
+        const where = hit.channelName ?? `channel ${hit.channelId}`;
+        const when = `${getDateStr(hit.createdAt)} ${getTimestamp(hit.createdAt)}`;
+        const imported = hit.importedFrom ? ` (from ${hit.importedFrom})` : '';
+        addMessage('', `system:   ${where} @${hit.senderUsername} [${when}]${imported} ${hit.snippet}`, 'system');
+      });
+    } catch (err: unknown) {
+      const message = err instanceof Error ? err.message : 'unknown error';
+      addMessage('', `system: Search failed: ${message}`, 'system');
+    }
+  }
+
+  function showImportReport(report: ImportReport) {
+    const verb = report.dryRun ? 'Would import' : 'Imported';
+    addMessage('', `system: ${verb} ${report.imported} ${report.source} messages, skipping ${report.skipped}`, 'system');
+    report.channels.forEach((c) => {
+      const target = channels.find((ch) => Number(ch.id) === c.channelId);
+      const mapped = target ? `#${target.name.replace(/^#/, '')}` : 'not mapped, skipped';
+      addMessage('', `system:   #${c.name} (${c.messages}) -> ${mapped}`, 'system');
+    });
+    report.users.forEach((u) => {
+      const mapped = u.username ? `@${u.username}` : 'kept as is';
+      addMessage('', `system:   @${u.name} (${u.messages}) -> ${mapped}`, 'system');
+    });
+  }
+
+  async function runImport(path: string, dryRun: boolean, mapping?: ImportMapping) {
+    try {
+      const report = await importHistory(path, dryRun, mapping);
+      importRef.current = dryRun ? { path, report } : null;
+      showImportReport(report);
+      if (dryRun) {
+        addMessage('', 'system: Adjust with /import map <name> <#channel|@user|->, then /import run', 'system');
+      }
+    } catch (err: unknown) {
+      const message = (err as { message?: string }).message ?? String(err);
+      addMessage('', `system: Import failed: ${message}`, 'system');
+    }
+  }
+
+  async function handleImportPick() {
+    const path = await pickImportArchive();
+    if (path) {
+      await runImport(path, true);
+    }
+  }
+
+  async function handleImportMap(name: string, target: string) {
+    const pending = importRef.current;
+    if (!pending) {
+      addMessage('', 'system: Pick an archive with /import first', 'system');
+      return;
+    }
+    const mapping: ImportMapping = {
+      users: { ...pending.report.mapping.users },
+      channels: { ...pending.report.mapping.channels },
+    };
+    const key = name.replace(/^[#@]/, '');
+    if (target === '-') {
+      delete mapping.channels[key];
+      delete mapping.users[key];
+    } else if (target.startsWith('@')) {
+      mapping.users[key] = target.slice(1);
+    } else {
+      const channelName = target.replace(/^#/, '');
+      const channel = channels.find((c) => c.name === channelName || c.name === `#${channelName}`);
+      if (!channel) {
+        addMessage('', `system: No channel #${channelName}`, 'system');
+        return;
+      }
+      mapping.channels[key] = Number(channel.id);
+    }
+    await runImport(pending.path, true, mapping);
+  }
+
+  async function handleImportRun() {
+    const pending = importRef.current;
+    if (!pending) {
+      addMessage('', 'system: Pick an archive with /import first', 'system');
+      return;
+    }
+    // The mapping the preview showed; previews do not save it
+    await runImport(pending.path, false, pending.report.mapping);
+  }
+
+  async function handleExport(format: ExportFormat, from?: string, to?: string) {
+    if (!activeChannelId) return;
+    try {
+      const path = await exportChannel(activeChannelId, format, { from, to });
+      if (path) {
+        addMessage('', `system: Exported to ${path}`, 'system');
+      }
+    } catch (err: unknown) {
+      const message = (err as { message?: string }).message ?? String(err);
+      addMessage('', `system: Export failed: ${message}`, 'system');
+    } finally {
+      setExportCount(null);
+    }
+  }
+
   async function handleListMembers() {
     if (!activeChannelId) return;
     try {
@@ -550,7 +729,7 @@ export default function ChatArea() {
                   <div className="message-line">
                     {msg.type === 'system' ? (
                       <>
-                        <span className="msg-system">*** {msg.text}</span>
+                        <span className="msg-system">*** {highlightMatches(msg.text)}</span>
                         <span className="msg-timestamp">[{msg.timestamp}]</span>
                       </>
                     ) : (
@@ -564,6 +743,19 @@ export default function ChatArea() {
                           className="msg-text"
                           dangerouslySetInnerHTML={{ __html: escapeHtml(msg.text) }}
                         />
+                        {msg.importedFrom && (
+                          <span className="msg-imported">{msg.importedFrom}</span>
+                        )}
+                        {(msg.outbox?.status === 'pending' || msg.outbox?.status === 'sending') && (
+                          <span className="msg-outbox">sending…</span>
+                        )}
+                        {msg.outbox?.status === 'failed' && (
+                          <span className="msg-outbox failed" title={msg.outbox.error}>
+                            not sent
+                            <button onClick={() => retryChatMessage(msg.outbox!.key)}>retry</button>
+                            <button onClick={() => discardChatMessage(msg.outbox!.key)}>discard</button>
+                          </span>
+                        )}
                         <span className="msg-timestamp">[{msg.timestamp}]</span>
                       </>
                     )}
@@ -609,6 +801,9 @@ export default function ChatArea() {
 
       {/* Input footer */}
       <footer id="footer">
+        {exportCount !== null && (
+          <div className="export-status">exporting... {exportCount} messages</div>
+        )}
         <div className="input-row">
           <div className="input-wrapper">
             <input
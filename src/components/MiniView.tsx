@@ -1,6 +1,7 @@
 import { useState, useEffect, useRef } from 'react';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { useApp } from '../context/AppContext';
+import { toggleMini } from '../lib/window';
 import Ascii3 from './ascii-3ring';
 
 export default function MiniView() {
@@ -22,13 +23,39 @@ export default function MiniView() {
     prevUnreadRef.current = totalUnread;
   }, [totalUnread]);
 
+  // A press that moves more than a few pixels drags the bubble instead of opening it.
+  // Rust remembers where it was dropped when the mini anchor is set to "free".
+  const pressRef = useRef<{ x: number; y: number } | null>(null);
+  const draggedRef = useRef(false);
+
+  const handleMouseDown = (e: React.MouseEvent) => {
+    if (e.button !== 0) return;
+    pressRef.current = { x: e.screenX, y: e.screenY };
+    draggedRef.current = false;
+  };
+
+  const handleMouseMove = (e: React.MouseEvent) => {
+    const press = pressRef.current;
+    if (!press || draggedRef.current) return;
+    if (Math.abs(e.screenX - press.x) + Math.abs(e.screenY - press.y) > 4) {
+      draggedRef.current = true;
+      pressRef.current = null;
+      getCurrentWindow().startDragging().catch(console.error);
+    }
+  };
+
   const handleClick = async () => {
+    pressRef.current = null;
+    if (draggedRef.current) {
+      draggedRef.current = false;
+      return;
+    }
     setHasNewMessage(false);
-    await getCurrentWindow().emit('restore-window');
+    await toggleMini().catch(console.error);
   };
 
   return (
-    <div className="mini-view" onClick={handleClick}>
+    <div className="mini-view" onMouseDown={handleMouseDown} onMouseMove={handleMouseMove} onClick={handleClick}>
       <div className="mini-logo">
         <Ascii3 compact />
       </div>
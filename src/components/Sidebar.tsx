@@ -1,11 +1,19 @@
 import { useState, useCallback, useEffect, useRef } from 'react';
 import { getCurrentWindow } from '@tauri-apps/api/window';
 import { getVersion } from '@tauri-apps/api/app';
+import { listen } from '@tauri-apps/api/event';
 import { check, type Update } from '@tauri-apps/plugin-updater';
 import { relaunch } from '@tauri-apps/plugin-process';
 import { useApp } from '../context/AppContext';
 import type { Channel } from '../lib/api';
 import type { UsernameStyle, DisplayMode } from '../context/AppContext';
+import * as presenceApi from '../lib/presence';
+import type { PresenceChoice, PresenceView } from '../lib/presence';
+import * as profilesApi from '../lib/profiles';
+import type { ProfilesView } from '../lib/profiles';
+import * as accountsApi from '../lib/accounts';
+import type { AccountsView } from '../lib/accounts';
+import * as api from '../lib/api';
 
 function escapeHtml(text: string): string {
   const div = document.createElement('div');
@@ -42,6 +50,14 @@ const FONT_OPTIONS: { value: UsernameStyle; label: string; fontClass: string }[]
   { value: 'traditional', label: 'Traditional (<@user>)', fontClass: 'traditional' },
 ];
 
+const PRESENCE_OPTIONS: { value: PresenceChoice; label: string; desc: string }[] = [
+  { value: 'auto', label: 'Auto', desc: 'Idle when the window is in the background' },
+  { value: 'online', label: 'Online', desc: '' },
+  { value: 'idle', label: 'Idle', desc: '' },
+  { value: 'dnd', label: 'Do Not Disturb', desc: '' },
+  { value: 'invisible', label: 'Invisible', desc: 'Shown to others as offline' },
+];
+
 export default function Sidebar() {
   const { channels, activeChannelId, switchToChannel, setSidebarOpen, usernameStyle, setUsernameStyle, displayMode, setDisplayMode } = useApp();
   const [searchFilter, setSearchFilter] = useState('');
@@ -51,11 +67,38 @@ export default function Sidebar() {
   const [pendingUpdate, setPendingUpdate] = useState<Update | null>(null);
   const [downloadProgress, setDownloadProgress] = useState({ downloaded: 0, total: 0 });
   const relaunchAfterInstall = useRef(false);
+  const [presenceChoice, setPresenceChoice] = useState<PresenceChoice>('auto');
+  const [profiles, setProfiles] = useState<ProfilesView | null>(null);
+  const [accounts, setAccounts] = useState<AccountsView | null>(null);
 
   useEffect(() => {
     getVersion().then(setAppVersion).catch(() => {});
   }, []);
 
+  useEffect(() => {
+    profilesApi.getProfiles().then(setProfiles).catch(console.error);
+  }, []);
+
+  // Unread totals of the other accounts change in the background
+  useEffect(() => {
+    accountsApi.listAccounts().then(setAccounts).catch(console.error);
+    let unlisten: (() => void) | undefined;
+    listen<AccountsView>('accounts-changed', (event) => {
+      setAccounts(event.payload);
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
+  // Follow the choice Rust holds, which the tray can change too
+  useEffect(() => {
+    presenceApi.getPresence().then((view) => setPresenceChoice(view.choice)).catch(console.error);
+    let unlisten: (() => void) | undefined;
+    listen<PresenceView>('presence-changed', (event) => {
+      setPresenceChoice(event.payload.choice);
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, []);
+
   async function handleCheckUpdate() {
     setUpdateStatus('checking');
     setPendingUpdate(null);
@@ -206,6 +249,107 @@ export default function Sidebar() {
             </div>
           </div>
 
+          <div className="setting-group" style={{ marginBottom: '20px' }}>
+            <label style={{ display: 'block', color: '#a3a3a3', fontSize: '12px', fontWeight: 500, marginBottom: '10px' }}>
+              Status
+            </label>
+            <div className="setting-options" style={{ display: 'flex', flexDirection: 'column', gap: '12px' }}>
+              {PRESENCE_OPTIONS.map((opt) => (
+                <label
+                  key={opt.value}
+                  style={{ display: 'flex', alignItems: 'flex-start', gap: '8px', cursor: 'pointer', fontSize: '13px' }}
+                >
+                  <input
+                    type="radio"
+                    name="presence"
+                    value={opt.value}
+                    checked={presenceChoice === opt.value}
+                    onChange={() => presenceApi.setPresence(opt.value).catch(console.error)}
+                    style={{ accentColor: '#fb923c', marginTop: '2px', flexShrink: 0 }}
+                  />
+                  <div>
+                    <div style={{ color: '#ffffff' }}>{opt.label}</div>
+                    {opt.desc && <div style={{ color: '#525252', fontSize: '11px', marginTop: '2px' }}>{opt.desc}</div>}
+                  </div>
+                </label>
+              ))}
+            </div>
+          </div>
+
+          {accounts && (
+            <div className="setting-group" style={{ marginBottom: '20px' }}>
+              <label style={{ display: 'block', color: '#a3a3a3', fontSize: '12px', fontWeight: 500, marginBottom: '10px' }}>
+                Accounts
+              </label>
+              <div className="setting-options" style={{ display: 'flex', flexDirection: 'column', gap: '12px' }}>
+                {accounts.accounts.map((account) => (
+                  <div key={account.id} style={{ display: 'flex', alignItems: 'flex-start', gap: '8px', fontSize: '13px' }}>
+                    <label style={{ display: 'flex', alignItems: 'flex-start', gap: '8px', cursor: 'pointer', flex: 1 }}>
+                      <input
+                        type="radio"
+                        name="account"
+                        value={account.id}
+                        checked={accounts.active === account.id}
+                        onChange={() => accountsApi.switchAccount(account.id).catch(console.error)}
+                        style={{ accentColor: '#fb923c', marginTop: '2px', flexShrink: 0 }}
+                      />
+                      <div>
+                        <div style={{ color: '#ffffff' }}>
+                          @{account.user.username}
+                          {account.unread > 0 && <span style={{ color: '#fb923c' }}> ({account.unread})</span>}
+                        </div>
+                        <div style={{ color: '#525252', fontSize: '11px', marginTop: '2px' }}>
+                          {account.profile.name}{account.connected ? '' : ' · offline'}
+                        </div>
+                      </div>
+                    </label>
+                    <button
+                      onClick={() => api.forgetAccount(account.id).then(setAccounts).catch(console.error)}
+                      style={{ background: 'none', border: 'none', color: '#525252', fontSize: '11px', cursor: 'pointer', padding: 0 }}
+                    >
+                      sign out
+                    </button>
+                  </div>
+                ))}
+                <button
+                  onClick={() => accountsApi.switchAccount(null).catch(console.error)}
+                  style={{ background: 'none', border: 'none', color: '#fb923c', fontSize: '12px', cursor: 'pointer', padding: 0, textAlign: 'left' }}
+                >
+                  + add account
+                </button>
+              </div>
+            </div>
+          )}
+
+          {profiles && (profiles.profiles.length > 1 || !profiles.profiles.some((p) => p.name === profiles.active.name)) && (
+            <div className="setting-group" style={{ marginBottom: '20px' }}>
+              <label style={{ display: 'block', color: '#a3a3a3', fontSize: '12px', fontWeight: 500, marginBottom: '10px' }}>
+                Server
+              </label>
+              <div className="setting-options" style={{ display: 'flex', flexDirection: 'column', gap: '12px' }}>
+                {profiles.profiles.map((profile) => (
+                  <label
+                    key={profile.name}
+                    style={{ display: 'flex', alignItems: 'flex-start', gap: '8px', cursor: 'pointer', fontSize: '13px' }}
+                  >
+                    <input
+                      type="radio"
+                      name="profile"
+                      value={profile.name}
+                      checked={profiles.active.name === profile.name}
+                      onChange={() => profilesApi.switchProfile(profile.name).catch(console.error)}
+                      style={{ accentColor: '#fb923c', marginTop: '2px', flexShrink: 0 }}
+                    />
+                    <div>
+                      <div style={{ color: '#ffffff' }}>{profile.name}</div>
+                      <div style={{ color: '#525252', fontSize: '11px', marginTop: '2px' }}>{profile.baseUrl}</div>
+                    </div>
+                  </label>
+                ))}
+              </div>
+            </div>
+          )}
+
           {/* ── About ── */}
           <div style={{ borderTop: '1px solid #1a1a1a', marginBottom: '14px' }} />
           <div style={{ color: '#fb923c', fontSize: '11px', fontWeight: 600, letterSpacing: '0.08em', textTransform: 'uppercase', marginBottom: '14px' }}>
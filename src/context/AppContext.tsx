@@ -71,6 +71,8 @@ interface AppContextValue {
   // WebSocket
   initApp: () => Promise<void>;
   sendChatMessage: (text: string) => void;
+  retryChatMessage: (key: string) => void;
+  discardChatMessage: (key: string) => void;
 
   // Settings
   usernameStyle: UsernameStyle;
@@ -153,6 +155,8 @@ export function AppProvider({ children }: { children: ReactNode }) {
     reloadActiveChannel: chatState.reloadActiveChannel,
     initApp,
     sendChatMessage: chatState.sendChatMessage,
+    retryChatMessage: chatState.retryChatMessage,
+    discardChatMessage: chatState.discardChatMessage,
     usernameStyle: uiState.usernameStyle,
     setUsernameStyle: uiState.setUsernameStyle,
     displayMode: uiState.displayMode,
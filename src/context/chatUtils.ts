@@ -1,4 +1,5 @@
 import type { Channel } from '../lib/api';
+import type { OutboxStatus } from '../lib/outbox';
 
 export type MessageType = 'user' | 'bot' | 'system';
 
@@ -19,6 +20,10 @@ export interface ChatMessage {
   type: MessageType;
   timestamp: string;
   date: string;
+  // Set while our own message waits in the outbox or after it failed
+  outbox?: { key: string; status: OutboxStatus; error?: string };
+  // Set on history imported from another chat, e.g. 'slack'
+  importedFrom?: string;
 }
 
 let msgIdCounter = 0;
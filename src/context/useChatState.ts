@@ -1,6 +1,8 @@
-import { useCallback, useRef, useState } from 'react';
-import type { User, Channel, ChannelMember } from '../lib/api';
+import { useCallback, useEffect, useRef, useState } from 'react';
+import type { User, Channel, ChannelMember, HistoryPage } from '../lib/api';
 import * as api from '../lib/api';
+import * as outbox from '../lib/outbox';
+import type { OutboxItem } from '../lib/outbox';
 import { isWsConnected, sendWs } from '../lib/ws';
 import {
   createChatMessage,
@@ -55,6 +57,26 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
     setMessages((prev) => [...prev, createChatMessage(username, text, type, timestamp, date)]);
   }, []);
 
+  // Outbox updates that arrived before outbox_send returned the item
+  const earlyOutboxRef = useRef(new Map<string, OutboxItem>());
+
+  useEffect(() => {
+    const unlisten = outbox.onOutboxChanged((item) => {
+      let known = false;
+      setMessages((prev) => prev.flatMap((message) => {
+        if (message.outbox?.key !== item.key) return [message];
+        known = true;
+        if (item.status === 'discarded') return [];
+        if (item.status === 'sent') return [{ ...message, outbox: undefined }];
+        return [{ ...message, outbox: { key: item.key, status: item.status, error: item.error } }];
+      }));
+      if (!known) earlyOutboxRef.current.set(item.key, item);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   const clearMessages = useCallback(() => {
     setMessages([]);
   }, []);
@@ -90,9 +112,9 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
       date: getDateStr(),
     };
 
-    try {
-      const { messages: apiMessages, hasMore: initialHasMore } = await api.getMessages(channel.id, { limit: 50 });
-
+    const showPage = async ({ messages: apiMessages, hasMore: initialHasMore }: HistoryPage) => {
+      // Our own messages still waiting to go out follow the history
+      const queued = await outbox.listOutbox().catch(() => [] as OutboxItem[]);
       if (activeChannelIdRef.current !== channel.id) {
         return;
       }
@@ -105,11 +127,41 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
         type: message.type || 'user',
         timestamp: getTimestamp(message.createdAt),
         date: getDateStr(message.createdAt),
+        importedFrom: message.importedFrom ?? undefined,
       }));
-
-      setMessages([systemMsg, ...rendered]);
+      const pending: ChatMessage[] = queued
+        .filter((item) => item.channelId === Number(channel.id))
+        .map((item) => ({
+          ...createChatMessage(
+            currentUserRef.current ? `@${currentUserRef.current.username}` : '@anon',
+            item.content,
+            item.kind || 'user',
+            getTimestamp(item.createdAt),
+            getDateStr(item.createdAt),
+          ),
+          outbox: { key: item.key, status: item.status, error: item.error },
+        }));
+
+      setMessages([systemMsg, ...rendered, ...pending]);
       setHasMore(initialHasMore);
       setOldestMessageApiId(apiMessages.length > 0 ? String(apiMessages[apiMessages.length - 1].id) : null);
+    };
+
+    try {
+      // Paint what the cache has, then catch up with the server
+      const cached = await api.getCachedHistory(channel.id, 50).catch(() => null);
+      if (activeChannelIdRef.current !== channel.id) {
+        return;
+      }
+      if (cached && cached.messages.length > 0) {
+        await showPage(cached);
+      }
+
+      const page = await api.getHistory(channel.id, { limit: 50 });
+      if (activeChannelIdRef.current !== channel.id) {
+        return;
+      }
+      await showPage(page);
     } catch (err: unknown) {
       const message = err instanceof Error ? err.message : 'unknown error';
 
@@ -117,8 +169,8 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
         return;
       }
 
-      setMessages([
-        systemMsg,
+      setMessages((prev) => [
+        ...(prev.length > 0 ? prev : [systemMsg]),
         createChatMessage('', `system: Failed to load messages: ${message}`, 'system'),
       ]);
     }
@@ -268,7 +320,7 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
     setIsLoadingMore(true);
     try {
       const { messages: apiMessages, hasMore: moreRemaining } =
-        await api.getMessages(channelId, { limit: 50, before: oldestMessageApiId });
+        await api.getHistory(channelId, { limit: 50, before: oldestMessageApiId });
 
       if (activeChannelIdRef.current !== channelId) return;
 
@@ -287,6 +339,7 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
         type: message.type || 'user',
         timestamp: getTimestamp(message.createdAt),
         date: getDateStr(message.createdAt),
+        importedFrom: message.importedFrom ?? undefined,
       }));
 
       setMessages((prev) => {
@@ -311,19 +364,34 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
 
     const user = currentUserRef.current;
     const displayName = user ? `@${user.username}` : '@anon';
-    addMessage(displayName, text, 'user');
+    const message = createChatMessage(displayName, text, 'user');
+    setMessages((prev) => [...prev, message]);
+
+    // Rust queues it on disk and sends it once the socket is up
+    outbox.queueMessage(channelId, text).then((queued) => {
+      const item = earlyOutboxRef.current.get(queued.key) ?? queued;
+      earlyOutboxRef.current.delete(queued.key);
+      setMessages((prev) => prev.flatMap((existing) => {
+        if (existing.id !== message.id) return [existing];
+        if (item.status === 'discarded') return [];
+        if (item.status === 'sent') return [existing];
+        return [{ ...existing, outbox: { key: item.key, status: item.status, error: item.error } }];
+      }));
+    }).catch((err: Error) => {
+      addMessage('', `system: Failed to send: ${err.message}`, 'system');
+    });
+  }, [addMessage]);
 
-    if (isWsConnected()) {
-      sendWs({
-        type: 'message',
-        channelId,
-        content: text,
-      });
-    } else {
-      api.sendMessage(channelId, text).catch((err: Error) => {
-        addMessage('', `system: Failed to send: ${err.message}`, 'system');
-      });
-    }
+  const retryChatMessage = useCallback((key: string) => {
+    outbox.retryMessage(key).catch((err: Error) => {
+      addMessage('', `system: Failed to retry: ${err.message}`, 'system');
+    });
+  }, [addMessage]);
+
+  const discardChatMessage = useCallback((key: string) => {
+    outbox.discardMessage(key).catch((err: Error) => {
+      addMessage('', `system: Failed to discard: ${err.message}`, 'system');
+    });
   }, [addMessage]);
 
   return {
@@ -338,6 +406,8 @@ export function useChatState({ currentUser, displayMode, setSidebarOpen }: UseCh
     setChannelMembers,
     myRoleInChannel,
     messages,
+    retryChatMessage,
+    discardChatMessage,
     addMessage,
     clearMessages,
     hasMore,
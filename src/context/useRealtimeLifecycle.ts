@@ -1,8 +1,11 @@
 import { useCallback, useEffect, useRef } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import type { Dispatch, MutableRefObject, SetStateAction } from 'react';
 import type { Channel, User } from '../lib/api';
 import * as api from '../lib/api';
 import { connectWs, disconnectWs, isWsConnected, onWs, sendWs } from '../lib/ws';
+import { reportConnectionStatus, type ConnectionState } from '../lib/connection';
+import * as presenceApi from '../lib/presence';
 import { getTimestamp, type MessageType } from './chatUtils';
 
 interface UseRealtimeLifecycleOptions {
@@ -34,9 +37,23 @@ export function useRealtimeLifecycle({
 }: UseRealtimeLifecycleOptions) {
   const wsSetupRef = useRef(false);
   const unsubscribeHandlersRef = useRef<Array<() => void>>([]);
+  // Last unrecoverable connect error shown, so retries do not repeat it
+  const lastWsErrorRef = useRef<string | undefined>(undefined);
   const isMinimizedRef = useRef(isMinimized);
   isMinimizedRef.current = isMinimized;
 
+  // What the tray shows; reported to Rust whenever any part changes.
+  const connectionRef = useRef<{ state: ConnectionState; lastMessageAt: string | null }>({
+    state: 'offline',
+    lastMessageAt: null,
+  });
+  const reportConnection = useCallback(() => {
+    reportConnectionStatus({
+      ...connectionRef.current,
+      username: currentUserRef.current?.username ?? null,
+    });
+  }, [currentUserRef]);
+
   const initApp = useCallback(async () => {
     if (!wsSetupRef.current) {
       wsSetupRef.current = true;
@@ -44,19 +61,23 @@ export function useRealtimeLifecycle({
       unsubscribeHandlersRef.current = [
         onWs('message', (msg) => {
           const data = msg.data;
-          const channelId = data.channelId as number | string;
-          const senderId = data.senderId as number;
-          const senderUsername = (data.senderUsername as string) || 'unknown';
-          const content = (data.content as string) || '';
-          const ts = (data.timestamp as string) || (data.createdAt as string);
+          const channelId = data.channelId;
+          const senderId = data.senderId;
+          const senderUsername = data.senderUsername || 'unknown';
+          const content = data.content || '';
+          const ts = data.timestamp || data.createdAt;
           const user = currentUserRef.current;
           const isOwnMessage = !!user && senderId === user.id;
+          if (!isOwnMessage) {
+            connectionRef.current.lastMessageAt = ts || new Date().toISOString();
+            reportConnection();
+          }
           const isVisibleActiveChannel = !isMinimizedRef.current && channelId === activeChannelIdRef.current;
 
           if (isVisibleActiveChannel && !isOwnMessage) {
             const timeStr = ts ? getTimestamp(ts) : undefined;
-            addMessage(senderUsername, content, (data.messageType as MessageType) || 'user', timeStr);
-            const messageId = data.id as number;
+            addMessage(senderUsername, content, data.messageType || 'user', timeStr);
+            const messageId = data.id;
 
             if (isWsConnected()) {
               sendWs({ type: 'mark-read', channelId, messageId });
@@ -92,9 +113,19 @@ export function useRealtimeLifecycle({
         }),
         onWs('presence', (msg) => {
           const data = msg.data;
+          connectionRef.current.state = data.status;
+          reportConnection();
+          // Shown once; every retry fails the same way until the profile changes
+          if (data.error && data.error !== lastWsErrorRef.current) {
+            addMessage('', `system: cannot connect: ${data.error}`, 'system');
+          }
+          lastWsErrorRef.current = data.error;
           if (data.status === 'connected') {
             addMessage('', 'system: connected to mesh', 'system');
-            api.updateMe({ status: isMinimizedRef.current ? 'idle' : 'online' }).catch(() => {});
+            // Push the status again; the server forgets it with the connection
+            presenceApi.reportAutoPresence(isMinimizedRef.current ? 'idle' : 'online')
+              .then(() => presenceApi.pushPresence())
+              .catch(() => {});
           }
         }),
         onWs('connected', () => {
@@ -107,7 +138,7 @@ export function useRealtimeLifecycle({
           if (data.userId) {
             setAllUsers((prev) =>
               prev.map((user) =>
-                user.id === (data.userId as number) ? { ...user, status: data.status as string } : user
+                user.id === data.userId ? { ...user, status: data.status } : user
               )
             );
           }
@@ -115,7 +146,7 @@ export function useRealtimeLifecycle({
         onWs('user-joined', (msg) => {
           const data = msg.data;
           if (data.channelId === activeChannelIdRef.current) {
-            const username = (data.user as { username: string })?.username || (data.username as string) || 'unknown';
+            const username = data.user?.username || data.username || 'unknown';
             addMessage('', `system: @${username} joined`, 'system');
           }
         }),
@@ -140,45 +171,45 @@ export function useRealtimeLifecycle({
     currentUserRef,
     loadChannels,
     loadUsers,
+    reportConnection,
     setAllUsers,
     setChannels,
   ]);
 
+  useEffect(() => {
+    if (isAuthenticated) return;
+    connectionRef.current = { state: 'signed-out', lastMessageAt: null };
+    reportConnection();
+  }, [isAuthenticated, reportConnection]);
+
   useEffect(() => {
     const handleBlur = () => {
       if (currentUserRef.current) {
-        api.updateMe({ status: 'idle' }).catch(() => {});
+        presenceApi.reportAutoPresence('idle').catch(() => {});
       }
     };
 
     const handleFocus = () => {
       if (currentUserRef.current && !isMinimized) {
-        api.updateMe({ status: 'online' }).catch(() => {});
-      }
-    };
-
-    const handleUnload = () => {
-      if (currentUserRef.current) {
-        api.updateMe({ status: 'offline' }).catch(() => {});
+        presenceApi.reportAutoPresence('online').catch(() => {});
       }
-      disconnectWs();
     };
 
+    // No offline status or disconnect on unload: the sockets live in Rust and
+    // keep running while the app reloads as another account.
     window.addEventListener('blur', handleBlur);
     window.addEventListener('focus', handleFocus);
-    window.addEventListener('beforeunload', handleUnload);
 
     return () => {
       window.removeEventListener('blur', handleBlur);
       window.removeEventListener('focus', handleFocus);
-      window.removeEventListener('beforeunload', handleUnload);
     };
   }, [currentUserRef, isMinimized]);
 
   useEffect(() => {
     if (!currentUserRef.current) return;
 
-    api.updateMe({ status: isMinimized ? 'idle' : 'online' }).catch(() => {});
+    presenceApi.reportAutoPresence(isMinimized ? 'idle' : 'online').catch(() => {});
 
     if (!isMinimized) {
       reloadActiveChannel({ markRead: true }).catch(() => {});
@@ -195,6 +226,17 @@ export function useRealtimeLifecycle({
     return () => clearInterval(interval);
   }, [isAuthenticated, loadUsers]);
 
+  // Rust reconnects after suspend or a network change (system_watch.rs); who
+  // is online has likely changed meanwhile.
+  useEffect(() => {
+    if (!isAuthenticated) return;
+    let unlisten: (() => void) | undefined;
+    listen('system-resumed', () => {
+      loadUsers();
+    }).then((fn) => { unlisten = fn; });
+    return () => { unlisten?.(); };
+  }, [isAuthenticated, loadUsers]);
+
   useEffect(() => {
     return () => {
       unsubscribeHandlersRef.current.forEach((unsubscribe) => unsubscribe());
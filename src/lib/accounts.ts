@@ -0,0 +1,53 @@
+// ── Accounts ──
+// Rust keeps a session per signed-in account (src-tauri/src/accounts.rs), each
+// with its own REST client and socket, all connected at once. The webview shows
+// the active one; switching from the tray or the sidebar emits `account-switched`
+// and the app reloads as that account. Tokens stay in Rust.
+
+import { invoke } from '@tauri-apps/api/core';
+import type { ConnectionProfile } from './profiles';
+import type { User } from './protocol';
+
+export interface AccountView {
+  // "<profile>:<user id>"
+  id: string;
+  profile: ConnectionProfile;
+  user: User;
+  unread: number;
+  connected: boolean;
+}
+
+export interface AccountsView {
+  accounts: AccountView[];
+  active: string | null;
+}
+
+export function listAccounts(): Promise<AccountsView> {
+  return invoke<AccountsView>('list_accounts');
+}
+
+// Sign in with a token issued elsewhere, e.g. a bot's, on the named profile;
+// the same user again just refreshes the token. `activate` makes it active
+// without a reload. Rust keeps the token in the keyring.
+export function addAccount(profile: string, token: string, activate: boolean): Promise<AccountView> {
+  return invoke<AccountView>('add_account', { profile, token, activate });
+}
+
+export interface MigrationView {
+  accounts: AccountsView;
+  // localStorage keys whose tokens Rust took over or the server rejected
+  settled: string[];
+}
+
+export function migrateTokens(tokens: { key: string; profile: string; token: string }[]): Promise<MigrationView> {
+  return invoke<MigrationView>('migrate_tokens', { tokens });
+}
+
+export function removeAccount(id: string): Promise<AccountsView> {
+  return invoke<AccountsView>('remove_account', { id });
+}
+
+// null goes to the sign-in screen, to add another account.
+export function switchAccount(id: string | null): Promise<AccountsView> {
+  return invoke<AccountsView>('switch_account', { id });
+}
@@ -1,376 +1,317 @@
 // ── API Client for close-chat ──
-// Base URL: https://api.t-bash.space
+// Every request is made by Rust (src-tauri/src/api) as the active account, so
+// the webview never holds a token. Call loadSession() first.
+
+import { invoke, type InvokeArgs } from '@tauri-apps/api/core';
+import { listAccounts, migrateTokens, removeAccount, type AccountView, type AccountsView } from './accounts';
+import type { CommandError } from './window';
+
+let ACCOUNT: AccountView | null = null;
+
+// Find the active account once Rust has signed the saved ones back in, moving
+// any tokens an older version left in localStorage over first.
+export async function loadSession(): Promise<void> {
+  try {
+    let view = await listAccounts();
+    const legacy = legacyTokens();
+    if (legacy.length > 0) {
+      const migrated = await migrateTokens(legacy);
+      view = migrated.accounts;
+      clearLegacyTokens(legacy, migrated.settled);
+    }
+    ACCOUNT = view.accounts.find((account) => account.id === view.active) ?? null;
+  } catch (err) {
+    console.error("failed to load accounts", err);
+  }
+}
 
-const BASE_URL = "https://api.t-bash.space";
+export function currentAccountId(): string | null {
+  return ACCOUNT?.id ?? null;
+}
 
 // ── Types ──
-export interface User {
-  id: number;
-  username: string;
-  email?: string;
-  status: string;
-  isBot?: boolean;
-  lastSeen?: string;
-  createdAt?: string;
-}
-
-export interface AuthResponse {
-  user: User;
-  token: string;
+// Wire types are generated from src-tauri/protocol; see protocol.ts.
+import type {
+  Channel,
+  ChannelKind,
+  ChannelMember,
+  DmChannel,
+  DmResult,
+  Invite,
+  InviteOptions,
+  Message,
+  MessageKind,
+  MessagePage,
+  MessagesQuery,
+  UpdateMe,
+  User,
+} from './protocol';
+
+export type {
+  AuthResponse,
+  Channel,
+  ChannelLastMessage,
+  ChannelMember,
+  DmChannel,
+  DmRecipient,
+  DmResult,
+  Invite,
+  Message,
+  MessagePage,
+  User,
+} from './protocol';
+
+// Rust rejects with a CommandError; callers expect an Error with the message.
+// `kind` is kept for the few that branch on it, e.g. "unauthorized".
+async function call<T>(cmd: string, args?: InvokeArgs): Promise<T> {
+  try {
+    return await invoke<T>(cmd, args);
+  } catch (err) {
+    const { kind, message } = err as CommandError;
+    throw Object.assign(new Error(message ?? String(err)), { kind });
+  }
 }
 
-export interface ChannelLastMessage {
-  content: string;
-  senderId: number;
-  senderUsername: string;
-  createdAt: string;
-}
+// ── Session ──
 
-export interface Channel {
-  id: number;
-  name: string;
-  type: "channel" | "dm";
-  createdBy?: number;
-  createdAt?: string;
-  lastMessage?: ChannelLastMessage | null;
-  unreadCount?: number;
-  members?: number[];
-  role?: "admin" | "member";
-  recipient?: DmRecipient;
-}
-
-export interface DmRecipient {
-  id: number;
-  username: string;
-  status: string;
-}
-
-export interface DmChannel {
-  id: number;
-  name: string;
-  type: "dm";
-  recipient: DmRecipient;
-  lastMessage: ChannelLastMessage | null;
-  unreadCount: number;
-}
-
-export interface ChannelMember {
-  id: number;
-  username: string;
-  status: string;
-  isBot?: boolean;
-  role: "admin" | "member";
-  joinedAt?: string;
-}
-
-export interface Invite {
-  id: number;
-  code: string;
-  channelId?: number;
-  createdBy: { id: number; username: string } | number;
-  maxUses: number | null;
-  uses: number;
-  expiresAt: string | null;
-  isActive?: boolean;
-  createdAt: string;
-}
+// Tokens used to live here: one per profile (the default one under the bare
+// key), then a list of accounts. Handed to Rust once and removed.
+const LEGACY_TOKEN_KEY = "closechat_token";
+const LEGACY_ACCOUNTS_KEY = "closechat_accounts";
 
-export interface Message {
-  id: number;
-  channelId: number;
-  senderId: number;
-  senderUsername: string;
-  content: string | null;
-  type: "user" | "bot" | "system";
-  imageUrl?: string | null;
-  createdAt: string;
+interface LegacyToken {
+  key: string;
+  profile: string;
+  token: string;
 }
 
-export interface ApiError {
-  error: string;
+function legacyTokens(): LegacyToken[] {
+  const found: LegacyToken[] = [];
+  for (let i = 0; i < localStorage.length; i++) {
+    const key = localStorage.key(i);
+    if (!key || (key !== LEGACY_TOKEN_KEY && !key.startsWith(`${LEGACY_TOKEN_KEY}:`))) continue;
+    const token = localStorage.getItem(key);
+    const profile = key === LEGACY_TOKEN_KEY ? "default" : key.slice(LEGACY_TOKEN_KEY.length + 1);
+    if (token) found.push({ key, profile, token });
+  }
+  try {
+    const stored = JSON.parse(localStorage.getItem(LEGACY_ACCOUNTS_KEY) ?? "[]") as { profile: string; token: string }[];
+    stored.forEach(({ profile, token }) => found.push({ key: LEGACY_ACCOUNTS_KEY, profile, token }));
+  } catch {
+    localStorage.removeItem(LEGACY_ACCOUNTS_KEY);
+  }
+  return found;
 }
 
-// ── Token management ──
-let _token: string | null = null;
+// Keys whose tokens Rust took or the server rejected; the rest wait for a retry.
+function clearLegacyTokens(legacy: LegacyToken[], settled: string[]): void {
+  for (const key of new Set(legacy.map((entry) => entry.key))) {
+    const pending = legacy.filter((entry) => entry.key === key).length;
+    if (settled.filter((k) => k === key).length === pending) localStorage.removeItem(key);
+  }
+}
 
-export function getToken(): string | null {
-  if (_token) return _token;
-  _token = localStorage.getItem("closechat_token");
-  return _token;
+export function hasSession(): boolean {
+  return ACCOUNT !== null;
 }
 
-export function setToken(token: string): void {
-  _token = token;
-  localStorage.setItem("closechat_token", token);
+// Sign an account out for good. For the active one, Rust switches to the next
+// account or the sign-in screen and the app reloads.
+export function forgetAccount(id: string): Promise<AccountsView> {
+  return removeAccount(id);
 }
 
 export function clearToken(): void {
-  _token = null;
-  localStorage.removeItem("closechat_token");
-}
-
-// ── Fetch wrapper ──
-async function apiFetch<T>(
-  path: string,
-  options: RequestInit = {},
-): Promise<T> {
-  const token = getToken();
-  const headers: Record<string, string> = {
-    "Content-Type": "application/json",
-    ...((options.headers as Record<string, string>) || {}),
-  };
-
-  if (token) {
-    headers["Authorization"] = `Bearer ${token}`;
-  }
-
-  const res = await fetch(`${BASE_URL}${path}`, {
-    ...options,
-    headers,
-  });
-
-  if (!res.ok) {
-    let errMsg = `HTTP ${res.status}`;
-    try {
-      const body = (await res.json()) as ApiError;
-      if (body.error) errMsg = body.error;
-    } catch {
-      /* ignore parse errors */
-    }
-    throw new Error(errMsg);
-  }
-
-  // Some endpoints may return empty body (204 etc)
-  const text = await res.text();
-  if (!text) return {} as T;
-  return JSON.parse(text) as T;
+  if (!ACCOUNT) return;
+  const id = ACCOUNT.id;
+  ACCOUNT = null;
+  removeAccount(id).catch(console.error);
 }
 
 // ══════════════════════════════════════
 // Auth endpoints
 // ══════════════════════════════════════
 
+// Both sign in on the selected profile and make the new account active.
 export async function signup(
   username: string,
   email: string,
   password: string,
-): Promise<AuthResponse> {
-  const data = await apiFetch<AuthResponse>("/api/auth/signup", {
-    method: "POST",
-    body: JSON.stringify({ username, email, password }),
-  });
-  setToken(data.token);
-  return data;
+): Promise<AccountView> {
+  ACCOUNT = await call<AccountView>("api_signup", { username, email, password });
+  return ACCOUNT;
 }
 
 export async function login(
   username: string,
   password: string,
-): Promise<AuthResponse> {
-  const data = await apiFetch<AuthResponse>("/api/auth/login", {
-    method: "POST",
-    body: JSON.stringify({ username, password }),
-  });
-  setToken(data.token);
-  return data;
+): Promise<AccountView> {
+  ACCOUNT = await call<AccountView>("api_login", { username, password });
+  return ACCOUNT;
 }
 
-export async function verifyToken(): Promise<User> {
-  const data = await apiFetch<{ user: User }>("/api/auth/verify");
-  return data.user;
+export function verifyToken(): Promise<User> {
+  return call<User>("api_verify_token");
 }
 
 // ══════════════════════════════════════
 // Users endpoints
 // ══════════════════════════════════════
 
-export async function listUsers(): Promise<User[]> {
-  const data = await apiFetch<{ users: User[]; onlineCount: number }>("/api/users/");
-  return data.users || [];
+export function listUsers(): Promise<User[]> {
+  return call<User[]>("api_list_users");
 }
 
-export async function getMe(): Promise<User> {
-  const data = await apiFetch<{ user: User }>("/api/users/me");
-  return data.user;
+export function getMe(): Promise<User> {
+  return call<User>("api_get_me");
 }
 
-export async function updateMe(opts: {
-  username?: string;
-  status?: "online" | "idle" | "offline";
-}): Promise<User> {
-  const data = await apiFetch<{ user: User }>("/api/users/me", {
-    method: "PATCH",
-    body: JSON.stringify(opts),
-  });
-  return data.user;
+export function updateMe(update: UpdateMe): Promise<User> {
+  return call<User>("api_update_me", { update });
 }
 
-export async function getUserById(id: number | string): Promise<User> {
-  const data = await apiFetch<{ user: User }>(`/api/users/${id}`);
-  return data.user;
+export function getUserById(id: number | string): Promise<User> {
+  return call<User>("api_get_user", { id: Number(id) });
 }
 
-export async function searchUsers(query: string): Promise<User[]> {
-  const params = new URLSearchParams({ q: query });
-  const data = await apiFetch<{ users: User[] }>(`/api/users/search?${params.toString()}`);
-  return data.users || [];
+export function searchUsers(query: string): Promise<User[]> {
+  return call<User[]>("api_search_users", { query });
 }
 
 // ══════════════════════════════════════
 // Channels endpoints
 // ══════════════════════════════════════
 
-export async function listChannels(): Promise<Channel[]> {
-  const data = await apiFetch<{ channels: Channel[] }>("/api/channels/");
-  return data.channels || [];
+export function listChannels(): Promise<Channel[]> {
+  return call<Channel[]>("api_list_channels");
 }
 
-export async function createChannel(
+export function createChannel(
   name: string,
-  type: "channel" | "dm",
+  kind: ChannelKind,
   members?: number[],
 ): Promise<Channel> {
-  const data = await apiFetch<{ channel: Channel }>("/api/channels/", {
-    method: "POST",
-    body: JSON.stringify({ name, type, members }),
-  });
-  return data.channel;
+  return call<Channel>("api_create_channel", { name, kind, members });
 }
 
-export async function getChannel(id: number | string): Promise<Channel> {
-  const data = await apiFetch<{ channel: Channel }>(`/api/channels/${id}`);
-  return data.channel;
+export function getChannel(id: number | string): Promise<Channel> {
+  return call<Channel>("api_get_channel", { id: Number(id) });
 }
 
-export async function joinChannel(id: number | string): Promise<void> {
-  await apiFetch<void>(`/api/channels/${id}/join`, { method: "POST" });
+export function joinChannel(id: number | string): Promise<void> {
+  return call<void>("api_join_channel", { id: Number(id) });
 }
 
-export async function leaveChannel(id: number | string): Promise<void> {
-  await apiFetch<void>(`/api/channels/${id}/leave`, { method: "POST" });
+export function leaveChannel(id: number | string): Promise<void> {
+  return call<void>("api_leave_channel", { id: Number(id) });
 }
 
-export async function getChannelMembers(
-  id: number | string,
-): Promise<ChannelMember[]> {
-  const data = await apiFetch<{ members: ChannelMember[] }>(`/api/channels/${id}/members`);
-  return data.members || [];
+export function getChannelMembers(id: number | string): Promise<ChannelMember[]> {
+  return call<ChannelMember[]>("api_channel_members", { id: Number(id) });
 }
 
 // ── Join by invite code ──
-export async function joinByInviteCode(code: string): Promise<Channel> {
-  const data = await apiFetch<{ channel: Channel }>(`/api/channels/join/invite/${encodeURIComponent(code)}`, {
-    method: "POST",
-  });
-  return data.channel;
+export function joinByInviteCode(code: string): Promise<Channel> {
+  return call<Channel>("api_join_by_invite_code", { code });
 }
 
 // ── Admin: add member ──
-export async function addMember(
-  channelId: number | string,
-  userId: number,
-): Promise<void> {
-  await apiFetch<void>(`/api/channels/${channelId}/members`, {
-    method: "POST",
-    body: JSON.stringify({ userId }),
-  });
+export function addMember(channelId: number | string, userId: number): Promise<void> {
+  return call<void>("api_add_member", { channelId: Number(channelId), userId });
 }
 
 // ── Admin: remove member ──
-export async function removeMember(
-  channelId: number | string,
-  userId: number | string,
-): Promise<void> {
-  await apiFetch<void>(`/api/channels/${channelId}/members/${userId}`, {
-    method: "DELETE",
-  });
+export function removeMember(channelId: number | string, userId: number | string): Promise<void> {
+  return call<void>("api_remove_member", { channelId: Number(channelId), userId: Number(userId) });
 }
 
 // ── Admin: create invite ──
-export async function createInvite(
-  channelId: number | string,
-  options?: { maxUses?: number; expiresInHours?: number },
-): Promise<Invite> {
-  const data = await apiFetch<{ invite: Invite }>(`/api/channels/${channelId}/invites`, {
-    method: "POST",
-    body: JSON.stringify(options || {}),
-  });
-  return data.invite;
+export function createInvite(channelId: number | string, options?: InviteOptions): Promise<Invite> {
+  return call<Invite>("api_create_invite", { channelId: Number(channelId), options });
 }
 
 // ── Admin: list invites ──
-export async function listInvites(
-  channelId: number | string,
-): Promise<Invite[]> {
-  const data = await apiFetch<{ invites: Invite[] }>(`/api/channels/${channelId}/invites`);
-  return data.invites || [];
+export function listInvites(channelId: number | string): Promise<Invite[]> {
+  return call<Invite[]>("api_list_invites", { channelId: Number(channelId) });
 }
 
 // ── Admin: revoke invite ──
-export async function revokeInvite(
-  channelId: number | string,
-  inviteId: number | string,
-): Promise<void> {
-  await apiFetch<void>(`/api/channels/${channelId}/invites/${inviteId}`, {
-    method: "DELETE",
-  });
+export function revokeInvite(channelId: number | string, inviteId: number | string): Promise<void> {
+  return call<void>("api_revoke_invite", { channelId: Number(channelId), inviteId: Number(inviteId) });
 }
 
 // ══════════════════════════════════════
 // Messages endpoints
 // ══════════════════════════════════════
 
-export async function getMessages(
-  channelId: number | string,
-  options?: { limit?: number; before?: string },
-): Promise<{ messages: Message[]; hasMore: boolean }> {
-  const params = new URLSearchParams();
-  if (options?.limit) params.set("limit", String(options.limit));
-  if (options?.before) params.set("before", options.before);
-  const qs = params.toString();
-  const path = `/api/channels/${channelId}/messages${qs ? "?" + qs : ""}`;
-  const data = await apiFetch<{ messages: Message[]; hasMore: boolean }>(path);
-  return { messages: data.messages || [], hasMore: data.hasMore ?? false };
-}
-
-export async function sendMessage(
+export function getMessages(channelId: number | string, query?: MessagesQuery): Promise<MessagePage> {
+  return call<MessagePage>("api_get_messages", { channelId: Number(channelId), query });
+}
+
+// A message from the Rust cache, which also holds imported history.
+export interface CachedMessage extends Message {
+  // Set on history imported from another chat, e.g. "slack"
+  importedFrom?: string;
+}
+
+export interface HistoryPage {
+  messages: CachedMessage[];
+  hasMore: boolean;
+}
+
+// History through the Rust message cache: the newest page without `before`,
+// else an older one. Served from disk where possible, and when offline.
+export function getHistory(channelId: number | string, query?: MessagesQuery): Promise<HistoryPage> {
+  return call<HistoryPage>("history_page", { channelId: Number(channelId), query });
+}
+
+// The newest cached page, without touching the network; empty if none.
+export function getCachedHistory(channelId: number | string, limit?: number): Promise<HistoryPage> {
+  return call<HistoryPage>("history_cached", { channelId: Number(channelId), limit });
+}
+
+export interface SearchHit {
+  channelId: number;
+  messageId: number;
+  channelName?: string;
+  senderUsername: string;
+  createdAt: string;
+  // Matches are wrapped in SEARCH_MATCH_START / SEARCH_MATCH_END
+  snippet: string;
+  rank: number;
+  // Set on history imported from another chat
+  importedFrom?: string;
+}
+
+export const SEARCH_MATCH_START = '\u0002';
+export const SEARCH_MATCH_END = '\u0003';
+
+// Search cached history, e.g. `from:alice in:#deploys "rollback" before:2026-09-01`.
+// Only messages this device has loaded or received are found.
+export function searchMessages(query: string, limit?: number): Promise<SearchHit[]> {
+  return call<SearchHit[]>("search_messages", { query, limit });
+}
+
+export function sendMessage(
   channelId: number | string,
   content: string,
-  type?: "user" | "bot" | "system",
+  kind?: MessageKind,
 ): Promise<Message> {
-  const body: Record<string, string> = { content };
-  if (type) body.type = type;
-  const data = await apiFetch<{ message: Message }>(`/api/channels/${channelId}/messages`, {
-    method: "POST",
-    body: JSON.stringify(body),
-  });
-  return data.message;
+  return call<Message>("api_send_message", { channelId: Number(channelId), content, kind });
 }
 
-export async function markChannelRead(id: number | string): Promise<void> {
-  await apiFetch<void>(`/api/channels/${id}/read`, { method: "POST" });
+export function markChannelRead(id: number | string): Promise<void> {
+  return call<void>("api_mark_channel_read", { channelId: Number(id) });
 }
 
 // ══════════════════════════════════════
 // DM endpoints
 // ══════════════════════════════════════
 
-export async function getOrCreateDm(userId: number): Promise<{ channel: Channel; created: boolean }> {
-  const data = await apiFetch<{ channel: Channel; created: boolean }>(`/api/dm/${userId}`, {
-    method: "POST",
-  });
-  return data;
-}
-
-export async function listDms(): Promise<DmChannel[]> {
-  const data = await apiFetch<{ dms: DmChannel[] }>("/api/dm/");
-  return data.dms || [];
+export function getOrCreateDm(userId: number): Promise<DmResult> {
+  return call<DmResult>("api_get_or_create_dm", { userId });
 }
 
-// ── WebSocket URL helper ──
-export function getWebSocketUrl(): string {
-  const token = getToken();
-  // Convert https to wss
-  const wsBase = BASE_URL.replace(/^http/, "ws");
-  return `${wsBase}/ws?token=${encodeURIComponent(token || "")}`;
+export function listDms(): Promise<DmChannel[]> {
+  return call<DmChannel[]>("api_list_dms");
 }
@@ -0,0 +1,17 @@
+// ── Connection status for the tray ──
+// Rust redraws the tray icon and tooltip from these reports.
+
+import { emit } from '@tauri-apps/api/event';
+
+export type ConnectionState = 'connected' | 'reconnecting' | 'offline' | 'signed-out';
+
+export interface ConnectionStatus {
+  state: ConnectionState;
+  username: string | null;
+  // ISO timestamp of the last message received
+  lastMessageAt: string | null;
+}
+
+export function reportConnectionStatus(status: ConnectionStatus): void {
+  emit('connection-status', status).catch(console.error);
+}
@@ -0,0 +1,33 @@
+// ── Channel export ──
+// Rust pages through the channel's history (cache first, then the server),
+// asks where to save and writes the transcript (src-tauri/src/export.rs).
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+export type ExportFormat = 'json' | 'markdown' | 'html' | 'log';
+
+export const EXPORT_FORMATS: ExportFormat[] = ['json', 'markdown', 'html', 'log'];
+
+// Inclusive days, "YYYY-MM-DD"; either end may be left open.
+export interface DateRange {
+  from?: string;
+  to?: string;
+}
+
+export interface ExportProgress {
+  channelId: number;
+  // Messages in range collected so far
+  messages: number;
+  done: boolean;
+  path?: string;
+}
+
+// Resolves to the saved file, or null if the save dialog was cancelled.
+export function exportChannel(channelId: number | string, format: ExportFormat, range?: DateRange): Promise<string | null> {
+  return invoke<string | null>('export_channel', { channelId: Number(channelId), format, range });
+}
+
+export function onExportProgress(handler: (progress: ExportProgress) => void): Promise<UnlistenFn> {
+  return listen<ExportProgress>('export-progress', (event) => handler(event.payload));
+}
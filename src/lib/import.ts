@@ -0,0 +1,38 @@
+// ── History import ──
+// Rust reads Slack and Discord export archives into the local message cache
+// (src-tauri/src/import.rs). Imported messages are only on this device.
+
+import { invoke } from '@tauri-apps/api/core';
+
+export type ImportSource = 'slack' | 'discord';
+
+// Archive user name -> closechat username, archive channel -> channel id.
+// Saved between runs; channels left out are skipped.
+export interface ImportMapping {
+  users: Record<string, string>;
+  channels: Record<string, number>;
+}
+
+export interface ImportReport {
+  source: ImportSource;
+  dryRun: boolean;
+  // Messages stored, or that would be on a dry run
+  imported: number;
+  // Messages in unmapped channels
+  skipped: number;
+  channels: { name: string; messages: number; channelId?: number | null }[];
+  users: { name: string; messages: number; username?: string | null }[];
+  // The mapping used, with suggestions for names closechat also has
+  mapping: ImportMapping;
+}
+
+// Resolves to the picked archive, or null if the dialog was cancelled.
+export function pickImportArchive(): Promise<string | null> {
+  return invoke<string | null>('import_pick_archive');
+}
+
+// `mapping` is used instead of the saved one, and replaces it unless this is a
+// dry run. A dry run reports without storing anything.
+export function importHistory(path: string, dryRun: boolean, mapping?: ImportMapping): Promise<ImportReport> {
+  return invoke<ImportReport>('import_history', { path, dryRun, mapping });
+}
@@ -0,0 +1,47 @@
+// ── Outbox ──
+// Sends go through a queue in Rust (src-tauri/src/outbox.rs) that survives
+// restarts and dropped connections. Each message gets an idempotency key, is
+// posted in order once the account is connected, and reports its progress as
+// `outbox-changed`.
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { Message, MessageKind } from './protocol';
+
+export type OutboxStatus = 'pending' | 'sending' | 'sent' | 'failed' | 'discarded';
+
+export interface OutboxItem {
+  // Idempotency key
+  key: string;
+  account: string;
+  channelId: number;
+  content: string;
+  kind?: MessageKind;
+  status: OutboxStatus;
+  error?: string;
+  createdAt: string;
+  // The server's copy, once sent
+  message?: Message;
+}
+
+export function queueMessage(channelId: number | string, content: string, kind?: MessageKind): Promise<OutboxItem> {
+  return invoke<OutboxItem>('outbox_send', { channelId: Number(channelId), content, kind });
+}
+
+// Pending and failed messages of the active account, oldest first.
+export function listOutbox(): Promise<OutboxItem[]> {
+  return invoke<OutboxItem[]>('outbox_list');
+}
+
+export function retryMessage(key: string): Promise<OutboxItem> {
+  return invoke<OutboxItem>('outbox_retry', { key });
+}
+
+// Rejected with kind "busy" once the message is being sent.
+export function discardMessage(key: string): Promise<void> {
+  return invoke<void>('outbox_discard', { key });
+}
+
+export function onOutboxChanged(handler: (item: OutboxItem) => void): Promise<UnlistenFn> {
+  return listen<OutboxItem>('outbox-changed', (event) => handler(event.payload));
+}
@@ -0,0 +1,35 @@
+// ── Presence commands ──
+// Rust owns the status and sends it to the server: a choice pinned from the tray
+// wins over the focus-based Auto status the UI reports. Changes come back as
+// `presence-changed`.
+
+import { invoke } from '@tauri-apps/api/core';
+
+export type Presence = 'online' | 'idle' | 'dnd' | 'invisible';
+
+export type PresenceChoice = 'auto' | Presence;
+
+export interface PresenceView {
+  choice: PresenceChoice;
+  status: Presence;
+  // `status` as the server spells it
+  serverStatus: 'online' | 'idle' | 'dnd' | 'offline';
+}
+
+export function getPresence(): Promise<PresenceView> {
+  return invoke<PresenceView>('get_presence');
+}
+
+export function setPresence(choice: PresenceChoice): Promise<PresenceView> {
+  return invoke<PresenceView>('set_presence', { choice });
+}
+
+// What Auto resolves to; ignored by Rust while a status is pinned.
+export function reportAutoPresence(status: 'online' | 'idle'): Promise<PresenceView> {
+  return invoke<PresenceView>('report_auto_presence', { status });
+}
+
+// Send the current status to the server again, e.g. after reconnecting.
+export function pushPresence(): Promise<PresenceView> {
+  return invoke<PresenceView>('push_presence');
+}
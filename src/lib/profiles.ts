@@ -0,0 +1,56 @@
+// ── Connection profiles ──
+// Rust decides which server new sign-ins go to (settings, `--profile` or
+// `--server`). Switching makes the first account on that server active, if any;
+// Rust then emits `profile-changed` and the app reloads.
+
+import { invoke } from '@tauri-apps/api/core';
+
+export interface TlsOptions {
+  // Skip certificate checks, for self-signed self-hosted servers
+  acceptInvalidCerts: boolean;
+  // PEM files with extra roots to trust, e.g. an internal CA
+  caCertificates?: string[];
+  // sha256/<base64> public key hashes; the server's chain must contain one
+  pins?: string[];
+}
+
+// REST and WebSocket traffic go through this proxy; without a url, Rust
+// follows HTTPS_PROXY / ALL_PROXY / NO_PROXY.
+export interface ProxyOptions {
+  // http://host:port (CONNECT) or socks5://host:port (socks5h:// to resolve
+  // names at the proxy), optionally user:password@
+  url?: string;
+  // Comma-separated hosts to reach directly, like NO_PROXY
+  noProxy?: string;
+}
+
+export interface ConnectionProfile {
+  name: string;
+  baseUrl: string;
+  // Used as-is instead of ws(s)://<baseUrl>/ws
+  wsUrl?: string;
+  tls: TlsOptions;
+  proxy: ProxyOptions;
+}
+
+export interface ProfilesView {
+  profiles: ConnectionProfile[];
+  // Follows the active account. May be unsaved, e.g. one given with --server
+  active: ConnectionProfile;
+}
+
+export function getProfiles(): Promise<ProfilesView> {
+  return invoke<ProfilesView>('get_profiles');
+}
+
+export function saveProfile(profile: ConnectionProfile): Promise<ProfilesView> {
+  return invoke<ProfilesView>('save_profile', { profile });
+}
+
+export function deleteProfile(name: string): Promise<ProfilesView> {
+  return invoke<ProfilesView>('delete_profile', { name });
+}
+
+export function switchProfile(name: string): Promise<ConnectionProfile> {
+  return invoke<ConnectionProfile>('switch_profile', { name });
+}
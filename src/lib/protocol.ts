@@ -0,0 +1,65 @@
+// Generated from src-tauri/protocol by src-tauri/build.rs. Do not edit.
+
+export type User = { id: number, username: string, email?: string | null, status: string, isBot: boolean, lastSeen?: string | null, createdAt?: string | null, };
+
+export type AuthResponse = { user: User, token: string, };
+
+export type ChannelKind = "channel" | "dm";
+
+export type MemberRole = "admin" | "member";
+
+export type ChannelLastMessage = { content: string, senderId: number, senderUsername: string, createdAt: string, };
+
+export type DmRecipient = { id: number, username: string, status: string, };
+
+export type Channel = { id: number, name: string, type: ChannelKind, createdBy?: number | null, createdAt?: string | null, lastMessage: ChannelLastMessage | null, unreadCount: number, members?: Array<number> | null, role?: MemberRole | null, recipient?: DmRecipient | null, };
+
+export type DmChannel = { id: number, name: string, type: ChannelKind, recipient: DmRecipient, lastMessage: ChannelLastMessage | null, unreadCount: number, };
+
+export type ChannelMember = { id: number, username: string, status: string, isBot: boolean, role: MemberRole, joinedAt?: string | null, };
+
+export type InviteCreator = { id: number, username: string, } | number;
+
+export type Invite = { id: number, code: string, channelId?: number | null, createdBy: InviteCreator, maxUses: number | null, uses: number, expiresAt: string | null, isActive?: boolean | null, createdAt: string, };
+
+export type MessageKind = "user" | "bot" | "system";
+
+export type Message = { id: number, channelId: number, senderId: number, senderUsername: string, content: string | null, type: MessageKind, imageUrl?: string | null, createdAt: string, };
+
+export type MessagePage = { messages: Array<Message>, hasMore: boolean, };
+
+export type DmResult = { channel: Channel, created: boolean, };
+
+export type UpdateMe = { username?: string | null, status?: string | null, };
+
+export type InviteOptions = { maxUses?: number | null, expiresInHours?: number | null, };
+
+export type MessagesQuery = { limit?: number | null, 
+/**
+ * Cursor: only messages older than this one.
+ */
+before?: string | null, };
+
+export type SignupRequest = { username: string, email: string, password: string, };
+
+export type LoginRequest = { username: string, password: string, };
+
+export type CreateChannelRequest = { name: string, type: ChannelKind, 
+/**
+ * Initial members besides the creator.
+ */
+members?: Array<number> | null, };
+
+export type AddMemberRequest = { userId: number, };
+
+export type SendMessageRequest = { content: string, 
+/**
+ * Defaults to `user` on the server.
+ */
+type?: MessageKind | null, };
+
+export type FrameUser = { id: number | null, username: string, };
+
+export type IncomingFrame = { "type": "message", id: number, channelId: number, senderId: number, senderUsername: string | null, content: string | null, messageType: MessageKind | null, imageUrl: string | null, timestamp: string | null, createdAt: string | null, } | { "type": "connected", userId: number | null, username: string | null, } | { "type": "user-joined", channelId: number, user: FrameUser | null, username: string | null, } | { "type": "user-left", channelId: number, userId: number | null, } | { "type": "status-changed", userId: number, status: string, } | { "type": "user-typing", channelId: number, userId: number, username: string | null, } | { "type": "user-stopped-typing", channelId: number, userId: number, } | { "type": "joined-channel", channelId: number, } | { "type": "channel_update", channelId: number | null, } | { "type": "error", message: string | null, };
+
+export type OutgoingFrame = { "type": "message", channelId: number, content: string, } | { "type": "typing-start", channelId: number, } | { "type": "typing-stop", channelId: number, } | { "type": "join-channel", channelId: number, } | { "type": "leave-channel", channelId: number, } | { "type": "status-update", status: string, } | { "type": "mark-read", channelId: number, messageId: number, };
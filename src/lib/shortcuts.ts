@@ -0,0 +1,35 @@
+// ── Global shortcut commands ──
+// Rust registers the OS-level shortcuts from settings; actions that need the UI
+// come back as a `shortcut-triggered` event.
+
+import { invoke } from '@tauri-apps/api/core';
+
+export type ShortcutAction = 'toggle-mini' | 'show-hide' | 'focus-input' | 'next-unread' | 'toggle-dnd';
+
+// Accelerator strings such as "Ctrl+Shift+K", keyed by action.
+export type ShortcutBindings = Partial<Record<ShortcutAction, string>>;
+
+// A binding that could not be registered (invalid, duplicated or taken by another app).
+export interface ShortcutIssue {
+  action: ShortcutAction;
+  accelerator: string;
+  reason: string;
+}
+
+export interface ShortcutsView {
+  bindings: ShortcutBindings;
+  issues: ShortcutIssue[];
+}
+
+export function getShortcuts(): Promise<ShortcutsView> {
+  return invoke<ShortcutsView>('get_shortcuts');
+}
+
+// Pass an empty accelerator to unbind. Resolves with the issues left after re-registering.
+export function setShortcut(action: ShortcutAction, accelerator: string): Promise<ShortcutIssue[]> {
+  return invoke<ShortcutIssue[]>('set_shortcut', { action, accelerator });
+}
+
+export function describeIssue(issue: ShortcutIssue): string {
+  return `shortcut ${issue.accelerator} (${issue.action}) not active: ${issue.reason}`;
+}
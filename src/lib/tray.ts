@@ -0,0 +1,27 @@
+// ── Tray commands ──
+// Rust owns the tray menu; the frontend reports what it should show.
+
+import { invoke } from '@tauri-apps/api/core';
+import type { Channel } from './api';
+
+export interface UnreadChannel {
+  id: number;
+  name: string;
+  kind: 'channel' | 'dm';
+  count: number;
+}
+
+export function toUnreadChannels(channels: Channel[]): UnreadChannel[] {
+  return channels
+    .filter((ch) => (ch.unreadCount || 0) > 0)
+    .map((ch) => ({
+      id: ch.id,
+      name: ch.type === 'dm' && ch.recipient ? ch.recipient.username : ch.name,
+      kind: ch.type,
+      count: ch.unreadCount || 0,
+    }));
+}
+
+export function setUnreadChannels(channels: UnreadChannel[]): Promise<void> {
+  return invoke<void>('set_unread_channels', { channels });
+}
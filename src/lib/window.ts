@@ -0,0 +1,64 @@
+// ── Window control commands ──
+// Rust owns the window mode state machine; these wrappers request transitions
+// and reject with a CommandError when Rust refuses or fails.
+
+import { invoke } from '@tauri-apps/api/core';
+import type { DisplayMode } from '../context/chatUtils';
+
+export type WindowMode = DisplayMode | 'mini' | 'hidden';
+
+export type AlwaysOnTopPolicy = 'compact-and-mini' | 'mini-only' | 'always' | 'never';
+
+export type Anchor = 'top-left' | 'top-right' | 'bottom-left' | 'bottom-right' | 'free';
+
+export interface Margins {
+  x: number;
+  y: number;
+}
+
+export interface WindowSettings {
+  mini: { anchor: Anchor; margins: Margins; size: number };
+  compact: { anchor: Anchor; margins: Margins };
+  alwaysOnTop: AlwaysOnTopPolicy;
+}
+
+export interface WindowStateView {
+  mode: WindowMode;
+  displayMode: DisplayMode;
+  alwaysOnTop: AlwaysOnTopPolicy;
+}
+
+// Payload of the `window-mode-changed` event.
+export interface WindowModeChange {
+  from: WindowMode;
+  to: WindowMode;
+}
+
+export interface CommandError {
+  kind: string;
+  message: string;
+}
+
+export function getWindowState(): Promise<WindowStateView> {
+  return invoke<WindowStateView>('get_window_state');
+}
+
+export function setMode(mode: WindowMode): Promise<void> {
+  return invoke<void>('set_mode', { mode });
+}
+
+export function toggleMini(): Promise<WindowMode> {
+  return invoke<WindowMode>('toggle_mini');
+}
+
+export function setAlwaysOnTopPolicy(policy: AlwaysOnTopPolicy): Promise<void> {
+  return invoke<void>('set_always_on_top_policy', { policy });
+}
+
+export function getWindowSettings(): Promise<WindowSettings> {
+  return invoke<WindowSettings>('get_window_settings');
+}
+
+export function setWindowSettings(settings: WindowSettings): Promise<void> {
+  return invoke<void>('set_window_settings', { settings });
+}
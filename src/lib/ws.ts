@@ -1,101 +1,109 @@
 // ── WebSocket client for close-chat ──
-// Connects to wss://api.t-bash.space/ws?token=...
-
-import { getWebSocketUrl } from './api';
-
-export type WsMessageType =
-  | 'message'            // new chat message
-  | 'connected'          // server auth confirmed
-  | 'user-joined'        // user joined a channel
-  | 'user-left'          // user left a channel
-  | 'status-changed'     // user status change (online/idle/offline)
-  | 'user-typing'        // user is typing
-  | 'user-stopped-typing'
-  | 'joined-channel'     // ack for joining a channel
-  | 'presence'           // synthetic: emitted locally on ws open
-  | 'channel_update'     // channel metadata changed
-  | 'error';             // server error
-
-export interface WsIncomingMessage {
-  type: WsMessageType;
-  data: Record<string, unknown>;
+// The socket lives in Rust (src-tauri/src/ws.rs) so it keeps running while the
+// window is hidden; this module forwards its frames and state to handlers.
+// Every signed-in account has a socket; only the active account's events are
+// passed on.
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { currentAccountId } from './api';
+import type { IncomingFrame, OutgoingFrame } from './protocol';
+
+/** Synthetic frame emitted locally when the socket connects or drops. */
+export interface PresenceFrame {
+  type: 'presence';
+  status: 'connected' | 'reconnecting' | 'offline';
+  // Why connecting keeps failing, when retrying alone will not fix it
+  error?: string;
 }
 
-export interface WsOutgoingMessage {
-  type: 'message' | 'typing-start' | 'typing-stop' | 'join-channel' | 'leave-channel' | 'status-update' | 'mark-read';
-  [key: string]: unknown;
+export type WsFrame = IncomingFrame | PresenceFrame;
+
+export type WsMessageType = WsFrame['type'];
+
+export interface WsIncomingMessage<T extends WsMessageType = WsMessageType> {
+  type: T;
+  data: Extract<WsFrame, { type: T }>;
 }
 
-type WsHandler = (msg: WsIncomingMessage) => void;
+export type WsOutgoingMessage = OutgoingFrame;
 
-let _ws: WebSocket | null = null;
-const _handlers: Map<WsMessageType | '*', WsHandler[]> = new Map();
-let _reconnectTimer: ReturnType<typeof setTimeout> | null = null;
-let _reconnectAttempts = 0;
-const MAX_RECONNECT_DELAY = 30000;
-let _intentionalClose = false;
+interface WsStatus {
+  account: string;
+  state: 'connecting' | 'connected' | 'reconnecting' | 'offline';
+  attempt: number;
+  retryInMs: number | null;
+  // Set on a certificate pin mismatch
+  error?: string;
+}
 
-// ── Connect ──
-export function connectWs(): void {
-  if (_ws && (_ws.readyState === WebSocket.OPEN || _ws.readyState === WebSocket.CONNECTING)) {
-    return;
-  }
+interface WsLatency {
+  account: string;
+  // Round trip of the last heartbeat; null once the socket drops
+  latencyMs: number | null;
+}
 
-  _intentionalClose = false;
-  const url = getWebSocketUrl();
-  _ws = new WebSocket(url);
+// Ping cadence of the Rust socket. A pong missing for `timeoutSecs` forces a
+// reconnect. Changes apply from the next connection.
+export interface HeartbeatSettings {
+  intervalSecs: number;
+  timeoutSecs: number;
+}
 
-  _ws.onopen = () => {
-    _reconnectAttempts = 0;
-    emit({ type: 'presence', data: { status: 'connected' } });
-  };
+type WsHandler<T extends WsMessageType = WsMessageType> = (msg: WsIncomingMessage<T>) => void;
 
-  _ws.onmessage = (event) => {
-    try {
-      const raw = JSON.parse(event.data);
-      // Server sends flat JSON with `type` at top level.
-      // Normalize into { type, data } so handlers can use msg.data consistently.
-      const type = raw.type as WsMessageType;
-      emit({ type, data: raw });
-    } catch {
-      // Non-JSON messages ignored
-    }
-  };
+const _handlers: Map<WsMessageType | '*', WsHandler[]> = new Map();
+let _connected = false;
+const _latencyHandlers: ((latencyMs: number | null) => void)[] = [];
+
+// Server sends flat JSON with `type` at top level.
+// Normalize into { type, data } so handlers can use msg.data consistently.
+listen<IncomingFrame & { account: string }>('ws-frame', (event) => {
+  const { account, ...frame } = event.payload;
+  if (account !== currentAccountId()) return;
+  emit(frame as IncomingFrame);
+});
+
+listen<WsStatus>('ws-state', (event) => {
+  const { account, state, error } = event.payload;
+  if (account !== currentAccountId()) return;
+  _connected = state === 'connected';
+  if (state === 'connected') {
+    emit({ type: 'presence', status: 'connected' });
+  } else if (state === 'reconnecting') {
+    emit({ type: 'presence', status: navigator.onLine ? 'reconnecting' : 'offline', error });
+  } else if (state === 'offline') {
+    emit({ type: 'presence', status: 'offline' });
+  }
+});
 
-  _ws.onclose = () => {
-    _ws = null;
-    if (!_intentionalClose) {
-      scheduleReconnect();
-    }
-  };
+listen<WsLatency>('ws-latency', (event) => {
+  const { account, latencyMs } = event.payload;
+  if (account !== currentAccountId()) return;
+  _latencyHandlers.forEach((h) => h(latencyMs));
+});
 
-  _ws.onerror = () => {
-    // onclose will fire after onerror
-  };
+// ── Connect ──
+export function connectWs(): void {
+  invoke('ws_connect').catch((err) => console.warn('ws_connect failed', err));
 }
 
 // ── Disconnect ──
 export function disconnectWs(): void {
-  _intentionalClose = true;
-  if (_reconnectTimer) {
-    clearTimeout(_reconnectTimer);
-    _reconnectTimer = null;
-  }
-  if (_ws) {
-    _ws.close();
-    _ws = null;
-  }
+  invoke('ws_disconnect').catch((err) => console.warn('ws_disconnect failed', err));
 }
 
 // ── Send ──
 export function sendWs(msg: WsOutgoingMessage): void {
-  if (_ws && _ws.readyState === WebSocket.OPEN) {
-    _ws.send(JSON.stringify(msg));
-  }
+  if (!_connected) return;
+  invoke('ws_send', { frame: msg }).catch(() => {
+    // Dropped between the state event and the send
+  });
 }
 
 // ── Event system ──
-function emit(msg: WsIncomingMessage): void {
+function emit(frame: WsFrame): void {
+  const msg = { type: frame.type, data: frame } as WsIncomingMessage;
   // Type-specific handlers
   const typeHandlers = _handlers.get(msg.type);
   if (typeHandlers) {
@@ -108,32 +116,45 @@ function emit(msg: WsIncomingMessage): void {
   }
 }
 
-export function onWs(type: WsMessageType | '*', handler: WsHandler): () => void {
+export function onWs<T extends WsMessageType>(type: T | '*', handler: WsHandler<T>): () => void {
+  const stored = handler as WsHandler;
   if (!_handlers.has(type)) {
     _handlers.set(type, []);
   }
-  _handlers.get(type)!.push(handler);
+  _handlers.get(type)!.push(stored);
 
   // Return unsubscribe function
   return () => {
     const arr = _handlers.get(type);
     if (arr) {
-      const idx = arr.indexOf(handler);
+      const idx = arr.indexOf(stored);
       if (idx >= 0) arr.splice(idx, 1);
     }
   };
 }
 
-// ── Reconnect with exponential backoff ──
-function scheduleReconnect(): void {
-  const delay = Math.min(1000 * Math.pow(2, _reconnectAttempts), MAX_RECONNECT_DELAY);
-  _reconnectAttempts++;
-  _reconnectTimer = setTimeout(() => {
-    connectWs();
-  }, delay);
-}
-
 // ── Status ──
 export function isWsConnected(): boolean {
-  return _ws !== null && _ws.readyState === WebSocket.OPEN;
+  return _connected;
+}
+
+// ── Latency ──
+export function getWsLatency(): Promise<number | null> {
+  return invoke<number | null>('ws_latency');
+}
+
+export function onWsLatency(handler: (latencyMs: number | null) => void): () => void {
+  _latencyHandlers.push(handler);
+  return () => {
+    const idx = _latencyHandlers.indexOf(handler);
+    if (idx >= 0) _latencyHandlers.splice(idx, 1);
+  };
+}
+
+export function getHeartbeatSettings(): Promise<HeartbeatSettings> {
+  return invoke<HeartbeatSettings>('get_heartbeat_settings');
+}
+
+export function setHeartbeatSettings(settings: HeartbeatSettings): Promise<void> {
+  return invoke('set_heartbeat_settings', { settings });
 }
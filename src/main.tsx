@@ -3,11 +3,19 @@ import ReactDOM from "react-dom/client";
 import { AppProvider } from "./context/AppContext";
 import App from "./App";
 import "./index.css";
+import { listen } from "@tauri-apps/api/event";
+import { loadSession } from "./lib/api";
 
-ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
-  <React.StrictMode>
-    <AppProvider>
-      <App />
-    </AppProvider>
-  </React.StrictMode>,
-);
+// Rust has already switched; start over as the new account or on the new server
+listen("account-switched", () => window.location.reload());
+listen("profile-changed", () => window.location.reload());
+
+loadSession().finally(() => {
+  ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
+    <React.StrictMode>
+      <AppProvider>
+        <App />
+      </AppProvider>
+    </React.StrictMode>,
+  );
+});